@@ -6,10 +6,8 @@ const stocks = [
     { symbol: 'TSLA', name: 'Tesla, Inc.' }
 ];
 
-let currentPrice = 0;
-let autoRefreshInterval = null;  // Store the interval ID
-let countdownInterval = null;    // Store the countdown interval ID
-let nextRefreshTime = null;      // Store the next refresh timestamp
+let currentPrice = null;
+let watchedJob = null;           // The server side watch job of the selected stock
 
 function initializeStockSelector() {
     const stockSelect = document.getElementById('stockSelect');
@@ -22,6 +20,29 @@ function initializeStockSelector() {
     document.getElementById('stockName').textContent = initialStock.name;
 }
 
+// Lookback in bars for the interval options in index.html.
+const intervalLookbacks = { '5min': 78, '60min': 35, 'daily': 63, 'weekly': 52 };
+
+function getPriceHistory() {
+    const interval = document.getElementById('intervalSelect').value;
+    const lookback = intervalLookbacks[interval];
+    return lookback ? { interval, lookback } : { interval };
+}
+
+function getOrderStyle() {
+    return {
+        order_style: document.getElementById('orderStyleSelect').value,
+        time_in_force: document.getElementById('timeInForceSelect').value
+    };
+}
+
+function formatAdvice(advice) {
+    if (!advice) return '';
+    const confidence = Math.round((Number(advice.confidence) || 0) * 100);
+    const factors = (advice.key_factors || []).join('; ');
+    return ` - AI: ${advice.action} (${confidence}% confident) ${advice.rationale}` + (factors ? ` [${factors}]` : '');
+}
+
 function getFormattedTimestamp() {
     const now = new Date();
     return now.toLocaleDateString() + " " + now.toLocaleTimeString();
@@ -31,22 +52,17 @@ function updateOutput(message) {
     const outputDiv = document.getElementById('output');
     const timestamp = getFormattedTimestamp();
     outputDiv.textContent = `[${timestamp}] ${message}`;
-    
-    // Update countdown if auto-refresh is active
-    if (autoRefreshInterval) {
-        updateCountdown();
-    }
 }
 
 function analyzeInvestment() {
     const amount = document.getElementById('amountInput').value;
     const ticker = document.getElementById('stockSelect').value;
-    updateOutput(`Analyzing ${ticker} with €${amount}...`);
+    updateOutput(`Analyzing ${ticker} with ${amount}...`);
 
     fetch('/analyze', {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
-        body: JSON.stringify({ ticker, amount: parseFloat(amount) })
+        body: JSON.stringify({ ticker, amount: parseFloat(amount), ...getPriceHistory(), ...getOrderStyle() })
     })
         .then(response => {
             if (!response.ok) {
@@ -61,10 +77,12 @@ function analyzeInvestment() {
             } else {
                 // Handle successful analysis response
                 const quantity = Number(data.quantity) || 0;
-                const price = Number(data.price) || 0;
-                currentPrice = price;
-                updateOutput(`${data.message} - Order Type: ${data.order_type}`);
-                updateStockInfo(getSelectedStock(), amount);
+                currentPrice = data.price;
+                const orderStatus = data.order_id ? ` - Order ${data.order_id}: ${data.order_status}` : '';
+                updateOutput(`${data.message} - Order Type: ${data.order_type}${orderStatus}${formatAdvice(data.advice)}`);
+                if (data.order_type !== 'Hold') {
+                    updateStockInfo(getSelectedStock(), amount);
+                }
             }
         })
         .catch(error => {
@@ -86,44 +104,21 @@ function getSelectedStock() {
 }
 
 function updateStockInfo(stock, amount) {
-    const amt = Number(amount) || 0;
+    const currency = currentPrice ? currentPrice.currency : 'USD';
     document.getElementById('stockName').textContent = stock.name;
-    document.getElementById('investedAmount').textContent = `€${amt.toFixed(2)}`;
-    document.getElementById('currentPrice').textContent = `€${Number(currentPrice).toFixed(2)}`;
-}
-
-function updateCountdown() {
-    if (!nextRefreshTime) return;
-    
-    const now = new Date().getTime();
-    const timeLeft = nextRefreshTime - now;
-    
-    if (timeLeft <= 0) {
-        return;  // The refresh function will handle the reset
-    }
-
-    // Calculate minutes and seconds
-    const minutes = Math.floor(timeLeft / (1000 * 60));
-    const seconds = Math.floor((timeLeft % (1000 * 60)) / 1000);
-    
-    // Get the current output text, removing any existing countdown
-    const outputDiv = document.getElementById('output');
-    let currentText = outputDiv.textContent;
-    currentText = currentText.replace(/\s*\(Next refresh in:.*\)$/, '');
-    
-    // Append the countdown
-    outputDiv.textContent = `${currentText} (Next refresh in: ${minutes}m ${seconds}s)`;
+    document.getElementById('investedAmount').textContent = formatMoney({ amount: Number(amount) || 0, currency });
+    document.getElementById('currentPrice').textContent = formatMoney(currentPrice);
 }
 
 function refreshStockData() {
     const amount = document.getElementById('amountInput').value;
     const ticker = document.getElementById('stockSelect').value;
-    updateOutput(`Analyzing ${ticker} with €${amount}...`);
+    updateOutput(`Analyzing ${ticker} with ${amount}...`);
 
     fetch('/refresh', {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
-        body: JSON.stringify({ ticker })
+        body: JSON.stringify({ ticker, ...getPriceHistory() })
     })
         .then(response => {
             if (!response.ok) {
@@ -138,13 +133,11 @@ function refreshStockData() {
                 updateOutput(`[${data.error_type}] ${data.message}: ${data.details || ''}`);
             } else {
                 // Handle successful refresh response
-                const invested = Number(data.invested_amount) || 0;
-                const currPrice = Number(data.current_price) || 0;
                 const stock = getSelectedStock();
                 document.getElementById('stockName').textContent = stock.name;
-                document.getElementById('investedAmount').textContent = `€${invested.toFixed(2)}`;
-                document.getElementById('currentPrice').textContent = `€${currPrice.toFixed(2)}`;
-                updateOutput(`Action taken: ${data.action_taken}`);
+                document.getElementById('investedAmount').textContent = formatMoney(data.invested_amount);
+                document.getElementById('currentPrice').textContent = formatMoney(data.current_price);
+                updateOutput(`Action taken: ${data.action_taken}${formatAdvice(data.advice)}`);
             }
         })
         .catch(error => {
@@ -152,61 +145,115 @@ function refreshStockData() {
         });
 }
 
+function formatMoney(money) {
+    if (money === null || money === undefined) {
+        return '-';
+    }
+    return new Intl.NumberFormat(undefined, { style: 'currency', currency: money.currency })
+        .format(Number(money.amount));
+}
+
+function loadPortfolio() {
+    fetch('/portfolio')
+        .then(response => response.json())
+        .then(data => {
+            if (data.error_type) {
+                updateOutput(`[${data.error_type}] ${data.message}: ${data.details || ''}`);
+                return;
+            }
+            document.getElementById('portfolioCash').textContent = formatMoney(data.cash);
+            document.getElementById('portfolioNetLiquidation').textContent = formatMoney(data.net_liquidation_value);
+            document.getElementById('portfolioPositions').innerHTML = data.positions
+                .map(position => `<div class="info-row">
+                        <span>${position.stock_name} (${position.quantity} @ ${formatMoney(position.average_cost)})</span>
+                        <span>${formatMoney(position.market_value)}, P&amp;L ${formatMoney(position.unrealized_pnl)}</span>
+                    </div>`)
+                .join('');
+        })
+        .catch(error => {
+            updateOutput(`Error: ${error.message}`);
+        });
+}
+
+function updateAutoRefreshToggle() {
+    const toggleButton = document.getElementById('autoRefreshToggle');
+    if (watchedJob) {
+        toggleButton.innerHTML = `<span class="material-icons">stop</span> Stop Auto-Reanalyze (every ${watchedJob.every_minutes}m)`;
+        toggleButton.classList.add('active');
+    } else {
+        toggleButton.innerHTML = '<span class="material-icons">play_arrow</span> Start Auto-Reanalyze';
+        toggleButton.classList.remove('active');
+    }
+}
+
+// The server keeps reanalyzing watched stocks when this tab is closed.
+function loadWatchedJob() {
+    const ticker = document.getElementById('stockSelect').value;
+    fetch('/watch')
+        .then(response => response.json())
+        .then(jobs => {
+            watchedJob = jobs.find(job => job.ticker === ticker) || null;
+            updateAutoRefreshToggle();
+        })
+        .catch(error => {
+            updateOutput(`Error: ${error.message}`);
+        });
+}
+
 function startAutoRefresh(minutes) {
-    // Clear any existing intervals
-    stopAutoRefresh();
-    
-    // Convert minutes to milliseconds
-    const interval = minutes * 60 * 1000;
-    
-    // Set next refresh time
-    nextRefreshTime = new Date().getTime() + interval;
-    
-    // Set new intervals
-    autoRefreshInterval = setInterval(() => {
-        refreshStockData();
-        nextRefreshTime = new Date().getTime() + interval;
-    }, interval);
-    
-    // Start countdown update
-    countdownInterval = setInterval(updateCountdown, 1000);
+    const ticker = document.getElementById('stockSelect').value;
+    fetch('/watch', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ ticker, every_minutes: minutes, ...getPriceHistory() })
+    })
+        .then(response => response.json())
+        .then(data => {
+            if (data.error_type) {
+                updateOutput(`[${data.error_type}] ${data.message}: ${data.details || ''}`);
+            } else {
+                watchedJob = data;
+                updateAutoRefreshToggle();
+                updateOutput(`The server reanalyzes ${ticker} every ${data.every_minutes} minutes`);
+            }
+        })
+        .catch(error => {
+            updateOutput(`Error: ${error.message}`);
+        });
 }
 
 function stopAutoRefresh() {
-    if (autoRefreshInterval) {
-        clearInterval(autoRefreshInterval);
-        clearInterval(countdownInterval);
-        autoRefreshInterval = null;
-        countdownInterval = null;
-        nextRefreshTime = null;
-    }
+    const ticker = document.getElementById('stockSelect').value;
+    fetch(`/watch/${encodeURIComponent(ticker)}`, { method: 'DELETE' })
+        .then(response => response.json())
+        .then(data => {
+            watchedJob = null;
+            updateAutoRefreshToggle();
+            if (data.error_type) {
+                updateOutput(`[${data.error_type}] ${data.message}: ${data.details || ''}`);
+            } else {
+                updateOutput(`Stopped reanalyzing ${ticker}`);
+            }
+        })
+        .catch(error => {
+            updateOutput(`Error: ${error.message}`);
+        });
 }
 
 function toggleAutoRefresh() {
     const minutes = parseInt(document.getElementById('refreshInterval').value) || 5;
-    const toggleButton = document.getElementById('autoRefreshToggle');
-    
-    if (autoRefreshInterval) {
+    if (watchedJob) {
         stopAutoRefresh();
-        toggleButton.innerHTML = '<span class="material-icons">play_arrow</span> Start Auto-Refresh';
-        toggleButton.classList.remove('active');
     } else {
         startAutoRefresh(minutes);
-        toggleButton.innerHTML = '<span class="material-icons">stop</span> Stop Auto-Refresh';
-        toggleButton.classList.add('active');
     }
 }
 
-// Modify the last part to include initialization of auto-refresh controls
 document.addEventListener('DOMContentLoaded', () => {
     initializeStockSelector();
     refreshStockData();
-    
-    // Stop auto-refresh when changing stocks
-    document.getElementById('stockSelect').addEventListener('change', () => {
-        stopAutoRefresh();
-        const toggleButton = document.getElementById('autoRefreshToggle');
-        toggleButton.innerHTML = '<span class="material-icons">play_arrow</span> Start Auto-Refresh';
-        toggleButton.classList.remove('active');
-    });
-});
\ No newline at end of file
+    loadWatchedJob();
+    loadPortfolio();
+
+    document.getElementById('stockSelect').addEventListener('change', loadWatchedJob);
+});
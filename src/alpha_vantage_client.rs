@@ -0,0 +1,363 @@
+use crate::errors::AppErrors;
+use crate::ib_connection::backoff;
+use crate::models::{
+    Config, Currency, ExchangeRate, News, PriceHistory, PriceInterval, StockPricePerformance,
+};
+use crate::rate_limiter::RateLimiter;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use reqwest::Client;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+use tokio::time::sleep;
+
+/// Alpha Vantage client that keeps to the configured call budget and retries
+/// throttled and failed requests. Clones share the budget. The request timeout
+/// applies to every request on its own, not to the waits for the budget.
+#[derive(Clone)]
+pub struct AlphaVantageClient {
+    base_url: String,
+    api_key: String,
+    client: Client,
+    rate_limiter: Arc<RateLimiter>,
+    max_retries: u32,
+    request_timeout: StdDuration,
+}
+
+#[derive(Deserialize)]
+struct TimeSeriesBar {
+    #[serde(rename = "1. open")]
+    open: Decimal,
+    #[serde(rename = "2. high")]
+    high: Decimal,
+    #[serde(rename = "3. low")]
+    low: Decimal,
+    #[serde(rename = "4. close")]
+    close: Decimal,
+    #[serde(rename = "5. volume")]
+    volume: String,
+}
+
+#[derive(Deserialize)]
+struct NewsFeed {
+    feed: Vec<NewsItem>,
+}
+
+#[derive(Deserialize)]
+struct NewsItem {
+    title: String,
+    summary: String,
+    time_published: String,
+}
+
+#[derive(Deserialize)]
+struct ExchangeRateResponse {
+    #[serde(rename = "Realtime Currency Exchange Rate")]
+    quote: ExchangeRateQuote,
+}
+
+#[derive(Deserialize)]
+struct ExchangeRateQuote {
+    #[serde(rename = "1. From_Currency Code")]
+    from: Currency,
+    #[serde(rename = "3. To_Currency Code")]
+    to: Currency,
+    #[serde(rename = "5. Exchange Rate")]
+    rate: Decimal,
+}
+
+/// How much of a time series to request. The compact output size only holds
+/// the latest 100 bars.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputSize {
+    Compact,
+    Full,
+}
+
+impl OutputSize {
+    pub const COMPACT_BARS: usize = 100;
+
+    pub fn for_lookback(lookback: Option<usize>) -> Self {
+        match lookback {
+            Some(lookback) if lookback <= Self::COMPACT_BARS => OutputSize::Compact,
+            _ => OutputSize::Full,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            OutputSize::Compact => "compact",
+            OutputSize::Full => "full",
+        }
+    }
+}
+
+impl AlphaVantageClient {
+    pub fn new(config: &Config) -> Self {
+        let limits = &config.alpha_vantage_limits;
+        AlphaVantageClient {
+            base_url: config.alpha_vantage_base_url.trim_end_matches('/').to_string(),
+            api_key: config.alpha_vantage_api_key.clone(),
+            client: Client::new(),
+            rate_limiter: Arc::new(RateLimiter::new(limits.calls_per_minute, limits.calls_per_day)),
+            max_retries: limits.max_retries,
+            request_timeout: StdDuration::from_secs(config.request_timeout_seconds),
+        }
+    }
+
+    pub async fn get_time_series(
+        &self,
+        ticker_symbol: &str,
+        price_history: PriceHistory,
+    ) -> Result<Vec<StockPricePerformance>, AppErrors> {
+        let output_size = OutputSize::for_lookback(price_history.lookback);
+        let mut bars =
+            self.fetch_time_series(ticker_symbol, price_history.interval, output_size).await?;
+        if let Some(lookback) = price_history.lookback {
+            bars.drain(..bars.len().saturating_sub(lookback));
+        }
+        Ok(bars)
+    }
+
+    /// Every bar of the requested output size, oldest first.
+    pub async fn fetch_time_series(
+        &self,
+        ticker_symbol: &str,
+        interval: PriceInterval,
+        output_size: OutputSize,
+    ) -> Result<Vec<StockPricePerformance>, AppErrors> {
+        let minutes = interval.intraday_minutes().map(|minutes| format!("{minutes}min"));
+        let mut params = vec![
+            ("function", interval.alpha_vantage_function()),
+            ("symbol", ticker_symbol),
+            ("outputsize", output_size.as_str()),
+        ];
+        if let Some(minutes) = &minutes {
+            params.push(("interval", minutes));
+        }
+
+        let body = self.query(&params).await?;
+        parse_time_series(&body)
+    }
+
+    pub async fn get_news_sentiment(&self, ticker_symbol: &str) -> Result<Vec<News>, AppErrors> {
+        let body = self
+            .query(&[("function", "NEWS_SENTIMENT"), ("tickers", ticker_symbol)])
+            .await?;
+        parse_news(&body)
+    }
+
+    pub async fn get_exchange_rate(
+        &self,
+        from: Currency,
+        to: Currency,
+    ) -> Result<ExchangeRate, AppErrors> {
+        let body = self
+            .query(&[
+                ("function", "CURRENCY_EXCHANGE_RATE"),
+                ("from_currency", from.code()),
+                ("to_currency", to.code()),
+            ])
+            .await?;
+        parse_exchange_rate(&body)
+    }
+
+    async fn query(&self, params: &[(&str, &str)]) -> Result<String, AppErrors> {
+        retry_with_backoff(self.max_retries, || async {
+            self.rate_limiter.acquire().await?;
+            let body = self
+                .client
+                .get(format!("{}/query", self.base_url))
+                .query(params)
+                .query(&[("apikey", &self.api_key)])
+                .timeout(self.request_timeout)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|error| self.request_error(error))?
+                .text()
+                .await
+                .map_err(|error| self.request_error(error))?;
+            // Throttling is only told in the body, so it has to be checked
+            // before the parsing to be retried.
+            if let Ok(json) = serde_json::from_str::<Value>(&body) {
+                check_for_api_message(&json)?;
+            }
+            Ok(body)
+        })
+        .await
+    }
+
+    fn request_error(&self, error: reqwest::Error) -> AppErrors {
+        if error.is_timeout() {
+            return AppErrors::TimeoutError(format!(
+                "The Alpha Vantage call didn't finish within {}s",
+                self.request_timeout.as_secs_f64()
+            ));
+        }
+        AppErrors::NetworkError(error.to_string())
+    }
+}
+
+/// Runs `call` again after a throttled or failed request, waiting longer with
+/// every retry. Daily limits, bad keys and unknown symbols are returned right
+/// away.
+pub async fn retry_with_backoff<T, F, Fut>(max_retries: u32, call: F) -> Result<T, AppErrors>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, AppErrors>>,
+{
+    let mut retries = 0;
+    loop {
+        match call().await {
+            Err(error) if retries < max_retries && is_retryable(&error) => {
+                retries += 1;
+                let wait = backoff(retries);
+                eprintln!("Retrying the Alpha Vantage call in {}s: {error}", wait.as_secs());
+                sleep(wait).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_retryable(error: &AppErrors) -> bool {
+    match error {
+        AppErrors::NetworkError(_) => true,
+        AppErrors::RateLimitError(message) => {
+            !message.contains("per day") || message.contains("per minute")
+        }
+        _ => false,
+    }
+}
+
+/// Alpha Vantage answers throttled, unauthorized and invalid requests with
+/// HTTP 200 and a single `Note`, `Information` or `Error Message` field.
+fn check_for_api_message(body: &Value) -> Result<(), AppErrors> {
+    for field in ["Error Message", "Note", "Information"] {
+        if let Some(message) = body.get(field).and_then(Value::as_str) {
+            return Err(api_message_error(field, message));
+        }
+    }
+    Ok(())
+}
+
+/// Tells the kinds of API messages apart by their wording. Unknown symbols are
+/// answered like any other invalid call.
+fn api_message_error(field: &str, message: &str) -> AppErrors {
+    let text = format!("Alpha Vantage responded with {field}: {message}");
+    let message = message.to_lowercase();
+    if message.contains("apikey is invalid") || message.contains("api key is invalid") {
+        AppErrors::InvalidApiKeyError(text)
+    } else if field == "Note"
+        || ["rate limit", "call frequency", "requests per day", "spreading out"]
+            .iter()
+            .any(|phrase| message.contains(phrase))
+    {
+        AppErrors::RateLimitError(text)
+    } else if message.contains("invalid api call") || message.contains("invalid inputs") {
+        AppErrors::UnknownSymbolError(text)
+    } else {
+        AppErrors::GetStockDataError(text)
+    }
+}
+
+fn parse_json(body: &str) -> Result<Value, AppErrors> {
+    let json: Value = serde_json::from_str(body)
+        .map_err(|error| AppErrors::GetStockDataError(error.to_string()))?;
+    check_for_api_message(&json)?;
+    Ok(json)
+}
+
+/// Parses any `TIME_SERIES_*` response into bars ordered from oldest to newest.
+pub fn parse_time_series(body: &str) -> Result<Vec<StockPricePerformance>, AppErrors> {
+    let json = parse_json(body)?;
+    let series = json
+        .as_object()
+        .and_then(|fields| {
+            fields
+                .iter()
+                .find(|(key, _)| key.contains("Time Series"))
+                .map(|(_, series)| series.clone())
+        })
+        .ok_or_else(|| {
+            AppErrors::GetStockDataError(
+                "The Alpha Vantage response did not contain a time series".to_string(),
+            )
+        })?;
+    let bars: BTreeMap<String, TimeSeriesBar> = serde_json::from_value(series)
+        .map_err(|error| AppErrors::GetStockDataError(error.to_string()))?;
+    let mut bars = bars
+        .into_iter()
+        .map(|(date, bar)| {
+            Ok(StockPricePerformance {
+                date: parse_bar_date(&date)?,
+                open: bar.open,
+                high: bar.high,
+                low: bar.low,
+                close: bar.close,
+                volume: bar.volume.parse().map_err(|_| {
+                    AppErrors::GetStockDataError(format!(
+                        "Invalid volume {} for bar {date}",
+                        bar.volume
+                    ))
+                })?,
+            })
+        })
+        .collect::<Result<Vec<StockPricePerformance>, AppErrors>>()?;
+    bars.sort_by_key(|bar| bar.date);
+    Ok(bars)
+}
+
+/// Daily and longer series are keyed by `YYYY-MM-DD`, intraday series by
+/// `YYYY-MM-DD HH:MM:SS`.
+fn parse_bar_date(date: &str) -> Result<NaiveDateTime, AppErrors> {
+    NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map(|date| date.and_time(NaiveTime::MIN))
+        })
+        .map_err(|error| AppErrors::GetStockDataError(format!("Invalid bar date {date}: {error}")))
+}
+
+pub fn parse_news(body: &str) -> Result<Vec<News>, AppErrors> {
+    let json = parse_json(body)?;
+    let news_feed = serde_json::from_value::<NewsFeed>(json)
+        .map_err(|error| AppErrors::GetStockDataError(error.to_string()))?;
+    news_feed
+        .feed
+        .into_iter()
+        .map(|item| {
+            Ok(News {
+                time_published: parse_news_timestamp(&item.time_published)?,
+                title: item.title,
+                summary: item.summary,
+            })
+        })
+        .collect()
+}
+
+/// News timestamps look like `20250204T025520`.
+pub fn parse_news_timestamp(timestamp: &str) -> Result<NaiveDateTime, AppErrors> {
+    NaiveDateTime::parse_from_str(timestamp, "%Y%m%dT%H%M%S").map_err(|error| {
+        AppErrors::GetStockDataError(format!("Invalid news timestamp {timestamp}: {error}"))
+    })
+}
+
+pub fn parse_exchange_rate(body: &str) -> Result<ExchangeRate, AppErrors> {
+    let json = parse_json(body)?;
+    let quote = serde_json::from_value::<ExchangeRateResponse>(json)
+        .map_err(|error| AppErrors::GetExchangeRateError(error.to_string()))?
+        .quote;
+    if quote.rate <= Decimal::ZERO {
+        return Err(AppErrors::GetExchangeRateError(format!(
+            "The {} to {} exchange rate isn't positive: {}",
+            quote.from, quote.to, quote.rate
+        )));
+    }
+    Ok(ExchangeRate { from: quote.from, to: quote.to, rate: quote.rate })
+}
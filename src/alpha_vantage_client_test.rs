@@ -0,0 +1,373 @@
+use crate::alpha_vantage_fixture_server::AlphaVantageFixtureServer;
+use crate::config::ConfigLayer;
+use crate::ib_connection::IbConnection;
+use crate::models::Config;
+use crate::services::TradingApiServiceLive;
+use std::sync::Arc;
+
+fn fixture_config(server: &AlphaVantageFixtureServer) -> Config {
+    ConfigLayer {
+        alpha_vantage_api_key: Some("demo".to_string()),
+        alpha_vantage_base_url: Some(server.base_url.clone()),
+        ollama_model: Some("deepseek-r1:1.5b".to_string()),
+        alpha_vantage_max_retries: Some(0),
+        ..ConfigLayer::defaults()
+    }
+    .build()
+    .unwrap()
+}
+
+fn fixture_trading_service(server: &AlphaVantageFixtureServer) -> TradingApiServiceLive {
+    let config = fixture_config(server);
+    let ib_connection =
+        Arc::new(IbConnection::new(&config.interactive_brokers_connection_url_with_port));
+    TradingApiServiceLive::new(config, ib_connection)
+}
+
+mod parsing {
+    use crate::alpha_vantage_client::{
+        parse_exchange_rate, parse_news, parse_news_timestamp, parse_time_series,
+    };
+    use crate::alpha_vantage_fixture_server::fixture;
+    use crate::errors::AppErrors;
+    use crate::models::Currency;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_parse_time_series_success() {
+        let bars = parse_time_series(&fixture("TIME_SERIES_MONTHLY_AAPL")).unwrap();
+        let dates: Vec<String> = bars.iter().map(|bar| bar.date.date().to_string()).collect();
+        assert_eq!(dates, vec!["2024-11-29", "2024-12-31", "2025-01-31", "2025-02-04"]);
+        let latest = bars.last().unwrap();
+        assert_eq!(latest.open, Decimal::new(22999, 2));
+        assert_eq!(latest.high, Decimal::new(23313, 2));
+        assert_eq!(latest.low, Decimal::new(2257, 1));
+        assert_eq!(latest.close, Decimal::new(2328, 1));
+        assert_eq!(latest.volume, 118_765_432)
+    }
+
+    #[test]
+    fn test_parse_time_series_intraday_timestamps() {
+        let bars = parse_time_series(
+            r#"{"Time Series (5min)": {
+                "2025-02-04 15:55:00": {"1. open": "232.5", "2. high": "232.9", "3. low": "232.4", "4. close": "232.8", "5. volume": "1200"},
+                "2025-02-04 15:50:00": {"1. open": "232.1", "2. high": "232.6", "3. low": "232.0", "4. close": "232.5", "5. volume": "900"}
+            }}"#,
+        )
+        .unwrap();
+        assert_eq!(bars[0].date.to_string(), "2025-02-04 15:50:00");
+        assert_eq!(bars[1].close, Decimal::new(2328, 1))
+    }
+
+    #[test]
+    fn test_parse_time_series_invalid_price() {
+        let maybe_bars = parse_time_series(
+            r#"{"Monthly Time Series": {"2025-02-04": {"1. open": "n/a", "2. high": "1", "3. low": "1", "4. close": "1", "5. volume": "1"}}}"#,
+        );
+        assert!(maybe_bars.is_err())
+    }
+
+    #[test]
+    fn test_parse_time_series_rate_limit_note() {
+        let maybe_bars = parse_time_series(&fixture("TIME_SERIES_MONTHLY_THROTTLED"));
+        let error = maybe_bars.unwrap_err();
+        assert!(matches!(error, AppErrors::RateLimitError(_)));
+        assert!(error.to_string().contains("5 calls per minute"))
+    }
+
+    #[test]
+    fn test_parse_time_series_error_message() {
+        let maybe_bars = parse_time_series(&fixture("ERROR_MESSAGE"));
+        let error = maybe_bars.unwrap_err();
+        assert!(matches!(error, AppErrors::UnknownSymbolError(_)));
+        assert!(error.to_string().contains("Invalid API call"))
+    }
+
+    #[test]
+    fn test_parse_time_series_invalid_api_key() {
+        let maybe_bars = parse_time_series(&fixture("INVALID_API_KEY"));
+        assert!(matches!(maybe_bars, Err(AppErrors::InvalidApiKeyError(_))))
+    }
+
+    #[test]
+    fn test_parse_time_series_missing_series() {
+        let maybe_bars = parse_time_series("{\"Meta Data\": {}}");
+        assert!(maybe_bars.is_err())
+    }
+
+    #[test]
+    fn test_parse_news_success() {
+        let news = parse_news(&fixture("NEWS_SENTIMENT_AAPL")).unwrap();
+        assert_eq!(news.len(), 2);
+        assert_eq!(
+            news[0].time_published,
+            NaiveDate::from_ymd_opt(2025, 2, 4).unwrap().and_hms_opt(2, 55, 20).unwrap()
+        );
+        assert!(news[1].title.starts_with("Apple Earnings Beat"))
+    }
+
+    #[test]
+    fn test_parse_news_timestamp_invalid() {
+        let maybe_timestamp = parse_news_timestamp("2025-02-04 02:55:20");
+        assert!(maybe_timestamp.unwrap_err().to_string().contains("Invalid news timestamp"))
+    }
+
+    #[test]
+    fn test_parse_news_daily_limit_information() {
+        let maybe_news = parse_news(&fixture("NEWS_SENTIMENT_DAILYLIMIT"));
+        let error = maybe_news.unwrap_err();
+        assert!(matches!(error, AppErrors::RateLimitError(_)));
+        assert!(error.to_string().contains("25 requests per day"))
+    }
+
+    #[test]
+    fn test_parse_news_invalid_json() {
+        let maybe_news = parse_news("<html>Bad Gateway</html>");
+        assert!(maybe_news.is_err())
+    }
+
+    #[test]
+    fn test_parse_exchange_rate_success() {
+        let exchange_rate = parse_exchange_rate(&fixture("CURRENCY_EXCHANGE_RATE_EURUSD")).unwrap();
+        assert_eq!(exchange_rate.from, "EUR".parse::<Currency>().unwrap());
+        assert_eq!(exchange_rate.to, Currency::USD);
+        assert_eq!(exchange_rate.rate, Decimal::new(10842, 4))
+    }
+
+    #[test]
+    fn test_parse_exchange_rate_not_positive() {
+        let maybe_exchange_rate = parse_exchange_rate(
+            r#"{"Realtime Currency Exchange Rate": {
+                "1. From_Currency Code": "EUR",
+                "3. To_Currency Code": "USD",
+                "5. Exchange Rate": "0.00000000"
+            }}"#,
+        );
+        assert!(matches!(maybe_exchange_rate, Err(AppErrors::GetExchangeRateError(_))))
+    }
+}
+
+mod fixture_server {
+    use super::{fixture_config, fixture_trading_service};
+    use crate::alpha_vantage_client::AlphaVantageClient;
+    use crate::alpha_vantage_fixture_server::AlphaVantageFixtureServer;
+    use crate::errors::AppErrors;
+    use crate::ib_connection::IbConnection;
+    use crate::models::{Config, Currency, ExchangeRate, Instrument, PriceHistory, PriceInterval};
+    use crate::services::{TradingApiService, TradingApiServiceLive};
+    use rust_decimal::Decimal;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_get_stock_data_against_fixture_server_success() {
+        let server = AlphaVantageFixtureServer::start().await;
+        let trading_service = fixture_trading_service(&server);
+        let stock_data = trading_service
+            .get_stock_data(Instrument::stock("AAPL"), PriceHistory::default())
+            .await
+            .unwrap();
+        assert_eq!(stock_data.price_history.interval, PriceInterval::Monthly);
+        assert_eq!(stock_data.stock_price_performance.len(), 4);
+        assert_eq!(stock_data.news.len(), 2)
+    }
+
+    #[tokio::test]
+    async fn test_get_stock_data_against_fixture_server_daily_lookback() {
+        let server = AlphaVantageFixtureServer::start().await;
+        let trading_service = fixture_trading_service(&server);
+        let price_history = PriceHistory { interval: PriceInterval::Daily, lookback: Some(3) };
+        let stock_data =
+            trading_service.get_stock_data(Instrument::stock("AAPL"), price_history).await.unwrap();
+        let dates: Vec<String> = stock_data
+            .stock_price_performance
+            .iter()
+            .map(|bar| bar.date.date().to_string())
+            .collect();
+        assert_eq!(stock_data.price_history, price_history);
+        assert_eq!(dates, vec!["2025-01-31", "2025-02-03", "2025-02-04"])
+    }
+
+    #[tokio::test]
+    async fn test_get_stock_data_against_fixture_server_rate_limited() {
+        let server = AlphaVantageFixtureServer::start().await;
+        let trading_service = fixture_trading_service(&server);
+        let maybe_stock_data = trading_service
+            .get_stock_data(Instrument::stock("THROTTLED"), PriceHistory::default())
+            .await;
+        assert!(matches!(maybe_stock_data, Err(AppErrors::RateLimitError(_))))
+    }
+
+    #[tokio::test]
+    async fn test_get_time_series_intraday_sends_interval() {
+        let server = AlphaVantageFixtureServer::start().await;
+        let client = AlphaVantageClient::new(&fixture_config(&server));
+        let bars = client
+            .get_time_series(
+                "AAPL",
+                PriceHistory { interval: PriceInterval::FiveMinutes, lookback: None },
+            )
+            .await
+            .unwrap();
+        assert_eq!(bars.len(), 4);
+        assert_eq!(bars[0].date.to_string(), "2025-02-04 15:45:00");
+        assert_eq!(bars[3].date.to_string(), "2025-02-04 16:00:00")
+    }
+
+    #[tokio::test]
+    async fn test_get_time_series_unknown_symbol() {
+        let server = AlphaVantageFixtureServer::start().await;
+        let client = AlphaVantageClient::new(&fixture_config(&server));
+        let maybe_bars = client.get_time_series("NOTATICKER", PriceHistory::default()).await;
+        let error = maybe_bars.unwrap_err();
+        assert!(matches!(error, AppErrors::UnknownSymbolError(_)));
+        assert!(error.to_string().contains("Error Message"))
+    }
+
+    #[tokio::test]
+    async fn test_get_news_sentiment_unknown_symbol() {
+        let server = AlphaVantageFixtureServer::start().await;
+        let client = AlphaVantageClient::new(&fixture_config(&server));
+        let maybe_news = client.get_news_sentiment("NOTATICKER").await;
+        let error = maybe_news.unwrap_err();
+        assert!(matches!(error, AppErrors::UnknownSymbolError(_)));
+        assert!(error.to_string().contains("Invalid inputs"))
+    }
+
+    #[tokio::test]
+    async fn test_get_time_series_invalid_api_key() {
+        let server = AlphaVantageFixtureServer::start().await;
+        let config =
+            Config { alpha_vantage_api_key: "INVALID".to_string(), ..fixture_config(&server) };
+        let maybe_bars =
+            AlphaVantageClient::new(&config).get_time_series("AAPL", PriceHistory::default()).await;
+        assert!(matches!(maybe_bars, Err(AppErrors::InvalidApiKeyError(_))))
+    }
+
+    #[tokio::test]
+    async fn test_get_time_series_server_unavailable() {
+        let server = AlphaVantageFixtureServer::start().await;
+        let client = AlphaVantageClient::new(&fixture_config(&server));
+        let maybe_bars = client.get_time_series("UNAVAILABLE", PriceHistory::default()).await;
+        assert!(matches!(maybe_bars, Err(AppErrors::NetworkError(_))))
+    }
+
+    #[tokio::test]
+    async fn test_get_exchange_rate_against_fixture_server() {
+        let server = AlphaVantageFixtureServer::start().await;
+        let trading_service = fixture_trading_service(&server);
+        let euros: Currency = "EUR".parse().unwrap();
+        let exchange_rate = trading_service.get_exchange_rate(euros, Currency::USD).await.unwrap();
+        let maybe_exchange_rate = trading_service.get_exchange_rate(Currency::USD, euros).await;
+        assert_eq!(exchange_rate.rate, Decimal::new(10842, 4));
+        assert!(matches!(maybe_exchange_rate, Err(AppErrors::UnknownSymbolError(_))))
+    }
+
+    #[tokio::test]
+    async fn test_get_exchange_rate_same_currency_needs_no_call() {
+        let server = AlphaVantageFixtureServer::start().await;
+        let mut config = fixture_config(&server);
+        config.alpha_vantage_api_key = "INVALID".to_string();
+        let trading_service = TradingApiServiceLive::new(
+            config.clone(),
+            Arc::new(IbConnection::new(&config.interactive_brokers_connection_url_with_port)),
+        );
+        let exchange_rate =
+            trading_service.get_exchange_rate(Currency::USD, Currency::USD).await.unwrap();
+        assert_eq!(exchange_rate, ExchangeRate::identity(Currency::USD))
+    }
+
+    #[tokio::test]
+    async fn test_get_time_series_keeps_to_the_day_budget() {
+        let server = AlphaVantageFixtureServer::start().await;
+        let mut config = fixture_config(&server);
+        config.alpha_vantage_limits.calls_per_day = 1;
+        let client = AlphaVantageClient::new(&config);
+        client.get_time_series("AAPL", PriceHistory::default()).await.unwrap();
+        let maybe_news = client.clone().get_news_sentiment("AAPL").await;
+        assert!(matches!(maybe_news, Err(AppErrors::RateLimitError(_))))
+    }
+
+    #[tokio::test]
+    async fn test_get_exchange_rate_waits_for_the_minute_budget_past_the_timeout() {
+        let server = AlphaVantageFixtureServer::start().await;
+        let mut config = fixture_config(&server);
+        config.alpha_vantage_limits.calls_per_minute = 1;
+        config.request_timeout_seconds = 1;
+        let ib_connection =
+            Arc::new(IbConnection::new(&config.interactive_brokers_connection_url_with_port));
+        let trading_service = TradingApiServiceLive::new(config, ib_connection);
+        let euros: Currency = "EUR".parse().unwrap();
+        trading_service.get_exchange_rate(euros, Currency::USD).await.unwrap();
+        let maybe_waiting = tokio::time::timeout(
+            Duration::from_millis(1_500),
+            trading_service.get_exchange_rate(euros, Currency::USD),
+        )
+        .await;
+        assert!(maybe_waiting.is_err())
+    }
+}
+
+mod retries {
+    use crate::alpha_vantage_client::retry_with_backoff;
+    use crate::errors::AppErrors;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    /// Fails with the given errors, one per call, then succeeds with the number
+    /// of calls.
+    async fn run(max_retries: u32, errors: Vec<AppErrors>) -> (Result<u32, AppErrors>, u32) {
+        let calls = AtomicU32::new(0);
+        let errors = std::sync::Mutex::new(errors);
+        let result = retry_with_backoff(max_retries, || async {
+            let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            let mut errors = errors.lock().unwrap();
+            if errors.is_empty() {
+                Ok(call)
+            } else {
+                Err(errors.remove(0))
+            }
+        })
+        .await;
+        (result, calls.load(Ordering::SeqCst))
+    }
+
+    fn throttled() -> AppErrors {
+        AppErrors::RateLimitError(
+            "Our standard API call frequency is 5 calls per minute".to_string(),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_with_backoff_waits_longer_every_time() {
+        let start = Instant::now();
+        let (result, calls) =
+            run(3, vec![throttled(), AppErrors::NetworkError("Reset".to_string())]).await;
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(calls, 3);
+        assert_eq!(start.elapsed(), Duration::from_secs(1 + 2))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_with_backoff_gives_up_after_the_max_retries() {
+        let (result, calls) = run(2, vec![throttled(), throttled(), throttled()]).await;
+        assert!(matches!(result, Err(AppErrors::RateLimitError(_))));
+        assert_eq!(calls, 3)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_with_backoff_returns_lasting_errors_right_away() {
+        let errors = [
+            AppErrors::RateLimitError("Our standard API rate limit is 25 requests per day".into()),
+            AppErrors::InvalidApiKeyError("The parameter apikey is invalid".into()),
+            AppErrors::UnknownSymbolError("Invalid API call".into()),
+        ];
+        for error in errors {
+            let (result, calls) = run(3, vec![error]).await;
+            assert!(result.is_err());
+            assert_eq!(calls, 1);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use axum::extract::Query;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::get;
+use axum::Router;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// In-process stand-in for `https://www.alphavantage.co/query` that serves the
+/// recorded responses in `fixtures/alpha_vantage`, keyed by `{FUNCTION}_{TICKER}.json`
+/// and `{FUNCTION}_{FROM}{TO}.json` for currency pairs.
+/// Unknown keys fall back to `{FUNCTION}_INVALID.json` or `ERROR_MESSAGE.json`,
+/// like the real API does for unknown symbols and intraday requests without an
+/// `interval`. The API key `INVALID` is rejected and the ticker `UNAVAILABLE`
+/// answers with HTTP 503.
+pub struct AlphaVantageFixtureServer {
+    pub base_url: String,
+}
+
+impl AlphaVantageFixtureServer {
+    pub async fn start() -> Self {
+        let app = Router::new().route("/query", get(query));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        AlphaVantageFixtureServer { base_url }
+    }
+}
+
+fn fixtures_directory() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/alpha_vantage")
+}
+
+pub fn fixture(name: &str) -> String {
+    fs::read_to_string(fixtures_directory().join(format!("{name}.json")))
+        .unwrap_or_else(|error| panic!("Missing Alpha Vantage fixture {name}: {error}"))
+}
+
+async fn query(Query(params): Query<HashMap<String, String>>) -> (StatusCode, HeaderMap, String) {
+    let function = params.get("function").cloned().unwrap_or_default();
+    let ticker = match (params.get("from_currency"), params.get("to_currency")) {
+        (Some(from), Some(to)) => format!("{from}{to}"),
+        _ => params.get("symbol").or_else(|| params.get("tickers")).cloned().unwrap_or_default(),
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert("content-type", "application/json".parse().unwrap());
+    if ticker == "UNAVAILABLE" {
+        return (StatusCode::SERVICE_UNAVAILABLE, headers, String::new());
+    }
+
+    let directory = fixtures_directory();
+    let body = if params.get("apikey").is_some_and(|api_key| api_key == "INVALID") {
+        fixture("INVALID_API_KEY")
+    } else if function == "TIME_SERIES_INTRADAY" && !params.contains_key("interval") {
+        fixture("ERROR_MESSAGE")
+    } else {
+        [format!("{function}_{ticker}"), format!("{function}_INVALID")]
+            .iter()
+            .find(|name| directory.join(format!("{name}.json")).exists())
+            .map(|name| fixture(name))
+            .unwrap_or_else(|| fixture("ERROR_MESSAGE"))
+    };
+
+    (StatusCode::OK, headers, body)
+}
@@ -0,0 +1,352 @@
+use crate::alpha_vantage_client::{parse_news, parse_time_series};
+use crate::errors::AppErrors;
+use crate::models::{
+    AdviceAction, Instrument, News, OrderType, PaperTradingConfig, PriceHistory, PriceInterval,
+    StockData, StockPricePerformance,
+};
+use crate::services::AiService;
+use chrono::NaiveDateTime;
+use rust_decimal::prelude::ToPrimitive;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+
+const DEFAULT_LOOKBACK: usize = 20;
+const DEFAULT_ORDER_AMOUNT: f64 = 1_000.0;
+
+/// What to replay and how to trade it.
+#[derive(Debug, Clone)]
+pub struct BacktestSettings {
+    pub stock: Instrument,
+    /// The interval of the bars and how many of them the model sees per decision.
+    pub price_history: PriceHistory,
+    /// The amount every buy invests, capped by the cash left.
+    pub order_amount: f64,
+    pub paper_trading: PaperTradingConfig,
+}
+
+/// The `backtest` subcommand's own flags. Everything else is passed on to the
+/// configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktestArgs {
+    pub ticker: String,
+    pub time_series_path: String,
+    pub news_path: Option<String>,
+    pub price_history: PriceHistory,
+    pub order_amount: f64,
+}
+
+impl BacktestArgs {
+    pub fn parse(args: Vec<String>) -> Result<(BacktestArgs, Vec<String>), AppErrors> {
+        let mut ticker = None;
+        let mut time_series_path = None;
+        let mut news_path = None;
+        let mut interval = PriceInterval::Daily;
+        let mut lookback = DEFAULT_LOOKBACK;
+        let mut order_amount = DEFAULT_ORDER_AMOUNT;
+        let mut config_args = vec![];
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            if !matches!(
+                flag.as_str(),
+                "--ticker" | "--time-series" | "--news" | "--interval" | "--lookback" | "--amount"
+            ) {
+                config_args.push(flag);
+                config_args.extend(inline_value.or_else(|| args.next()));
+                continue;
+            }
+            let value = inline_value.or_else(|| args.next()).ok_or_else(|| {
+                AppErrors::BacktestError(format!("Missing value for command line flag: {flag}"))
+            })?;
+            let invalid = || AppErrors::BacktestError(format!("Invalid value for {flag}: {value}"));
+            match flag.as_str() {
+                "--ticker" => ticker = Some(value.trim().to_uppercase()),
+                "--time-series" => time_series_path = Some(value),
+                "--news" => news_path = Some(value),
+                "--interval" => {
+                    interval = serde_json::from_value(Value::String(value.clone()))
+                        .map_err(|_| invalid())?
+                }
+                "--lookback" => {
+                    lookback =
+                        value.parse().ok().filter(|lookback| *lookback > 0).ok_or_else(invalid)?
+                }
+                _ => {
+                    order_amount = value
+                        .parse()
+                        .ok()
+                        .filter(|amount: &f64| amount.is_finite() && *amount > 0.0)
+                        .ok_or_else(invalid)?
+                }
+            }
+        }
+
+        let missing =
+            |flag: &str| AppErrors::BacktestError(format!("Missing command line flag: {flag}"));
+        Ok((
+            BacktestArgs {
+                ticker: ticker.ok_or_else(|| missing("--ticker"))?,
+                time_series_path: time_series_path.ok_or_else(|| missing("--time-series"))?,
+                news_path,
+                price_history: PriceHistory { interval, lookback: Some(lookback) },
+                order_amount,
+            },
+            config_args,
+        ))
+    }
+
+    /// Reads the saved Alpha Vantage responses, so the backtest runs offline.
+    pub fn load_data(&self) -> Result<(Vec<StockPricePerformance>, Vec<News>), AppErrors> {
+        let read = |path: &str| {
+            fs::read_to_string(path).map_err(|error| {
+                AppErrors::BacktestError(format!("Could not read {path}: {error}"))
+            })
+        };
+        let bars = parse_time_series(&read(&self.time_series_path)?)?;
+        let news = match &self.news_path {
+            Some(path) => parse_news(&read(path)?)?,
+            None => vec![],
+        };
+        Ok((bars, news))
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BacktestTrade {
+    pub date: NaiveDateTime,
+    pub order_type: OrderType,
+    pub quantity: f64,
+    pub price: f64,
+    pub commission: f64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EquityPoint {
+    pub date: NaiveDateTime,
+    pub equity: f64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BacktestReport {
+    pub ticker: String,
+    pub decisions: usize,
+    /// Decisions where the model gave no valid advice, counted as hold.
+    pub failed_decisions: usize,
+    pub trades: Vec<BacktestTrade>,
+    pub equity_curve: Vec<EquityPoint>,
+    pub total_return: f64,
+    pub max_drawdown: f64,
+    /// Annualized from the per bar returns, `None` when they don't vary.
+    pub sharpe_ratio: Option<f64>,
+    /// Share of closed positions that made money, `None` before the first sell.
+    pub win_rate: Option<f64>,
+    /// Traded value divided by the average equity.
+    pub turnover: f64,
+}
+
+#[derive(Default)]
+struct Account {
+    cash: f64,
+    quantity: f64,
+    /// What the held shares cost, commissions included.
+    cost_basis: f64,
+    traded_value: f64,
+    closed_positions: usize,
+    winning_positions: usize,
+}
+
+/// Replays the bars one at a time. Each decision sees the data as of the next
+/// bar, whose open fills the order. A failed advice is logged and counted as
+/// hold; an unreachable model, a bar without a price above 0 or a run where
+/// every advice failed is an error.
+pub async fn run_backtest(
+    ai_service: &(dyn AiService + Send + Sync),
+    settings: &BacktestSettings,
+    bars: &[StockPricePerformance],
+    news: &[News],
+) -> Result<BacktestReport, AppErrors> {
+    let lookback = settings.price_history.lookback.unwrap_or(DEFAULT_LOOKBACK).max(1);
+    if bars.len() <= lookback {
+        return Err(AppErrors::BacktestError(format!(
+            "The backtest needs more than {lookback} bars, only {} were given",
+            bars.len()
+        )));
+    }
+
+    let starting_cash = settings.paper_trading.starting_cash.to_f64().unwrap_or_default();
+    let mut account = Account { cash: starting_cash, ..Account::default() };
+    let mut trades = vec![];
+    let mut failed_decisions = 0;
+    let mut equity_curve =
+        vec![EquityPoint { date: bars[lookback - 1].date, equity: starting_cash }];
+
+    let history = StockData {
+        stock: settings.stock.clone(),
+        price_history: PriceHistory { lookback: Some(lookback), ..settings.price_history },
+        stock_price_performance: bars.to_vec(),
+        news: news.to_vec(),
+    };
+    for next_bar in bars.iter().skip(lookback) {
+        let price = bar_price(next_bar.open, next_bar.date)?;
+        let close = bar_price(next_bar.close, next_bar.date)?;
+        let stock_data = history.as_of(next_bar.date);
+        let action = match ai_service.get_order_advice(stock_data).await {
+            Ok(response) => response.order_advice.action,
+            Err(AppErrors::NetworkError(error)) => {
+                return Err(AppErrors::BacktestError(format!(
+                    "The Ai is unreachable, stopped at {}: {error}",
+                    next_bar.date
+                )))
+            }
+            Err(error) => {
+                eprintln!("The order advice for {} failed, holding: {}", next_bar.date, error);
+                failed_decisions += 1;
+                AdviceAction::Hold
+            }
+        };
+
+        let trade = match action {
+            AdviceAction::Buy => buy(&mut account, settings, price),
+            AdviceAction::Sell => sell(&mut account, settings, price),
+            AdviceAction::Hold => None,
+        };
+        trades.extend(trade.map(|(order_type, quantity, commission)| BacktestTrade {
+            date: next_bar.date,
+            order_type,
+            quantity,
+            price,
+            commission,
+        }));
+        equity_curve.push(EquityPoint {
+            date: next_bar.date,
+            equity: account.cash + account.quantity * close,
+        });
+    }
+
+    let decisions = bars.len() - lookback;
+    if failed_decisions == decisions {
+        return Err(AppErrors::BacktestError(format!(
+            "All {decisions} order advices failed, see the errors above"
+        )));
+    }
+
+    let equities: Vec<f64> = equity_curve.iter().map(|point| point.equity).collect();
+    let average_equity = equities.iter().sum::<f64>() / equities.len() as f64;
+    Ok(BacktestReport {
+        ticker: settings.stock.to_string(),
+        decisions,
+        failed_decisions,
+        trades,
+        total_return: equities[equities.len() - 1] / equities[0] - 1.0,
+        max_drawdown: max_drawdown(&equities),
+        sharpe_ratio: sharpe_ratio(&equities, periods_per_year(settings.price_history.interval)),
+        win_rate: (account.closed_positions > 0)
+            .then(|| account.winning_positions as f64 / account.closed_positions as f64),
+        turnover: if average_equity > 0.0 { account.traded_value / average_equity } else { 0.0 },
+        equity_curve,
+    })
+}
+
+/// Buys whole shares for the order amount, or for the cash left if that is less.
+fn buy(
+    account: &mut Account,
+    settings: &BacktestSettings,
+    price: f64,
+) -> Option<(OrderType, f64, f64)> {
+    let mut quantity = (settings.order_amount.min(account.cash) / price).floor();
+    let mut fee = commission(&settings.paper_trading, quantity);
+    if quantity * price + fee > account.cash {
+        quantity = ((account.cash - fee) / price).floor();
+        fee = commission(&settings.paper_trading, quantity);
+    }
+    if quantity < 1.0 || quantity * price + fee > account.cash {
+        return None;
+    }
+    account.cash -= quantity * price + fee;
+    account.quantity += quantity;
+    account.cost_basis += quantity * price + fee;
+    account.traded_value += quantity * price;
+    Some((OrderType::Buy, quantity, fee))
+}
+
+/// Sells the whole position, like `/refresh` does.
+fn sell(
+    account: &mut Account,
+    settings: &BacktestSettings,
+    price: f64,
+) -> Option<(OrderType, f64, f64)> {
+    if account.quantity <= 0.0 {
+        return None;
+    }
+    let quantity = account.quantity;
+    let fee = commission(&settings.paper_trading, quantity);
+    let proceeds = quantity * price - fee;
+    account.closed_positions += 1;
+    if proceeds > account.cost_basis {
+        account.winning_positions += 1;
+    }
+    account.cash += proceeds;
+    account.quantity = 0.0;
+    account.cost_basis = 0.0;
+    account.traded_value += quantity * price;
+    Some((OrderType::Sell, quantity, fee))
+}
+
+/// Same fee model as the paper trading broker.
+fn commission(paper_trading: &PaperTradingConfig, quantity: f64) -> f64 {
+    let commission_per_share = paper_trading.commission_per_share.to_f64().unwrap_or_default();
+    let minimum_commission = paper_trading.minimum_commission.to_f64().unwrap_or_default();
+    (quantity * commission_per_share).max(minimum_commission)
+}
+
+/// The largest drop from a previous peak, as a share of that peak.
+pub fn max_drawdown(equities: &[f64]) -> f64 {
+    let mut peak = f64::MIN;
+    equities.iter().fold(0.0, |max_drawdown: f64, &equity| {
+        peak = peak.max(equity);
+        if peak > 0.0 {
+            max_drawdown.max((peak - equity) / peak)
+        } else {
+            max_drawdown
+        }
+    })
+}
+
+/// Mean over standard deviation of the per bar returns, annualized. The risk
+/// free rate is taken as zero.
+pub fn sharpe_ratio(equities: &[f64], periods_per_year: f64) -> Option<f64> {
+    let returns: Vec<f64> =
+        equities.windows(2).map(|pair| pair[1] / pair[0] - 1.0).filter(|r| r.is_finite()).collect();
+    if returns.len() < 2 {
+        return None;
+    }
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let variance =
+        returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+    let deviation = variance.sqrt();
+    (deviation > f64::EPSILON).then(|| mean / deviation * periods_per_year.sqrt())
+}
+
+/// Bars per year, counting 252 trading days of 6.5 hours.
+pub fn periods_per_year(interval: PriceInterval) -> f64 {
+    match interval.intraday_minutes() {
+        Some(minutes) => 252.0 * 390.0 / minutes as f64,
+        None => match interval {
+            PriceInterval::Daily => 252.0,
+            PriceInterval::Weekly => 52.0,
+            _ => 12.0,
+        },
+    }
+}
+
+/// Orders fill and positions are valued at the price, so it has to be above 0.
+fn bar_price(price: rust_decimal::Decimal, date: NaiveDateTime) -> Result<f64, AppErrors> {
+    price.to_f64().filter(|price| *price > 0.0).ok_or_else(|| {
+        AppErrors::BacktestError(format!("The bar at {date} has an unusable price: {price}"))
+    })
+}
@@ -0,0 +1,273 @@
+use crate::backtest::BacktestSettings;
+use crate::models::{
+    AdviceAction, Currency, Instrument, OrderAdvice, OrderLevels, PaperTradingConfig, PriceHistory,
+    PriceInterval, StockPricePerformance,
+};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+fn fixture_path(name: &str) -> String {
+    format!("{}/fixtures/alpha_vantage/{name}.json", env!("CARGO_MANIFEST_DIR"))
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// Daily bars from the 1st of February that open and close at the given prices.
+fn bars(prices: &[(i64, i64)]) -> Vec<StockPricePerformance> {
+    prices
+        .iter()
+        .enumerate()
+        .map(|(day, &(open, close))| StockPricePerformance {
+            date: NaiveDate::from_ymd_opt(2025, 2, day as u32 + 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+            open: Decimal::from(open),
+            high: Decimal::from(open.max(close)),
+            low: Decimal::from(open.min(close)),
+            close: Decimal::from(close),
+            volume: 1_000,
+        })
+        .collect()
+}
+
+fn settings(lookback: usize) -> BacktestSettings {
+    BacktestSettings {
+        stock: Instrument::stock("AAPL"),
+        price_history: PriceHistory { interval: PriceInterval::Daily, lookback: Some(lookback) },
+        order_amount: 500.0,
+        paper_trading: PaperTradingConfig {
+            base_currency: Currency::USD,
+            starting_cash: Decimal::from(1_000),
+            commission_per_share: Decimal::ZERO,
+            minimum_commission: Decimal::ONE,
+            fractional_shares: false,
+        },
+    }
+}
+
+fn advice(action: AdviceAction) -> OrderAdvice {
+    OrderAdvice {
+        action,
+        confidence: 0.7,
+        rationale: "Replayed".to_string(),
+        key_factors: vec![],
+        levels: OrderLevels::default(),
+    }
+}
+
+mod args {
+    use super::{args, fixture_path};
+    use crate::backtest::BacktestArgs;
+    use crate::models::{PriceHistory, PriceInterval};
+
+    #[test]
+    fn test_parse_backtest_args_success() {
+        let (backtest_args, config_args) = BacktestArgs::parse(args(&[
+            "--ticker",
+            "aapl",
+            "--time-series=data/aapl.json",
+            "--interval",
+            "weekly",
+            "--lookback",
+            "12",
+            "--ollama-model",
+            "llama3",
+        ]))
+        .unwrap();
+        assert_eq!(backtest_args.ticker, "AAPL");
+        assert_eq!(backtest_args.time_series_path, "data/aapl.json");
+        assert_eq!(backtest_args.news_path, None);
+        assert_eq!(
+            backtest_args.price_history,
+            PriceHistory { interval: PriceInterval::Weekly, lookback: Some(12) }
+        );
+        assert_eq!(config_args, vec!["--ollama-model", "llama3"])
+    }
+
+    #[test]
+    fn test_parse_backtest_args_missing_time_series() {
+        let maybe_args = BacktestArgs::parse(args(&["--ticker", "AAPL"]));
+        assert!(maybe_args.unwrap_err().to_string().contains("--time-series"))
+    }
+
+    #[test]
+    fn test_parse_backtest_args_invalid_lookback() {
+        let maybe_args = BacktestArgs::parse(args(&[
+            "--ticker",
+            "AAPL",
+            "--time-series",
+            "a",
+            "--lookback",
+            "0",
+        ]));
+        assert!(maybe_args.is_err())
+    }
+
+    #[test]
+    fn test_load_data_from_saved_responses() {
+        let (backtest_args, _) = BacktestArgs::parse(args(&[
+            "--ticker",
+            "AAPL",
+            "--time-series",
+            &fixture_path("TIME_SERIES_DAILY_AAPL"),
+            "--news",
+            &fixture_path("NEWS_SENTIMENT_AAPL"),
+        ]))
+        .unwrap();
+        let (bars, news) = backtest_args.load_data().unwrap();
+        assert_eq!(bars.len(), 5);
+        assert_eq!(news.len(), 2)
+    }
+}
+
+mod metrics {
+    use crate::backtest::{max_drawdown, periods_per_year, sharpe_ratio};
+    use crate::models::PriceInterval;
+
+    #[test]
+    fn test_max_drawdown_from_the_highest_peak() {
+        let drawdown = max_drawdown(&[100.0, 120.0, 90.0, 130.0, 117.0]);
+        assert!((drawdown - 0.25).abs() < 1e-9)
+    }
+
+    #[test]
+    fn test_sharpe_ratio_annualizes_the_per_bar_returns() {
+        let sharpe = sharpe_ratio(&[100.0, 110.0, 99.0, 108.9], 4.0).unwrap();
+        let mean: f64 = (0.1 - 0.1 + 0.1) / 3.0;
+        let deviation = ((2.0 * (0.1 - mean).powi(2) + (-0.1 - mean).powi(2)) / 2.0).sqrt();
+        assert!((sharpe - mean / deviation * 2.0).abs() < 1e-9)
+    }
+
+    #[test]
+    fn test_sharpe_ratio_without_variation() {
+        assert_eq!(sharpe_ratio(&[100.0, 100.0, 100.0], 252.0), None)
+    }
+
+    #[test]
+    fn test_periods_per_year() {
+        assert_eq!(periods_per_year(PriceInterval::Daily), 252.0);
+        assert_eq!(periods_per_year(PriceInterval::FiveMinutes), 252.0 * 78.0)
+    }
+}
+
+mod run {
+    use super::{advice, bars, fixture_path, settings};
+    use crate::alpha_vantage_client::{parse_news, parse_time_series};
+    use crate::backtest::run_backtest;
+    use crate::errors::AppErrors;
+    use crate::models::{AdviceAction, OrderType};
+    use crate::services_mock::AiServiceMock;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_run_backtest_fills_at_the_next_open() {
+        let ai_service = AiServiceMock::new()
+            .with_order_advice(Ok(advice(AdviceAction::Buy)))
+            .with_order_advice(Ok(advice(AdviceAction::Hold)))
+            .with_order_advice(Ok(advice(AdviceAction::Sell)));
+        let bars = bars(&[(100, 100), (100, 100), (100, 110), (110, 90), (120, 120)]);
+        let report = run_backtest(&ai_service, &settings(2), &bars, &[]).await.unwrap();
+
+        assert_eq!(report.decisions, 3);
+        assert_eq!(report.trades.len(), 2);
+        assert_eq!(report.trades[0].order_type, OrderType::Buy);
+        assert_eq!((report.trades[0].quantity, report.trades[0].price), (5.0, 100.0));
+        assert_eq!(report.trades[1].order_type, OrderType::Sell);
+        assert_eq!(report.trades[1].price, 120.0);
+        let equities: Vec<f64> = report.equity_curve.iter().map(|point| point.equity).collect();
+        assert_eq!(equities, vec![1_000.0, 1_049.0, 949.0, 1_098.0]);
+        assert!((report.total_return - 0.098).abs() < 1e-9);
+        assert_eq!(report.win_rate, Some(1.0));
+        assert!(report.max_drawdown > 0.09)
+    }
+
+    #[tokio::test]
+    async fn test_run_backtest_only_shows_past_bars() {
+        let ai_service = AiServiceMock::new()
+            .with_order_advice(Ok(advice(AdviceAction::Hold)))
+            .with_order_advice(Ok(advice(AdviceAction::Hold)));
+        let bars = bars(&[(100, 101), (101, 102), (102, 103), (103, 104)]);
+        run_backtest(&ai_service, &settings(2), &bars, &[]).await.unwrap();
+
+        let bar_counts: Vec<usize> = ai_service
+            .calls()
+            .iter()
+            .map(|stock_data| stock_data.stock_price_performance.len())
+            .collect();
+        let last_seen_dates: Vec<String> = ai_service
+            .calls()
+            .iter()
+            .map(|stock_data| stock_data.latest_bar().unwrap().date.date().to_string())
+            .collect();
+        assert_eq!(bar_counts, vec![2, 2]);
+        assert_eq!(last_seen_dates, vec!["2025-02-02", "2025-02-03"])
+    }
+
+    #[tokio::test]
+    async fn test_run_backtest_counts_failed_advice_as_hold() {
+        let ai_service = AiServiceMock::new()
+            .with_order_advice(Err(AppErrors::GetOrderAdviceError("Invalid JSON".to_string())))
+            .with_order_advice(Ok(advice(AdviceAction::Hold)));
+        let bars = bars(&[(100, 100), (100, 100), (100, 100)]);
+        let report = run_backtest(&ai_service, &settings(1), &bars, &[]).await.unwrap();
+        assert_eq!(report.failed_decisions, 1);
+        assert!(report.trades.is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_run_backtest_fails_when_every_advice_failed() {
+        let ai_service = AiServiceMock::new()
+            .with_order_advice(Err(AppErrors::GetOrderAdviceError("Invalid JSON".to_string())))
+            .with_order_advice(Err(AppErrors::GetOrderAdviceError("Invalid JSON".to_string())));
+        let bars = bars(&[(100, 100), (100, 100), (100, 100)]);
+        let maybe_report = run_backtest(&ai_service, &settings(1), &bars, &[]).await;
+        assert!(matches!(maybe_report, Err(AppErrors::BacktestError(_))))
+    }
+
+    #[tokio::test]
+    async fn test_run_backtest_stops_when_the_ai_is_unreachable() {
+        let ai_service = AiServiceMock::new()
+            .with_order_advice(Ok(advice(AdviceAction::Hold)))
+            .with_order_advice(Err(AppErrors::NetworkError("Connection refused".to_string())));
+        let bars = bars(&[(100, 100), (100, 100), (100, 100), (100, 100)]);
+        let maybe_report = run_backtest(&ai_service, &settings(1), &bars, &[]).await;
+        assert!(matches!(maybe_report, Err(AppErrors::BacktestError(_))));
+        assert_eq!(ai_service.calls().len(), 2)
+    }
+
+    #[tokio::test]
+    async fn test_run_backtest_rejects_a_bar_without_a_price() {
+        let ai_service = AiServiceMock::new().with_order_advice(Ok(advice(AdviceAction::Buy)));
+        let bars = bars(&[(100, 100), (100, 100), (0, 100)]);
+        let maybe_report = run_backtest(&ai_service, &settings(1), &bars, &[]).await;
+        assert!(matches!(maybe_report, Err(AppErrors::BacktestError(_))));
+        assert_eq!(ai_service.calls().len(), 1)
+    }
+
+    #[tokio::test]
+    async fn test_run_backtest_needs_more_bars_than_the_lookback() {
+        let maybe_report =
+            run_backtest(&AiServiceMock::new(), &settings(3), &bars(&[(1, 1); 3]), &[]).await;
+        assert!(maybe_report.is_err())
+    }
+
+    #[tokio::test]
+    async fn test_run_backtest_on_saved_responses_filters_news_by_date() {
+        let bars =
+            parse_time_series(&fs::read_to_string(fixture_path("TIME_SERIES_DAILY_AAPL")).unwrap())
+                .unwrap();
+        let news =
+            parse_news(&fs::read_to_string(fixture_path("NEWS_SENTIMENT_AAPL")).unwrap()).unwrap();
+        let ai_service = (0..3).fold(AiServiceMock::new(), |ai_service, _| {
+            ai_service.with_order_advice(Ok(advice(AdviceAction::Hold)))
+        });
+        run_backtest(&ai_service, &settings(2), &bars, &news).await.unwrap();
+
+        let news_counts: Vec<usize> =
+            ai_service.calls().iter().map(|stock_data| stock_data.news.len()).collect();
+        assert_eq!(news_counts, vec![0, 1, 1])
+    }
+}
@@ -0,0 +1,539 @@
+use crate::errors::AppErrors;
+use crate::models::{
+    AlphaVantageLimits, Broker, Config, Currency, MarketDataCacheConfig, PaperTradingConfig,
+    RiskLimits,
+};
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+const DEFAULT_ALPHA_VANTAGE_BASE_URL: &str = "https://www.alphavantage.co";
+const DEFAULT_ALPHA_VANTAGE_CALLS_PER_MINUTE: u32 = 5;
+const DEFAULT_ALPHA_VANTAGE_CALLS_PER_DAY: u32 = 25;
+const DEFAULT_ALPHA_VANTAGE_MAX_RETRIES: u32 = 3;
+const DEFAULT_INTERACTIVE_BROKERS_CONNECTION_URL_WITH_PORT: &str = "127.0.0.1:4002";
+const DEFAULT_PAPER_TRADING_STARTING_CASH: f64 = 100_000.0;
+const DEFAULT_PAPER_TRADING_COMMISSION_PER_SHARE: f64 = 0.005;
+const DEFAULT_PAPER_TRADING_MINIMUM_COMMISSION: f64 = 1.0;
+const DEFAULT_JOURNAL_PATH: &str = "trade_journal.sqlite";
+const DEFAULT_WATCHLIST_PATH: &str = "watchlist.json";
+const DEFAULT_REQUEST_TIMEOUT_SECONDS: u64 = 30;
+const DEFAULT_MARKET_DATA_CACHE_PATH: &str = "market_data_cache.sqlite";
+const DEFAULT_MARKET_DATA_CACHE_TIME_SERIES_TTL_SECONDS: u64 = 3_600;
+const DEFAULT_MARKET_DATA_CACHE_NEWS_TTL_SECONDS: u64 = 900;
+const DEFAULT_MARKET_DATA_CACHE_EXCHANGE_RATE_TTL_SECONDS: u64 = 3_600;
+
+const CONFIG_FILE_ENV: &str = "AI_TRADING_BOT_CONFIG";
+
+/// Every setting as (config file key, environment variable, command line flag).
+const SETTINGS: &[(&str, &str, &str)] = &[
+    ("alpha_vantage_api_key", "ALPHA_VANTAGE_API_KEY", "--alpha-vantage-api-key"),
+    ("alpha_vantage_base_url", "ALPHA_VANTAGE_BASE_URL", "--alpha-vantage-base-url"),
+    (
+        "alpha_vantage_calls_per_minute",
+        "ALPHA_VANTAGE_CALLS_PER_MINUTE",
+        "--alpha-vantage-calls-per-minute",
+    ),
+    ("alpha_vantage_calls_per_day", "ALPHA_VANTAGE_CALLS_PER_DAY", "--alpha-vantage-calls-per-day"),
+    ("alpha_vantage_max_retries", "ALPHA_VANTAGE_MAX_RETRIES", "--alpha-vantage-max-retries"),
+    (
+        "interactive_brokers_connection_url_with_port",
+        "IB_CONNECTION_URL",
+        "--ib-connection-url",
+    ),
+    ("ollama_model", "OLLAMA_MODEL", "--ollama-model"),
+    ("request_timeout_seconds", "REQUEST_TIMEOUT_SECONDS", "--request-timeout-seconds"),
+    ("broker", "BROKER", "--broker"),
+    ("fractional_shares", "FRACTIONAL_SHARES", "--fractional-shares"),
+    ("paper_trading_base_currency", "PAPER_TRADING_BASE_CURRENCY", "--paper-trading-base-currency"),
+    (
+        "paper_trading_starting_cash",
+        "PAPER_TRADING_STARTING_CASH",
+        "--paper-trading-starting-cash",
+    ),
+    (
+        "paper_trading_commission_per_share",
+        "PAPER_TRADING_COMMISSION_PER_SHARE",
+        "--paper-trading-commission-per-share",
+    ),
+    (
+        "paper_trading_minimum_commission",
+        "PAPER_TRADING_MINIMUM_COMMISSION",
+        "--paper-trading-minimum-commission",
+    ),
+    ("journal_path", "JOURNAL_PATH", "--journal-path"),
+    ("watchlist_path", "WATCHLIST_PATH", "--watchlist-path"),
+    ("market_data_cache_path", "MARKET_DATA_CACHE_PATH", "--market-data-cache-path"),
+    (
+        "market_data_cache_time_series_ttl_seconds",
+        "MARKET_DATA_CACHE_TIME_SERIES_TTL_SECONDS",
+        "--market-data-cache-time-series-ttl-seconds",
+    ),
+    (
+        "market_data_cache_news_ttl_seconds",
+        "MARKET_DATA_CACHE_NEWS_TTL_SECONDS",
+        "--market-data-cache-news-ttl-seconds",
+    ),
+    (
+        "market_data_cache_exchange_rate_ttl_seconds",
+        "MARKET_DATA_CACHE_EXCHANGE_RATE_TTL_SECONDS",
+        "--market-data-cache-exchange-rate-ttl-seconds",
+    ),
+    ("risk_base_currency", "RISK_BASE_CURRENCY", "--risk-base-currency"),
+    ("risk_max_order_notional", "RISK_MAX_ORDER_NOTIONAL", "--risk-max-order-notional"),
+    (
+        "risk_max_position_notional",
+        "RISK_MAX_POSITION_NOTIONAL",
+        "--risk-max-position-notional",
+    ),
+    ("risk_max_daily_loss", "RISK_MAX_DAILY_LOSS", "--risk-max-daily-loss"),
+    ("risk_max_orders_per_hour", "RISK_MAX_ORDERS_PER_HOUR", "--risk-max-orders-per-hour"),
+    ("risk_trading_hours_utc", "RISK_TRADING_HOURS_UTC", "--risk-trading-hours-utc"),
+    ("risk_allowed_tickers", "RISK_ALLOWED_TICKERS", "--risk-allowed-tickers"),
+    ("risk_denied_tickers", "RISK_DENIED_TICKERS", "--risk-denied-tickers"),
+];
+
+/// One source of configuration values. Layers are merged in the order
+/// defaults < TOML file < environment variables < CLI flags.
+#[derive(Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigLayer {
+    pub alpha_vantage_api_key: Option<String>,
+    pub alpha_vantage_base_url: Option<String>,
+    pub alpha_vantage_calls_per_minute: Option<u32>,
+    pub alpha_vantage_calls_per_day: Option<u32>,
+    pub alpha_vantage_max_retries: Option<u32>,
+    pub interactive_brokers_connection_url_with_port: Option<String>,
+    pub ollama_model: Option<String>,
+    pub request_timeout_seconds: Option<u64>,
+    pub broker: Option<Broker>,
+    pub fractional_shares: Option<bool>,
+    pub paper_trading_base_currency: Option<Currency>,
+    pub paper_trading_starting_cash: Option<f64>,
+    pub paper_trading_commission_per_share: Option<f64>,
+    pub paper_trading_minimum_commission: Option<f64>,
+    pub journal_path: Option<String>,
+    pub watchlist_path: Option<String>,
+    pub market_data_cache_path: Option<String>,
+    pub market_data_cache_time_series_ttl_seconds: Option<u64>,
+    pub market_data_cache_news_ttl_seconds: Option<u64>,
+    pub market_data_cache_exchange_rate_ttl_seconds: Option<u64>,
+    pub risk_base_currency: Option<Currency>,
+    pub risk_max_order_notional: Option<f64>,
+    pub risk_max_position_notional: Option<f64>,
+    pub risk_max_daily_loss: Option<f64>,
+    pub risk_max_orders_per_hour: Option<u32>,
+    pub risk_trading_hours_utc: Option<String>,
+    pub risk_allowed_tickers: Option<Vec<String>>,
+    pub risk_denied_tickers: Option<Vec<String>>,
+}
+
+impl ConfigLayer {
+    pub fn defaults() -> Self {
+        ConfigLayer {
+            alpha_vantage_base_url: Some(DEFAULT_ALPHA_VANTAGE_BASE_URL.to_string()),
+            alpha_vantage_calls_per_minute: Some(DEFAULT_ALPHA_VANTAGE_CALLS_PER_MINUTE),
+            alpha_vantage_calls_per_day: Some(DEFAULT_ALPHA_VANTAGE_CALLS_PER_DAY),
+            alpha_vantage_max_retries: Some(DEFAULT_ALPHA_VANTAGE_MAX_RETRIES),
+            interactive_brokers_connection_url_with_port: Some(
+                DEFAULT_INTERACTIVE_BROKERS_CONNECTION_URL_WITH_PORT.to_string(),
+            ),
+            request_timeout_seconds: Some(DEFAULT_REQUEST_TIMEOUT_SECONDS),
+            broker: Some(Broker::Live),
+            fractional_shares: Some(false),
+            paper_trading_base_currency: Some(Currency::USD),
+            paper_trading_starting_cash: Some(DEFAULT_PAPER_TRADING_STARTING_CASH),
+            paper_trading_commission_per_share: Some(DEFAULT_PAPER_TRADING_COMMISSION_PER_SHARE),
+            paper_trading_minimum_commission: Some(DEFAULT_PAPER_TRADING_MINIMUM_COMMISSION),
+            journal_path: Some(DEFAULT_JOURNAL_PATH.to_string()),
+            watchlist_path: Some(DEFAULT_WATCHLIST_PATH.to_string()),
+            market_data_cache_path: Some(DEFAULT_MARKET_DATA_CACHE_PATH.to_string()),
+            market_data_cache_time_series_ttl_seconds: Some(
+                DEFAULT_MARKET_DATA_CACHE_TIME_SERIES_TTL_SECONDS,
+            ),
+            market_data_cache_news_ttl_seconds: Some(DEFAULT_MARKET_DATA_CACHE_NEWS_TTL_SECONDS),
+            market_data_cache_exchange_rate_ttl_seconds: Some(
+                DEFAULT_MARKET_DATA_CACHE_EXCHANGE_RATE_TTL_SECONDS,
+            ),
+            ..ConfigLayer::default()
+        }
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, AppErrors> {
+        toml::from_str(content)
+            .map_err(|error| AppErrors::ConfigError(format!("Invalid config file: {error}")))
+    }
+
+    pub fn from_env_vars<I>(vars: I) -> Result<Self, AppErrors>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut layer = ConfigLayer::default();
+        for (name, value) in vars {
+            if let Some((key, _, _)) = SETTINGS.iter().find(|(_, env, _)| *env == name) {
+                layer.set(key, value)?;
+            }
+        }
+        Ok(layer)
+    }
+
+    /// Parses `--flag value` and `--flag=value` pairs. `--config` is resolved
+    /// separately by `config_file_path` and skipped here.
+    pub fn from_args<I>(args: I) -> Result<Self, AppErrors>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut layer = ConfigLayer::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let key = match SETTINGS.iter().find(|(_, _, name)| *name == flag) {
+                Some((key, _, _)) => Some(*key),
+                None if flag == "--config" => None,
+                None => {
+                    return Err(AppErrors::ConfigError(format!(
+                        "Unknown command line flag: {flag}"
+                    )))
+                }
+            };
+            let value = inline_value.or_else(|| args.next()).ok_or_else(|| {
+                AppErrors::ConfigError(format!("Missing value for command line flag: {flag}"))
+            })?;
+            if let Some(key) = key {
+                layer.set(key, value)?;
+            }
+        }
+        Ok(layer)
+    }
+
+    fn set(&mut self, key: &str, value: String) -> Result<(), AppErrors> {
+        match key {
+            "alpha_vantage_api_key" => self.alpha_vantage_api_key = Some(value),
+            "alpha_vantage_base_url" => self.alpha_vantage_base_url = Some(value),
+            "alpha_vantage_calls_per_minute" => {
+                self.alpha_vantage_calls_per_minute = Some(parse(key, &value)?)
+            }
+            "alpha_vantage_calls_per_day" => {
+                self.alpha_vantage_calls_per_day = Some(parse(key, &value)?)
+            }
+            "alpha_vantage_max_retries" => {
+                self.alpha_vantage_max_retries = Some(parse(key, &value)?)
+            }
+            "interactive_brokers_connection_url_with_port" => {
+                self.interactive_brokers_connection_url_with_port = Some(value)
+            }
+            "ollama_model" => self.ollama_model = Some(value),
+            "request_timeout_seconds" => self.request_timeout_seconds = Some(parse(key, &value)?),
+            "broker" => self.broker = Some(parse(key, &value)?),
+            "fractional_shares" => self.fractional_shares = Some(parse(key, &value)?),
+            "paper_trading_base_currency" => {
+                self.paper_trading_base_currency = Some(parse(key, &value)?)
+            }
+            "paper_trading_starting_cash" => {
+                self.paper_trading_starting_cash = Some(parse(key, &value)?)
+            }
+            "paper_trading_commission_per_share" => {
+                self.paper_trading_commission_per_share = Some(parse(key, &value)?)
+            }
+            "paper_trading_minimum_commission" => {
+                self.paper_trading_minimum_commission = Some(parse(key, &value)?)
+            }
+            "journal_path" => self.journal_path = Some(value),
+            "watchlist_path" => self.watchlist_path = Some(value),
+            "market_data_cache_path" => self.market_data_cache_path = Some(value),
+            "market_data_cache_time_series_ttl_seconds" => {
+                self.market_data_cache_time_series_ttl_seconds = Some(parse(key, &value)?)
+            }
+            "market_data_cache_news_ttl_seconds" => {
+                self.market_data_cache_news_ttl_seconds = Some(parse(key, &value)?)
+            }
+            "market_data_cache_exchange_rate_ttl_seconds" => {
+                self.market_data_cache_exchange_rate_ttl_seconds = Some(parse(key, &value)?)
+            }
+            "risk_base_currency" => self.risk_base_currency = Some(parse(key, &value)?),
+            "risk_max_order_notional" => self.risk_max_order_notional = Some(parse(key, &value)?),
+            "risk_max_position_notional" => {
+                self.risk_max_position_notional = Some(parse(key, &value)?)
+            }
+            "risk_max_daily_loss" => self.risk_max_daily_loss = Some(parse(key, &value)?),
+            "risk_max_orders_per_hour" => {
+                self.risk_max_orders_per_hour = Some(parse(key, &value)?)
+            }
+            "risk_trading_hours_utc" => self.risk_trading_hours_utc = Some(value),
+            "risk_allowed_tickers" => self.risk_allowed_tickers = Some(split_list(&value)),
+            "risk_denied_tickers" => self.risk_denied_tickers = Some(split_list(&value)),
+            _ => return Err(AppErrors::ConfigError(format!("Unknown config value: {key}"))),
+        }
+        Ok(())
+    }
+
+    pub fn merge(self, other: ConfigLayer) -> Self {
+        ConfigLayer {
+            alpha_vantage_api_key: other.alpha_vantage_api_key.or(self.alpha_vantage_api_key),
+            alpha_vantage_base_url: other.alpha_vantage_base_url.or(self.alpha_vantage_base_url),
+            alpha_vantage_calls_per_minute: other
+                .alpha_vantage_calls_per_minute
+                .or(self.alpha_vantage_calls_per_minute),
+            alpha_vantage_calls_per_day: other
+                .alpha_vantage_calls_per_day
+                .or(self.alpha_vantage_calls_per_day),
+            alpha_vantage_max_retries: other
+                .alpha_vantage_max_retries
+                .or(self.alpha_vantage_max_retries),
+            interactive_brokers_connection_url_with_port: other
+                .interactive_brokers_connection_url_with_port
+                .or(self.interactive_brokers_connection_url_with_port),
+            ollama_model: other.ollama_model.or(self.ollama_model),
+            request_timeout_seconds: other.request_timeout_seconds.or(self.request_timeout_seconds),
+            broker: other.broker.or(self.broker),
+            fractional_shares: other.fractional_shares.or(self.fractional_shares),
+            paper_trading_base_currency: other
+                .paper_trading_base_currency
+                .or(self.paper_trading_base_currency),
+            paper_trading_starting_cash: other
+                .paper_trading_starting_cash
+                .or(self.paper_trading_starting_cash),
+            paper_trading_commission_per_share: other
+                .paper_trading_commission_per_share
+                .or(self.paper_trading_commission_per_share),
+            paper_trading_minimum_commission: other
+                .paper_trading_minimum_commission
+                .or(self.paper_trading_minimum_commission),
+            journal_path: other.journal_path.or(self.journal_path),
+            watchlist_path: other.watchlist_path.or(self.watchlist_path),
+            market_data_cache_path: other.market_data_cache_path.or(self.market_data_cache_path),
+            market_data_cache_time_series_ttl_seconds: other
+                .market_data_cache_time_series_ttl_seconds
+                .or(self.market_data_cache_time_series_ttl_seconds),
+            market_data_cache_news_ttl_seconds: other
+                .market_data_cache_news_ttl_seconds
+                .or(self.market_data_cache_news_ttl_seconds),
+            market_data_cache_exchange_rate_ttl_seconds: other
+                .market_data_cache_exchange_rate_ttl_seconds
+                .or(self.market_data_cache_exchange_rate_ttl_seconds),
+            risk_base_currency: other.risk_base_currency.or(self.risk_base_currency),
+            risk_max_order_notional: other.risk_max_order_notional.or(self.risk_max_order_notional),
+            risk_max_position_notional: other
+                .risk_max_position_notional
+                .or(self.risk_max_position_notional),
+            risk_max_daily_loss: other.risk_max_daily_loss.or(self.risk_max_daily_loss),
+            risk_max_orders_per_hour: other
+                .risk_max_orders_per_hour
+                .or(self.risk_max_orders_per_hour),
+            risk_trading_hours_utc: other.risk_trading_hours_utc.or(self.risk_trading_hours_utc),
+            risk_allowed_tickers: other.risk_allowed_tickers.or(self.risk_allowed_tickers),
+            risk_denied_tickers: other.risk_denied_tickers.or(self.risk_denied_tickers),
+        }
+    }
+
+    pub fn build(self) -> Result<Config, AppErrors> {
+        let alpha_vantage_api_key = required(self.alpha_vantage_api_key, "alpha_vantage_api_key")?;
+        let alpha_vantage_base_url =
+            required(self.alpha_vantage_base_url, "alpha_vantage_base_url")?;
+        if !alpha_vantage_base_url.starts_with("http://")
+            && !alpha_vantage_base_url.starts_with("https://")
+        {
+            return Err(AppErrors::ConfigError(format!(
+                "alpha_vantage_base_url must start with http:// or https://. Value provided: {alpha_vantage_base_url}"
+            )));
+        }
+        let alpha_vantage_limits = AlphaVantageLimits {
+            calls_per_minute: self
+                .alpha_vantage_calls_per_minute
+                .unwrap_or(DEFAULT_ALPHA_VANTAGE_CALLS_PER_MINUTE),
+            calls_per_day: self
+                .alpha_vantage_calls_per_day
+                .unwrap_or(DEFAULT_ALPHA_VANTAGE_CALLS_PER_DAY),
+            max_retries: self.alpha_vantage_max_retries.unwrap_or(DEFAULT_ALPHA_VANTAGE_MAX_RETRIES),
+        };
+        let interactive_brokers_connection_url_with_port = required(
+            self.interactive_brokers_connection_url_with_port,
+            "interactive_brokers_connection_url_with_port",
+        )?;
+        let ollama_model = required(self.ollama_model, "ollama_model")?;
+        let journal_path = required(self.journal_path, "journal_path")?;
+        let watchlist_path = required(self.watchlist_path, "watchlist_path")?;
+        let market_data_cache = MarketDataCacheConfig {
+            path: required(self.market_data_cache_path, "market_data_cache_path")?,
+            time_series_ttl_seconds: self
+                .market_data_cache_time_series_ttl_seconds
+                .unwrap_or(DEFAULT_MARKET_DATA_CACHE_TIME_SERIES_TTL_SECONDS),
+            news_ttl_seconds: self
+                .market_data_cache_news_ttl_seconds
+                .unwrap_or(DEFAULT_MARKET_DATA_CACHE_NEWS_TTL_SECONDS),
+            exchange_rate_ttl_seconds: self
+                .market_data_cache_exchange_rate_ttl_seconds
+                .unwrap_or(DEFAULT_MARKET_DATA_CACHE_EXCHANGE_RATE_TTL_SECONDS),
+        };
+        let request_timeout_seconds = match self.request_timeout_seconds {
+            Some(0) => {
+                return Err(AppErrors::ConfigError(
+                    "request_timeout_seconds must be at least 1".to_string(),
+                ))
+            }
+            Some(seconds) => seconds,
+            None => {
+                return Err(AppErrors::ConfigError(
+                    "Missing required config value: request_timeout_seconds".to_string(),
+                ))
+            }
+        };
+
+        let has_valid_port = interactive_brokers_connection_url_with_port
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+        if !has_valid_port {
+            return Err(AppErrors::ConfigError(format!(
+                "interactive_brokers_connection_url_with_port must be a host:port pair. Value provided: {interactive_brokers_connection_url_with_port}"
+            )));
+        }
+
+        let paper_trading = PaperTradingConfig {
+            base_currency: self.paper_trading_base_currency.unwrap_or(Currency::USD),
+            starting_cash: non_negative_amount(
+                self.paper_trading_starting_cash,
+                "paper_trading_starting_cash",
+            )?,
+            commission_per_share: non_negative_amount(
+                self.paper_trading_commission_per_share,
+                "paper_trading_commission_per_share",
+            )?,
+            minimum_commission: non_negative_amount(
+                self.paper_trading_minimum_commission,
+                "paper_trading_minimum_commission",
+            )?,
+            fractional_shares: self.fractional_shares.unwrap_or(false),
+        };
+
+        let risk = RiskLimits {
+            base_currency: self.risk_base_currency,
+            max_order_notional: self
+                .risk_max_order_notional
+                .map(|value| non_negative(Some(value), "risk_max_order_notional"))
+                .transpose()?,
+            max_position_notional: self
+                .risk_max_position_notional
+                .map(|value| non_negative(Some(value), "risk_max_position_notional"))
+                .transpose()?,
+            max_daily_loss: self
+                .risk_max_daily_loss
+                .map(|value| non_negative(Some(value), "risk_max_daily_loss"))
+                .transpose()?,
+            max_orders_per_hour: self.risk_max_orders_per_hour,
+            trading_hours_utc: self
+                .risk_trading_hours_utc
+                .filter(|hours| !hours.trim().is_empty())
+                .map(|hours| hours.parse())
+                .transpose()?,
+            allowed_tickers: tickers(self.risk_allowed_tickers),
+            denied_tickers: tickers(self.risk_denied_tickers),
+        };
+
+        Ok(Config {
+            alpha_vantage_api_key,
+            alpha_vantage_base_url,
+            alpha_vantage_limits,
+            interactive_brokers_connection_url_with_port,
+            ollama_model,
+            request_timeout_seconds,
+            broker: self.broker.unwrap_or(Broker::Live),
+            fractional_shares: self.fractional_shares.unwrap_or(false),
+            paper_trading,
+            journal_path,
+            watchlist_path,
+            market_data_cache,
+            risk,
+        })
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, AppErrors> {
+    value.trim().parse().map_err(|_| {
+        AppErrors::ConfigError(format!("Invalid value for {key}. Value provided: {value}"))
+    })
+}
+
+fn non_negative(value: Option<f64>, name: &str) -> Result<f64, AppErrors> {
+    match value {
+        Some(value) if value.is_finite() && value >= 0.0 => Ok(value),
+        Some(value) => Err(AppErrors::ConfigError(format!(
+            "{name} must be a non negative number. Value provided: {value}"
+        ))),
+        None => Err(AppErrors::ConfigError(format!("Missing required config value: {name}"))),
+    }
+}
+
+/// A non negative amount of money, kept as the shortest decimal that reads
+/// back as the configured number.
+fn non_negative_amount(value: Option<f64>, name: &str) -> Result<Decimal, AppErrors> {
+    let value = non_negative(value, name)?;
+    Decimal::from_f64(value).ok_or_else(|| {
+        AppErrors::ConfigError(format!("{name} is too large. Value provided: {value}"))
+    })
+}
+
+/// Comma separated list, as used by environment variables and flags.
+fn split_list(value: &str) -> Vec<String> {
+    value.split(',').map(str::to_string).collect()
+}
+
+/// Tickers are compared upper case, blank entries are dropped.
+fn tickers(value: Option<Vec<String>>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .iter()
+        .map(|ticker| ticker.trim().to_uppercase())
+        .filter(|ticker| !ticker.is_empty())
+        .collect()
+}
+
+fn required(value: Option<String>, name: &str) -> Result<String, AppErrors> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| AppErrors::ConfigError(format!("Missing required config value: {name}")))
+}
+
+/// The config file is taken from `--config`, then `AI_TRADING_BOT_CONFIG`. Only
+/// an explicitly requested file has to exist.
+fn config_file_path(args: &[String]) -> (PathBuf, bool) {
+    let from_args = args.iter().enumerate().find_map(|(index, arg)| {
+        if arg == "--config" {
+            args.get(index + 1).cloned()
+        } else {
+            arg.strip_prefix("--config=").map(str::to_string)
+        }
+    });
+    match from_args.or_else(|| env::var(CONFIG_FILE_ENV).ok()) {
+        Some(path) => (PathBuf::from(path), true),
+        None => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+    }
+}
+
+pub fn load_from_args(args: Vec<String>) -> Result<Config, AppErrors> {
+    let (path, required) = config_file_path(&args);
+    let file_layer = match fs::read_to_string(&path) {
+        Ok(content) => ConfigLayer::from_toml(&content)?,
+        Err(_) if !required => ConfigLayer::default(),
+        Err(error) => {
+            return Err(AppErrors::ConfigError(format!(
+                "Could not read config file {}: {error}",
+                path.display()
+            )))
+        }
+    };
+
+    ConfigLayer::defaults()
+        .merge(file_layer)
+        .merge(ConfigLayer::from_env_vars(env::vars())?)
+        .merge(ConfigLayer::from_args(args)?)
+        .build()
+}
@@ -0,0 +1,255 @@
+mod config_layer {
+    use crate::config::ConfigLayer;
+    use crate::models::{Broker, Currency, RiskLimits};
+    use rust_decimal::Decimal;
+
+    fn complete_layer() -> ConfigLayer {
+        ConfigLayer {
+            alpha_vantage_api_key: Some("file-key".to_string()),
+            interactive_brokers_connection_url_with_port: Some("127.0.0.1:4002".to_string()),
+            ollama_model: Some("deepseek-r1:1.5b".to_string()),
+            ..ConfigLayer::defaults()
+        }
+    }
+
+    #[test]
+    fn test_config_layer_from_toml_success() {
+        let maybe_layer = ConfigLayer::from_toml(
+            "alpha_vantage_api_key = \"key\"\nollama_model = \"llama3\"\n",
+        );
+        assert!(maybe_layer.is_ok());
+        let layer = maybe_layer.unwrap();
+        assert_eq!(layer.alpha_vantage_api_key.as_deref(), Some("key"));
+        assert_eq!(layer.ollama_model.as_deref(), Some("llama3"));
+        assert!(layer.interactive_brokers_connection_url_with_port.is_none())
+    }
+
+    #[test]
+    fn test_config_layer_from_toml_unknown_field() {
+        let maybe_layer = ConfigLayer::from_toml("alpha_vantage_key = \"key\"\n");
+        assert!(maybe_layer.is_err())
+    }
+
+    #[test]
+    fn test_config_layer_precedence() {
+        let env_layer = ConfigLayer::from_env_vars(vec![
+            ("ALPHA_VANTAGE_API_KEY".to_string(), "env-key".to_string()),
+            ("OLLAMA_MODEL".to_string(), "env-model".to_string()),
+            ("UNRELATED".to_string(), "ignored".to_string()),
+        ])
+        .unwrap();
+        let cli_layer = ConfigLayer::from_args(vec![
+            "--ollama-model".to_string(),
+            "cli-model".to_string(),
+        ])
+        .unwrap();
+
+        let config = ConfigLayer::defaults()
+            .merge(complete_layer())
+            .merge(env_layer)
+            .merge(cli_layer)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.alpha_vantage_api_key, "env-key");
+        assert_eq!(config.ollama_model, "cli-model");
+        assert_eq!(config.interactive_brokers_connection_url_with_port, "127.0.0.1:4002")
+    }
+
+    #[test]
+    fn test_config_layer_from_args_inline_values() {
+        let layer = ConfigLayer::from_args(vec![
+            "--config=other.toml".to_string(),
+            "--ib-connection-url=localhost:7497".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(
+            layer.interactive_brokers_connection_url_with_port.as_deref(),
+            Some("localhost:7497")
+        )
+    }
+
+    #[test]
+    fn test_config_layer_from_args_unknown_flag() {
+        let maybe_layer = ConfigLayer::from_args(vec!["--verbose".to_string()]);
+        assert!(maybe_layer.is_err())
+    }
+
+    #[test]
+    fn test_config_layer_from_args_missing_value() {
+        let maybe_layer = ConfigLayer::from_args(vec!["--ollama-model".to_string()]);
+        assert!(maybe_layer.is_err())
+    }
+
+    #[test]
+    fn test_config_layer_build_missing_api_key() {
+        let maybe_config = ConfigLayer::defaults()
+            .merge(ConfigLayer {
+                alpha_vantage_api_key: Some("  ".to_string()),
+                ..complete_layer()
+            })
+            .build();
+        assert!(maybe_config.is_err())
+    }
+
+    #[test]
+    fn test_config_layer_build_invalid_connection_url() {
+        let maybe_config = ConfigLayer {
+            interactive_brokers_connection_url_with_port: Some("127.0.0.1".to_string()),
+            ..complete_layer()
+        }
+        .build();
+        assert!(maybe_config.is_err())
+    }
+
+    #[test]
+    fn test_config_layer_broker_selection() {
+        let cli_layer = ConfigLayer::from_args(vec![
+            "--broker".to_string(),
+            "paper".to_string(),
+            "--paper-trading-starting-cash=2500".to_string(),
+        ])
+        .unwrap();
+        let config = complete_layer().merge(cli_layer).build().unwrap();
+        assert_eq!(config.broker, Broker::Paper);
+        assert_eq!(config.paper_trading.starting_cash, Decimal::from(2500))
+    }
+
+    #[test]
+    fn test_config_layer_fractional_shares() {
+        let default_config = complete_layer().build().unwrap();
+        let cli_layer =
+            ConfigLayer::from_args(vec!["--fractional-shares".to_string(), "true".to_string()])
+                .unwrap();
+        let config = complete_layer().merge(cli_layer).build().unwrap();
+        assert!(!default_config.fractional_shares);
+        assert!(config.fractional_shares)
+    }
+
+    #[test]
+    fn test_config_layer_paper_trading_base_currency() {
+        let default_config = complete_layer().build().unwrap();
+        let env_layer = ConfigLayer::from_env_vars(vec![(
+            "PAPER_TRADING_BASE_CURRENCY".to_string(),
+            "eur".to_string(),
+        )])
+        .unwrap();
+        let config = complete_layer().merge(env_layer).build().unwrap();
+        let maybe_layer = ConfigLayer::from_args(vec![
+            "--paper-trading-base-currency".to_string(),
+            "EURO".to_string(),
+        ]);
+        assert_eq!(default_config.paper_trading.base_currency, Currency::USD);
+        assert_eq!(config.paper_trading.base_currency.code(), "EUR");
+        assert!(maybe_layer.is_err())
+    }
+
+    #[test]
+    fn test_config_layer_journal_path() {
+        let default_config = complete_layer().build().unwrap();
+        let env_layer = ConfigLayer::from_env_vars(vec![(
+            "JOURNAL_PATH".to_string(),
+            "/var/lib/ai-trading-bot/journal.sqlite".to_string(),
+        )])
+        .unwrap();
+        let config = complete_layer().merge(env_layer).build().unwrap();
+        assert_eq!(default_config.journal_path, "trade_journal.sqlite");
+        assert_eq!(config.journal_path, "/var/lib/ai-trading-bot/journal.sqlite")
+    }
+
+    #[test]
+    fn test_config_layer_alpha_vantage_limits() {
+        let default_config = complete_layer().build().unwrap();
+        let env_layer = ConfigLayer::from_env_vars(vec![
+            ("ALPHA_VANTAGE_CALLS_PER_MINUTE".to_string(), "75".to_string()),
+            ("ALPHA_VANTAGE_CALLS_PER_DAY".to_string(), "0".to_string()),
+        ])
+        .unwrap();
+        let config = complete_layer().merge(env_layer).build().unwrap();
+        assert_eq!(default_config.alpha_vantage_limits.calls_per_minute, 5);
+        assert_eq!(default_config.alpha_vantage_limits.calls_per_day, 25);
+        assert_eq!(config.alpha_vantage_limits.calls_per_minute, 75);
+        assert_eq!(config.alpha_vantage_limits.calls_per_day, 0);
+        assert_eq!(config.alpha_vantage_limits.max_retries, 3)
+    }
+
+    #[test]
+    fn test_config_layer_market_data_cache() {
+        let default_config = complete_layer().build().unwrap();
+        let args_layer = ConfigLayer::from_args(
+            ["--market-data-cache-news-ttl-seconds", "60"].map(str::to_string),
+        )
+        .unwrap();
+        let config = complete_layer().merge(args_layer).build().unwrap();
+        assert_eq!(default_config.market_data_cache.path, "market_data_cache.sqlite");
+        assert_eq!(default_config.market_data_cache.time_series_ttl_seconds, 3_600);
+        assert_eq!(config.market_data_cache.news_ttl_seconds, 60)
+    }
+
+    #[test]
+    fn test_config_layer_risk_limits() {
+        let file_layer = ConfigLayer::from_toml(
+            "risk_max_order_notional = 5000.0\nrisk_denied_tickers = [\"gme\", \" amc \"]\n",
+        )
+        .unwrap();
+        let env_layer = ConfigLayer::from_env_vars(vec![
+            ("RISK_ALLOWED_TICKERS".to_string(), "AAPL, msft,".to_string()),
+            ("RISK_TRADING_HOURS_UTC".to_string(), "14:30-21:00".to_string()),
+            ("RISK_MAX_ORDERS_PER_HOUR".to_string(), "10".to_string()),
+            ("RISK_BASE_CURRENCY".to_string(), "EUR".to_string()),
+        ])
+        .unwrap();
+        let default_config = complete_layer().build().unwrap();
+        let config = complete_layer().merge(file_layer).merge(env_layer).build().unwrap();
+        assert_eq!(default_config.risk, RiskLimits::default());
+        assert_eq!(config.risk.base_currency.unwrap().code(), "EUR");
+        assert_eq!(config.risk.max_order_notional, Some(5000.0));
+        assert_eq!(config.risk.max_orders_per_hour, Some(10));
+        assert_eq!(config.risk.trading_hours_utc.unwrap().to_string(), "14:30-21:00");
+        assert_eq!(config.risk.allowed_tickers, vec!["AAPL", "MSFT"]);
+        assert_eq!(config.risk.denied_tickers, vec!["GME", "AMC"])
+    }
+
+    #[test]
+    fn test_config_layer_invalid_trading_hours() {
+        let maybe_config = ConfigLayer {
+            risk_trading_hours_utc: Some("9 to 5".to_string()),
+            ..complete_layer()
+        }
+        .build();
+        assert!(maybe_config.is_err())
+    }
+
+    #[test]
+    fn test_config_layer_invalid_number() {
+        let maybe_layer = ConfigLayer::from_env_vars(vec![(
+            "PAPER_TRADING_STARTING_CASH".to_string(),
+            "a lot".to_string(),
+        )]);
+        assert!(maybe_layer.is_err())
+    }
+
+    #[test]
+    fn test_config_layer_build_negative_commission() {
+        let maybe_config = ConfigLayer {
+            paper_trading_commission_per_share: Some(-0.01),
+            ..complete_layer()
+        }
+        .build();
+        assert!(maybe_config.is_err())
+    }
+
+    #[test]
+    fn test_config_layer_build_zero_request_timeout() {
+        let maybe_config = ConfigLayer { request_timeout_seconds: Some(0), ..complete_layer() }.build();
+        assert!(maybe_config.is_err())
+    }
+
+    #[test]
+    fn test_config_debug_redacts_api_key() {
+        let config = complete_layer().build().unwrap();
+        let debug_output = format!("{:?}", config);
+        assert!(!debug_output.contains("file-key"));
+        assert!(debug_output.contains("<redacted>"))
+    }
+}
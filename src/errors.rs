@@ -1,10 +1,66 @@
+use crate::risk::RiskRejection;
+use std::fmt;
+
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum AppErrors {
     ModelCreationError(String),
+    MoneyArithmeticError(String),
     GetStockDataError(String),
+    GetExchangeRateError(String),
+    RateLimitError(String),
+    InvalidApiKeyError(String),
+    UnknownSymbolError(String),
+    NetworkError(String),
     ConvertMoneyToStockQuantityError(String),
     GetQuantityToSellEverythingError(String),
+    PositionSizingError(String),
+    QuantityBelowMinimumError(String),
+    GetContractDetailsError(String),
     PlaceOrderError(String),
+    CancelOrderError(String),
+    ModifyOrderError(String),
     GetOrderAdviceError(String),
     GetCurrentInvestmentError(String),
+    GetPortfolioError(String),
+    ConfigError(String),
+    JournalError(String),
+    WatchlistError(String),
+    BacktestError(String),
+    CacheError(String),
+    TimeoutError(String),
+    RiskRejectedError(RiskRejection),
+}
+
+impl fmt::Display for AppErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppErrors::ModelCreationError(message)
+            | AppErrors::MoneyArithmeticError(message)
+            | AppErrors::GetStockDataError(message)
+            | AppErrors::GetExchangeRateError(message)
+            | AppErrors::RateLimitError(message)
+            | AppErrors::InvalidApiKeyError(message)
+            | AppErrors::UnknownSymbolError(message)
+            | AppErrors::NetworkError(message)
+            | AppErrors::ConvertMoneyToStockQuantityError(message)
+            | AppErrors::GetQuantityToSellEverythingError(message)
+            | AppErrors::PositionSizingError(message)
+            | AppErrors::QuantityBelowMinimumError(message)
+            | AppErrors::GetContractDetailsError(message)
+            | AppErrors::PlaceOrderError(message)
+            | AppErrors::CancelOrderError(message)
+            | AppErrors::ModifyOrderError(message)
+            | AppErrors::GetOrderAdviceError(message)
+            | AppErrors::GetCurrentInvestmentError(message)
+            | AppErrors::GetPortfolioError(message)
+            | AppErrors::ConfigError(message)
+            | AppErrors::JournalError(message)
+            | AppErrors::WatchlistError(message)
+            | AppErrors::BacktestError(message)
+            | AppErrors::CacheError(message)
+            | AppErrors::TimeoutError(message) => write!(f, "{message}"),
+            AppErrors::RiskRejectedError(rejection) => write!(f, "{rejection}"),
+        }
+    }
 }
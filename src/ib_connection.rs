@@ -0,0 +1,171 @@
+use chrono::{DateTime, Utc};
+use ibapi::Client as IbClient;
+use ibapi::Error as IbError;
+use serde::Serialize;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The client id of the shared connection. Dedicated connections get the ids
+/// after it.
+pub const SHARED_CLIENT_ID: i32 = 1;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// What `GET /health` reports about the Interactive Brokers connection.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ConnectionHealth {
+    pub connected: bool,
+    pub connection_url: String,
+    pub client_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_version: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connected_since: Option<DateTime<Utc>>,
+    pub failed_attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_attempt_in_seconds: Option<u64>,
+}
+
+#[derive(Default)]
+struct ConnectionState {
+    client: Option<Arc<IbClient>>,
+    connected_since: Option<DateTime<Utc>>,
+    failed_attempts: u32,
+    last_error: Option<String>,
+    retry_at: Option<Instant>,
+}
+
+/// One long lived IB connection shared by every request. It is opened on first
+/// use and dropped when a request fails because the Gateway went away, after
+/// which the next request reconnects. Failed connects back off exponentially,
+/// requests during the backoff fail at once instead of hammering the Gateway.
+pub struct IbConnection {
+    connection_url: String,
+    next_client_id: AtomicI32,
+    state: Mutex<ConnectionState>,
+    // Held while connecting so concurrent requests wait for one handshake
+    // instead of each opening a socket.
+    connecting: Mutex<()>,
+}
+
+impl IbConnection {
+    pub fn new(connection_url: &str) -> Self {
+        IbConnection {
+            connection_url: connection_url.to_string(),
+            next_client_id: AtomicI32::new(SHARED_CLIENT_ID + 1),
+            state: Mutex::new(ConnectionState::default()),
+            connecting: Mutex::new(()),
+        }
+    }
+
+    /// Runs `request` on the shared client, connecting first when needed.
+    pub fn request<T>(
+        &self,
+        request: impl FnOnce(&IbClient) -> Result<T, IbError>,
+    ) -> Result<T, String> {
+        let client = self.client()?;
+        request(&client).map_err(|error| {
+            if is_connection_error(&error) {
+                self.disconnect(&client, &error);
+            }
+            error.to_string()
+        })
+    }
+
+    /// A separate connection for long lived subscriptions, like the order
+    /// updates, with a client id no other connection of this server uses.
+    pub fn connect_dedicated(&self) -> Result<IbClient, String> {
+        let client_id = self.next_client_id.fetch_add(1, Ordering::Relaxed);
+        IbClient::connect(&self.connection_url, client_id).map_err(|error| error.to_string())
+    }
+
+    pub fn health(&self) -> ConnectionHealth {
+        let state = self.state.lock().unwrap();
+        ConnectionHealth {
+            connected: state.client.is_some(),
+            connection_url: self.connection_url.clone(),
+            client_id: SHARED_CLIENT_ID,
+            server_version: state.client.as_ref().map(|client| client.server_version()),
+            connected_since: state.connected_since,
+            failed_attempts: state.failed_attempts,
+            last_error: state.last_error.clone(),
+            next_attempt_in_seconds: state
+                .retry_at
+                .map(|retry_at| retry_at.saturating_duration_since(Instant::now()).as_secs()),
+        }
+    }
+
+    fn client(&self) -> Result<Arc<IbClient>, String> {
+        if let Some(client) = self.connected_client()? {
+            return Ok(client);
+        }
+        let _connecting = self.connecting.lock().unwrap();
+        // Another request may have connected while this one waited.
+        if let Some(client) = self.connected_client()? {
+            return Ok(client);
+        }
+
+        match IbClient::connect(&self.connection_url, SHARED_CLIENT_ID) {
+            Ok(client) => {
+                let client = Arc::new(client);
+                let mut state = self.state.lock().unwrap();
+                state.client = Some(client.clone());
+                state.connected_since = Some(Utc::now());
+                state.failed_attempts = 0;
+                state.retry_at = None;
+                Ok(client)
+            }
+            Err(error) => {
+                let mut state = self.state.lock().unwrap();
+                state.failed_attempts += 1;
+                state.last_error = Some(error.to_string());
+                state.retry_at = Some(Instant::now() + backoff(state.failed_attempts));
+                Err(format!("Failed to connect to Interactive Brokers: {error}"))
+            }
+        }
+    }
+
+    /// The current client, `None` when a connect is due and an error while
+    /// still backing off.
+    fn connected_client(&self) -> Result<Option<Arc<IbClient>>, String> {
+        let state = self.state.lock().unwrap();
+        if let Some(client) = &state.client {
+            return Ok(Some(client.clone()));
+        }
+        match state.retry_at {
+            Some(retry_at) if retry_at > Instant::now() => Err(format!(
+                "Not connected to Interactive Brokers, retrying in {}s after: {}",
+                retry_at.duration_since(Instant::now()).as_secs() + 1,
+                state.last_error.as_deref().unwrap_or("unknown error")
+            )),
+            _ => Ok(None),
+        }
+    }
+
+    /// Forgets `client` unless a newer connection already replaced it.
+    fn disconnect(&self, client: &Arc<IbClient>, error: &IbError) {
+        let mut state = self.state.lock().unwrap();
+        if state.client.as_ref().is_some_and(|current| Arc::ptr_eq(current, client)) {
+            state.client = None;
+            state.connected_since = None;
+            state.last_error = Some(error.to_string());
+        }
+    }
+}
+
+/// Errors after which the connection can't be used anymore. ibapi already
+/// retries a reset socket itself and shuts the client down once it gives up.
+pub fn is_connection_error(error: &IbError) -> bool {
+    matches!(
+        error,
+        IbError::Io(_) | IbError::ConnectionFailed | IbError::ConnectionReset | IbError::Shutdown
+    )
+}
+
+/// Doubles from `INITIAL_BACKOFF` with every failed attempt up to `MAX_BACKOFF`.
+pub fn backoff(failed_attempts: u32) -> Duration {
+    INITIAL_BACKOFF.saturating_mul(1 << failed_attempts.saturating_sub(1).min(16)).min(MAX_BACKOFF)
+}
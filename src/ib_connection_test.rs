@@ -0,0 +1,54 @@
+mod backoff {
+    use crate::ib_connection::backoff;
+    use std::time::Duration;
+
+    #[test]
+    fn test_backoff_doubles_up_to_a_minute() {
+        assert_eq!(backoff(1), Duration::from_secs(1));
+        assert_eq!(backoff(2), Duration::from_secs(2));
+        assert_eq!(backoff(4), Duration::from_secs(8));
+        assert_eq!(backoff(7), Duration::from_secs(60));
+        assert_eq!(backoff(u32::MAX), Duration::from_secs(60))
+    }
+}
+
+mod ib_connection {
+    use crate::ib_connection::{is_connection_error, IbConnection, SHARED_CLIENT_ID};
+    use ibapi::Error as IbError;
+    use std::net::TcpListener;
+
+    /// An address nothing listens on.
+    fn closed_address() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_health_before_the_first_request() {
+        let health = IbConnection::new("127.0.0.1:4002").health();
+        assert!(!health.connected);
+        assert_eq!(health.client_id, SHARED_CLIENT_ID);
+        assert_eq!(health.failed_attempts, 0);
+        assert!(health.last_error.is_none())
+    }
+
+    #[test]
+    fn test_failed_connect_backs_off() {
+        let connection = IbConnection::new(&closed_address());
+        let maybe_first = connection.request(|client| Ok(client.client_id()));
+        let maybe_second = connection.request(|client| Ok(client.client_id()));
+        let health = connection.health();
+        assert!(maybe_first.unwrap_err().starts_with("Failed to connect"));
+        assert!(maybe_second.unwrap_err().starts_with("Not connected to Interactive Brokers"));
+        assert_eq!(health.failed_attempts, 1);
+        assert!(health.last_error.is_some());
+        assert!(health.next_attempt_in_seconds.is_some())
+    }
+
+    #[test]
+    fn test_is_connection_error() {
+        assert!(is_connection_error(&IbError::ConnectionReset));
+        assert!(is_connection_error(&IbError::Shutdown));
+        assert!(!is_connection_error(&IbError::Simple("No security definition".to_string())))
+    }
+}
@@ -0,0 +1,265 @@
+use crate::errors::AppErrors;
+use crate::models::{OrderAdvice, OrderType};
+use crate::orders::OrderRecord;
+use crate::risk::RiskFill;
+use chrono::{DateTime, Utc};
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+
+const SELECT_TRADES: &str = "SELECT id, created_at, endpoint, ticker, stock_data_hash, prompt, raw_response, advice, quantity, order_result, error FROM trades";
+
+/// One decision made by `/analyze` or `/refresh`, from the market data it was
+/// based on to the broker's answer. Steps the request didn't reach stay `None`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub endpoint: String,
+    pub ticker: String,
+    pub stock_data_hash: Option<String>,
+    pub prompt: Option<String>,
+    pub raw_response: Option<String>,
+    pub advice: Option<OrderAdvice>,
+    pub quantity: Option<f64>,
+    pub order_result: Option<String>,
+    pub error: Option<String>,
+}
+
+impl JournalEntry {
+    pub fn new(endpoint: &str, ticker: &str) -> Self {
+        JournalEntry {
+            id: 0,
+            created_at: Utc::now(),
+            endpoint: endpoint.to_string(),
+            ticker: ticker.to_string(),
+            stock_data_hash: None,
+            prompt: None,
+            raw_response: None,
+            advice: None,
+            quantity: None,
+            order_result: None,
+            error: None,
+        }
+    }
+}
+
+/// Append-only SQLite log of every trading decision.
+pub struct TradeJournal {
+    connection: Mutex<Connection>,
+}
+
+impl TradeJournal {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AppErrors> {
+        Self::from_connection(Connection::open(path).map_err(journal_error)?)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, AppErrors> {
+        Self::from_connection(Connection::open_in_memory().map_err(journal_error)?)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self, AppErrors> {
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS trades (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    created_at TEXT NOT NULL,
+                    endpoint TEXT NOT NULL,
+                    ticker TEXT NOT NULL,
+                    stock_data_hash TEXT,
+                    prompt TEXT,
+                    raw_response TEXT,
+                    advice TEXT,
+                    quantity REAL,
+                    order_result TEXT,
+                    error TEXT
+                );
+                CREATE TABLE IF NOT EXISTS fills (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    filled_at TEXT NOT NULL,
+                    instrument TEXT NOT NULL,
+                    order_type TEXT NOT NULL,
+                    quantity REAL NOT NULL,
+                    price REAL NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS orders (
+                    order_id INTEGER PRIMARY KEY,
+                    record TEXT NOT NULL
+                )",
+            )
+            .map_err(journal_error)?;
+        Ok(TradeJournal { connection: Mutex::new(connection) })
+    }
+
+    /// Stores the entry and returns its id. The `id` of the entry is ignored.
+    pub fn record(&self, entry: &JournalEntry) -> Result<i64, AppErrors> {
+        let advice = entry
+            .advice
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|error| AppErrors::JournalError(error.to_string()))?;
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT INTO trades (created_at, endpoint, ticker, stock_data_hash, prompt, raw_response, advice, quantity, order_result, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    entry.created_at.to_rfc3339(),
+                    entry.endpoint,
+                    entry.ticker,
+                    entry.stock_data_hash,
+                    entry.prompt,
+                    entry.raw_response,
+                    advice,
+                    entry.quantity,
+                    entry.order_result,
+                    entry.error,
+                ],
+            )
+            .map_err(journal_error)?;
+        Ok(connection.last_insert_rowid())
+    }
+
+    /// Every entry, newest first.
+    pub fn trades(&self) -> Result<Vec<JournalEntry>, AppErrors> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(&format!("{SELECT_TRADES} ORDER BY id DESC"))
+            .map_err(journal_error)?;
+        let rows = statement.query_map([], journal_entry_from_row).map_err(journal_error)?;
+        rows.map(|row| row.map_err(journal_error)).collect()
+    }
+
+    pub fn trade(&self, id: i64) -> Result<Option<JournalEntry>, AppErrors> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(&format!("{SELECT_TRADES} WHERE id = ?1"), [id], journal_entry_from_row)
+            .optional()
+            .map_err(journal_error)
+    }
+
+    /// Keeps a fill the risk engine counted, so it can be replayed at startup.
+    pub fn record_fill(&self, fill: &RiskFill) -> Result<(), AppErrors> {
+        let order_type = match fill.order_type {
+            OrderType::Buy => "BUY",
+            OrderType::Sell => "SELL",
+        };
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT INTO fills (filled_at, instrument, order_type, quantity, price) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    fill.filled_at.to_rfc3339(),
+                    fill.instrument,
+                    order_type,
+                    fill.quantity,
+                    fill.price
+                ],
+            )
+            .map_err(journal_error)?;
+        Ok(())
+    }
+
+    /// Every recorded fill, oldest first.
+    pub fn fills(&self) -> Result<Vec<RiskFill>, AppErrors> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(
+                "SELECT filled_at, instrument, order_type, quantity, price FROM fills ORDER BY id",
+            )
+            .map_err(journal_error)?;
+        let rows = statement.query_map([], risk_fill_from_row).map_err(journal_error)?;
+        rows.map(|row| row.map_err(journal_error)).collect()
+    }
+
+    /// Saves the latest state of the order, replacing the one saved before.
+    pub fn record_order(&self, record: &OrderRecord) -> Result<(), AppErrors> {
+        let json = serde_json::to_string(record)
+            .map_err(|error| AppErrors::JournalError(error.to_string()))?;
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO orders (order_id, record) VALUES (?1, ?2)",
+                params![record.order_id, json],
+            )
+            .map_err(journal_error)?;
+        Ok(())
+    }
+
+    /// Every saved order, by order id.
+    pub fn orders(&self) -> Result<Vec<OrderRecord>, AppErrors> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare("SELECT record FROM orders ORDER BY order_id")
+            .map_err(journal_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                let record: String = row.get("record")?;
+                serde_json::from_str(&record).map_err(|error| conversion_error(0, error))
+            })
+            .map_err(journal_error)?;
+        rows.map(|row| row.map_err(journal_error)).collect()
+    }
+}
+
+fn journal_error(error: rusqlite::Error) -> AppErrors {
+    AppErrors::JournalError(error.to_string())
+}
+
+fn journal_entry_from_row(row: &Row) -> rusqlite::Result<JournalEntry> {
+    let created_at: String = row.get("created_at")?;
+    let advice: Option<String> = row.get("advice")?;
+    Ok(JournalEntry {
+        id: row.get("id")?,
+        created_at: DateTime::parse_from_rfc3339(&created_at)
+            .map_err(|error| conversion_error(1, error))?
+            .with_timezone(&Utc),
+        endpoint: row.get("endpoint")?,
+        ticker: row.get("ticker")?,
+        stock_data_hash: row.get("stock_data_hash")?,
+        prompt: row.get("prompt")?,
+        raw_response: row.get("raw_response")?,
+        advice: advice
+            .map(|advice| serde_json::from_str(&advice))
+            .transpose()
+            .map_err(|error| conversion_error(7, error))?,
+        quantity: row.get("quantity")?,
+        order_result: row.get("order_result")?,
+        error: row.get("error")?,
+    })
+}
+
+fn risk_fill_from_row(row: &Row) -> rusqlite::Result<RiskFill> {
+    let filled_at: String = row.get("filled_at")?;
+    let order_type: String = row.get("order_type")?;
+    Ok(RiskFill {
+        filled_at: DateTime::parse_from_rfc3339(&filled_at)
+            .map_err(|error| conversion_error(0, error))?
+            .with_timezone(&Utc),
+        instrument: row.get("instrument")?,
+        order_type: match order_type.as_str() {
+            "BUY" => OrderType::Buy,
+            "SELL" => OrderType::Sell,
+            _ => {
+                return Err(rusqlite::Error::FromSqlConversionFailure(
+                    2,
+                    Type::Text,
+                    format!("Unknown order type: {order_type}").into(),
+                ))
+            }
+        },
+        quantity: row.get("quantity")?,
+        price: row.get("price")?,
+    })
+}
+
+fn conversion_error(
+    column: usize,
+    error: impl std::error::Error + Send + Sync + 'static,
+) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(column, Type::Text, Box::new(error))
+}
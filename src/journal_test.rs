@@ -0,0 +1,102 @@
+use crate::journal::JournalEntry;
+use crate::models::{AdviceAction, OrderAdvice, OrderLevels};
+
+fn journal_entry() -> JournalEntry {
+    JournalEntry {
+        stock_data_hash: Some("5e3c".to_string()),
+        prompt: Some("Should I BUY, SELL or HOLD?".to_string()),
+        raw_response: Some("<think>...</think>{}".to_string()),
+        advice: Some(OrderAdvice {
+            action: AdviceAction::Sell,
+            confidence: 0.6,
+            rationale: "Guidance was cut".to_string(),
+            key_factors: vec![],
+            levels: OrderLevels::default(),
+        }),
+        quantity: Some(4.0),
+        order_result: Some("placed".to_string()),
+        ..JournalEntry::new("refresh", "AAPL")
+    }
+}
+
+mod trade_journal {
+    use super::journal_entry;
+    use crate::journal::{JournalEntry, TradeJournal};
+    use crate::models::{Instrument, Order, OrderStyle, OrderType, TimeInForce};
+    use crate::orders::{OrderRecord, OrderStatus};
+    use crate::risk::RiskFill;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_record_and_read_trade_success() {
+        let journal = TradeJournal::open_in_memory().unwrap();
+        let entry = journal_entry();
+        let id = journal.record(&entry).unwrap();
+        let maybe_trade = journal.trade(id).unwrap();
+        assert_eq!(maybe_trade, Some(JournalEntry { id, ..entry }))
+    }
+
+    #[test]
+    fn test_trades_newest_first() {
+        let journal = TradeJournal::open_in_memory().unwrap();
+        journal.record(&JournalEntry::new("analyze", "AAPL")).unwrap();
+        journal.record(&JournalEntry::new("refresh", "MSFT")).unwrap();
+        let tickers: Vec<String> =
+            journal.trades().unwrap().into_iter().map(|trade| trade.ticker).collect();
+        assert_eq!(tickers, vec!["MSFT", "AAPL"])
+    }
+
+    #[test]
+    fn test_trade_unknown_id() {
+        let journal = TradeJournal::open_in_memory().unwrap();
+        assert_eq!(journal.trade(1).unwrap(), None)
+    }
+
+    #[test]
+    fn test_record_fill_and_read_fills_oldest_first() {
+        let journal = TradeJournal::open_in_memory().unwrap();
+        let filled_at = Utc.with_ymd_and_hms(2025, 2, 4, 15, 0, 0).unwrap();
+        let buy = RiskFill {
+            filled_at,
+            instrument: "AAPL".to_string(),
+            order_type: OrderType::Buy,
+            quantity: 10.0,
+            price: 100.0,
+        };
+        let sell = RiskFill { order_type: OrderType::Sell, price: 94.0, ..buy.clone() };
+        journal.record_fill(&buy).unwrap();
+        journal.record_fill(&sell).unwrap();
+        assert_eq!(journal.fills().unwrap(), vec![buy, sell])
+    }
+
+    #[test]
+    fn test_record_order_replaces_the_saved_state() {
+        let journal = TradeJournal::open_in_memory().unwrap();
+        let order = Order {
+            stock_quantity: 10.0,
+            stock: Instrument::stock("AAPL"),
+            order_type: OrderType::Buy,
+            style: OrderStyle::Limit { limit_price: 230.0 },
+            time_in_force: TimeInForce::Gtc,
+        };
+        let record = OrderRecord::new(5, &order);
+        let cancelled = OrderRecord { status: OrderStatus::Cancelled, ..record.clone() };
+        journal.record_order(&record).unwrap();
+        journal.record_order(&cancelled).unwrap();
+        journal.record_order(&OrderRecord::new(3, &order)).unwrap();
+        let order_ids: Vec<i32> =
+            journal.orders().unwrap().iter().map(|record| record.order_id).collect();
+        assert_eq!(order_ids, vec![3, 5]);
+        assert_eq!(journal.orders().unwrap()[1], cancelled)
+    }
+
+    #[test]
+    fn test_open_keeps_existing_trades() {
+        let path =
+            std::env::temp_dir().join(format!("trade_journal_{}.sqlite", std::process::id()));
+        let id = TradeJournal::open(&path).unwrap().record(&journal_entry()).unwrap();
+        let maybe_trade = TradeJournal::open(&path).unwrap().trade(id);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(maybe_trade.unwrap().unwrap().ticker, "AAPL")
+    }
+}
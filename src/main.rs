@@ -1,26 +1,106 @@
-use crate::models::{Money, Order, OrderType, Stock, StockInvestment};
+use crate::models::{
+    AdviceAction, Broker, Config, Currency, ExchangeRate, Instrument, Money, Order, OrderAdvice,
+    OrderAdviceResponse, OrderLevels, OrderStyle, OrderStyleKind, OrderType, Portfolio,
+    PriceHistory, RequestedInstrument, StockData, StockInvestment, TimeInForce,
+};
+use crate::backtest::{run_backtest, BacktestArgs, BacktestSettings};
+use crate::errors::AppErrors;
+use crate::ib_connection::{ConnectionHealth, IbConnection};
+use crate::journal::{JournalEntry, TradeJournal};
+use crate::market_data_cache::{CacheStats, MarketDataCache};
+use crate::orders::{OrderModification, OrderRecord, OrderStatus};
+use crate::paper_trading::PaperTradingService;
+use crate::risk::RiskEngine;
+use crate::watchlist::{WatchJob, Watchlist};
 use crate::services::{AiService, AiServiceLive, TradingApiService, TradingApiServiceLive};
-use axum::handler::Handler;
+use crate::sizing::{sell_quantity, PositionSizing, SizingInputs};
 use axum::http::HeaderMap;
 use axum::response::Html;
-use axum::routing::{get, post};
+use axum::routing::{delete, get, post};
 use axum::{Json, Router};
-use std::time::SystemTime;
+use chrono::Utc;
+use std::time::Duration;
 use std::sync::Arc;
-use axum::extract::State;
+use axum::extract::{Path, State};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
 
+mod alpha_vantage_client;
+#[cfg(test)]
+mod alpha_vantage_client_test;
+#[cfg(test)]
+mod alpha_vantage_fixture_server;
+mod backtest;
+#[cfg(test)]
+mod backtest_test;
 mod config;
+#[cfg(test)]
+mod config_test;
 mod errors;
+mod ib_connection;
+#[cfg(test)]
+mod ib_connection_test;
+mod journal;
+#[cfg(test)]
+mod journal_test;
+#[cfg(test)]
+mod main_test;
+mod market_data_cache;
+#[cfg(test)]
+mod market_data_cache_test;
 mod models;
+mod order_router;
+mod orders;
+#[cfg(test)]
+mod orders_test;
+#[cfg(test)]
 mod models_test;
+mod paper_trading;
+#[cfg(test)]
+mod paper_trading_test;
+mod rate_limiter;
+#[cfg(test)]
+mod rate_limiter_test;
+mod risk;
+#[cfg(test)]
+mod risk_test;
 mod services;
+#[cfg(test)]
+mod services_mock;
+#[cfg(test)]
 mod services_test;
+mod sizing;
+#[cfg(test)]
+mod sizing_test;
+mod watchlist;
+#[cfg(test)]
+mod watchlist_test;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct AnalysisRequest {
-    ticker: String,
-    amount: f64,
+    #[serde(flatten)]
+    instrument: RequestedInstrument,
+    /// The most a buy invests, in `currency`.
+    amount: Decimal,
+    /// The currency of the amount, USD when missing.
+    #[serde(default)]
+    currency: Currency,
+    #[serde(flatten)]
+    price_history: PriceHistory,
+    #[serde(default)]
+    order_style: OrderStyleKind,
+    #[serde(default)]
+    time_in_force: TimeInForce,
+    #[serde(flatten)]
+    levels: OrderLevels,
+    #[serde(default)]
+    sizing: PositionSizing,
+    /// The share of the position a sell sells, all of it when missing.
+    #[serde(default)]
+    sell_fraction: Option<f64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -36,42 +116,147 @@ struct AnalysisResponse {
     message: String,
     order_type: String,
     quantity: f64,
-    price: f64,
+    /// The average fill price, missing until the order filled.
+    price: Option<Money>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order_style: Option<OrderStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order_status: Option<OrderStatus>,
+    advice: OrderAdvice,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 struct RefreshRequest {
-    ticker: String,
+    #[serde(flatten)]
+    instrument: RequestedInstrument,
+    #[serde(flatten)]
+    price_history: PriceHistory,
 }
 
 #[derive(Debug, Serialize)]
 struct RefreshResponse {
     action_taken: String,
     stock_name: String,
-    invested_amount: f64,
-    current_price: f64,
+    invested_amount: Money,
+    current_price: Money,
+    advice: OrderAdvice,
 }
 
 #[derive(Clone)]
 struct AppState {
     trading_service: Arc<dyn TradingApiService + Send + Sync>,
     ai_service: Arc<dyn AiService + Send + Sync>,
+    journal: Arc<TradeJournal>,
+    watchlist: Arc<Watchlist>,
+    risk_engine: Arc<RiskEngine>,
+    ib_connection: Arc<IbConnection>,
+    market_data_cache: Option<Arc<MarketDataCache>>,
+}
+
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    interactive_brokers: ConnectionHealth,
+    market_data_cache: Option<CacheStats>,
 }
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "backtest") {
+        run_backtest_command(args[1..].to_vec()).await;
+        return;
+    }
+
+    let config = match config::load_from_args(args) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Failed to load the configuration: {}", error);
+            std::process::exit(1);
+        }
+    };
+    println!("Loaded configuration: {:?}", config);
+
+    let market_data_cache = match MarketDataCache::open(&config.market_data_cache) {
+        Ok(market_data_cache) => Arc::new(market_data_cache),
+        Err(error) => {
+            eprintln!(
+                "Failed to open the market data cache {}: {}",
+                config.market_data_cache.path, error
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let journal = match TradeJournal::open(&config.journal_path) {
+        Ok(journal) => Arc::new(journal),
+        Err(error) => {
+            eprintln!("Failed to open the trade journal {}: {}", config.journal_path, error);
+            std::process::exit(1);
+        }
+    };
+
+    let ib_connection =
+        Arc::new(IbConnection::new(&config.interactive_brokers_connection_url_with_port));
+    let live_trading_service = TradingApiServiceLive::new(config.clone(), ib_connection.clone())
+        .with_market_data_cache(market_data_cache.clone())
+        .with_journal(journal.clone());
+    let live_trading_service = match live_trading_service {
+        Ok(live_trading_service) => Arc::new(live_trading_service),
+        Err(error) => {
+            eprintln!("Failed to load the orders from the trade journal: {}", error);
+            std::process::exit(1);
+        }
+    };
+    let base_currency = match risk_base_currency(&config, &live_trading_service).await {
+        Ok(base_currency) => base_currency,
+        Err(error) => {
+            eprintln!("Failed to get the account's base currency: {}", error);
+            std::process::exit(1);
+        }
+    };
+    let trading_service: Arc<dyn TradingApiService + Send + Sync> = match config.broker {
+        Broker::Live => live_trading_service,
+        Broker::Paper => {
+            let paper_trading_service =
+                PaperTradingService::new(live_trading_service, config.paper_trading.clone());
+            println!(
+                "Paper trading with {:.2} cash, no orders will be sent to Interactive Brokers",
+                paper_trading_service.cash().to_f64()
+            );
+            Arc::new(paper_trading_service)
+        }
+    };
+
+    let risk_engine = match journal.fills() {
+        Ok(fills) => RiskEngine::new(config.risk.clone(), base_currency).with_fills(&fills),
+        Err(error) => {
+            eprintln!("Failed to read the fills from the trade journal: {}", error);
+            std::process::exit(1);
+        }
+    };
+
+    let watchlist = match Watchlist::open(&config.watchlist_path) {
+        Ok(watchlist) => watchlist,
+        Err(error) => {
+            eprintln!("Failed to open the watchlist {}: {}", config.watchlist_path, error);
+            std::process::exit(1);
+        }
+    };
+
     let state = AppState {
-        trading_service: Arc::new(TradingApiServiceLive),
-        ai_service: Arc::new(AiServiceLive),
+        trading_service,
+        risk_engine: Arc::new(risk_engine),
+        ai_service: Arc::new(AiServiceLive { config }),
+        journal,
+        watchlist: Arc::new(watchlist),
+        ib_connection,
+        market_data_cache: Some(market_data_cache),
     };
+    state.watchlist.resume(|job| spawn_watch_job(state.clone(), job.clone()));
 
-    let app = Router::new()
-        .route("/", get(handler))
-        .route("/analyze", post(analyze_investment))
-        .route("/refresh", post(refresh_investment))
-        .route("/style.css", get(serve_css))
-        .route("/app.js", get(serve_js))
-        .with_state(state);
+    let app = app(state);
 
     let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
         .await
@@ -81,154 +266,682 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
+/// The currency the risk limits are counted in and positions are valued in. A
+/// live account uses the one Interactive Brokers keeps it in, and a configured
+/// `risk_base_currency` has to match it. Paper trading takes the configured
+/// one over the paper account's.
+async fn risk_base_currency(
+    config: &Config,
+    live_trading_service: &TradingApiServiceLive,
+) -> Result<Currency, AppErrors> {
+    if config.broker == Broker::Paper {
+        return Ok(config.risk.base_currency.unwrap_or(config.paper_trading.base_currency));
+    }
+    let account_currency = live_trading_service.get_account_base_currency().await?;
+    match config.risk.base_currency {
+        Some(configured) if configured != account_currency => Err(AppErrors::ConfigError(format!(
+            "risk_base_currency is {configured}, but Interactive Brokers keeps the account in {account_currency}"
+        ))),
+        _ => Ok(account_currency),
+    }
+}
+
+/// `backtest --ticker AAPL --time-series [file]` replays saved Alpha Vantage
+/// responses through the model and prints the report as JSON. It only needs
+/// Ollama, neither Alpha Vantage nor Interactive Brokers are contacted.
+async fn run_backtest_command(args: Vec<String>) {
+    let (backtest_args, config_args) = match BacktestArgs::parse(args) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            eprintln!("Invalid backtest arguments: {}", error);
+            std::process::exit(1);
+        }
+    };
+    let config = match config::load_from_args(config_args) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Failed to load the configuration: {}", error);
+            std::process::exit(1);
+        }
+    };
+    let (bars, news) = match backtest_args.load_data() {
+        Ok(data) => data,
+        Err(error) => {
+            eprintln!("Failed to load the backtest data: {}", error);
+            std::process::exit(1);
+        }
+    };
+
+    let settings = BacktestSettings {
+        stock: Instrument::stock(&backtest_args.ticker),
+        price_history: backtest_args.price_history,
+        order_amount: backtest_args.order_amount,
+        paper_trading: config.paper_trading.clone(),
+    };
+    let ai_service = AiServiceLive { config };
+    match run_backtest(&ai_service, &settings, &bars, &news).await {
+        Ok(report) => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+        Err(error) => {
+            eprintln!("The backtest failed: {}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn app(state: AppState) -> Router {
+    Router::new()
+        .route("/", get(handler))
+        .route("/analyze", post(analyze_investment))
+        .route("/refresh", post(refresh_investment))
+        .route("/trades", get(list_trades))
+        .route("/trades/{id}", get(get_trade))
+        .route("/health", get(health))
+        .route("/portfolio", get(get_portfolio))
+        .route("/orders/{id}", get(get_order).delete(cancel_order).patch(modify_order))
+        .route("/watch", get(list_watch_jobs).post(watch))
+        .route("/watch/{ticker}", delete(unwatch))
+        .route("/style.css", get(serve_css))
+        .route("/app.js", get(serve_js))
+        .with_state(state)
+}
+
 async fn analyze_investment(
     State(state): State<AppState>,
     Json(payload): Json<AnalysisRequest>,
 ) -> Result<Json<AnalysisResponse>, Json<ErrorResponse>> {
-    let ticker_symbol = payload.ticker.clone();
-    let stock = Stock { ticker_symbol: payload.ticker.clone() };
+    let mut journal_entry = JournalEntry::new("analyze", &payload.instrument.0.to_string());
+    let result = analyze(&state, payload, &mut journal_entry).await;
+    record_in_journal(&state, journal_entry, &result);
+    result
+}
+
+async fn analyze(
+    state: &AppState,
+    payload: AnalysisRequest,
+    journal_entry: &mut JournalEntry,
+) -> Result<Json<AnalysisResponse>, Json<ErrorResponse>> {
+    let stock = payload.instrument.0.clone();
+    payload.sizing.validate().map_err(position_sizing_error)?;
 
-    let stock_data = state.trading_service.get_stock_data(stock.clone())
+    let stock_data = state.trading_service.get_stock_data(stock.clone(), payload.price_history)
         .await
         .map_err(|e| Json(ErrorResponse {
             error_type: "DATA_FETCH_FAILED".into(),
             message: "Failed to retrieve stock data".into(),
             details: Some(format!("{:?}", e)),
         }))?;
+    journal_entry.stock_data_hash = Some(stock_data.snapshot_hash());
 
-    let order_type = state.ai_service.get_order_advice(stock_data)
+    let order_advice_response = state.ai_service.get_order_advice(stock_data.clone())
         .await
         .map_err(|e| Json(ErrorResponse {
             error_type: "ORDER_ADVICE_FETCH_FAILED".into(),
             message: "Failed to retrieve order advice".into(),
             details: Some(format!("{:?}", e)),
         }))?;
+    let order_advice = journal_order_advice(journal_entry, order_advice_response);
 
-    let quantity = match order_type {
-        OrderType::Buy => state.trading_service.convert_money_amount_to_stock_quantity(
-            Money::new(payload.amount)
-                .map_err(|e| Json(ErrorResponse {
-                    error_type: "CONVERTING_MONEY_TO_STOCK_QUANTITY_FAILED".into(),
-                    message: "Failed to convert to stock quantity".into(),
-                    details: Some(format!("{:?}", e)),
-                }))?,
-            stock.clone(),
-        ),
-        OrderType::Sell => state.trading_service.get_quantity_to_sell_everything(stock.clone()),
-    }
+    let order_type = match order_advice.action.order_type() {
+        Some(order_type) => order_type,
+        None => {
+            return Ok(Json(AnalysisResponse {
+                message: "No trade, the AI advised to hold".to_string(),
+                order_type: "Hold".to_string(),
+                quantity: 0.0,
+                price: None,
+                order_style: None,
+                order_id: None,
+                order_status: None,
+                advice: order_advice,
+            }))
+        }
+    };
+
+    // Prices from the request win over the levels the AI proposed.
+    let order_style = payload.order_style
+        .with_levels(&order_type, payload.levels.or(order_advice.levels))
         .map_err(|e| Json(ErrorResponse {
-            error_type: "GETTING_THE_QUANTITY_FAILED".into(),
-            message: "Failed to get the quantity".into(),
+            error_type: "INVALID_ORDER_STYLE".into(),
+            message: format!("Failed to build the {} order", payload.order_style),
             details: Some(format!("{:?}", e)),
         }))?;
 
+    let quantity = match order_type {
+        OrderType::Buy => {
+            let buy_amount = size_buy(state, &payload, &stock_data, &order_advice).await?;
+            state.trading_service
+                .convert_money_amount_to_stock_quantity(buy_amount, stock.clone())
+                .await
+                .map_err(quantity_error)?
+        }
+        OrderType::Sell => {
+            let position = state.trading_service.get_quantity_to_sell_everything(stock.clone())
+                .await
+                .map_err(quantity_error)?;
+            match payload.sell_fraction {
+                Some(fraction) => {
+                    let lot_size = state.trading_service.get_lot_size(stock.clone())
+                        .await
+                        .map_err(quantity_error)?;
+                    sell_quantity(position, fraction, lot_size).map_err(|e| match e {
+                        AppErrors::QuantityBelowMinimumError(_) => quantity_error(e),
+                        _ => position_sizing_error(e),
+                    })?
+                }
+                None => position,
+            }
+        }
+    };
+    journal_entry.quantity = Some(quantity);
+
     let order = Order {
         stock_quantity: quantity,
-        stock: Stock { ticker_symbol: ticker_symbol.to_string() },
+        stock: stock.clone(),
         order_type,
-        timestamp: SystemTime::now(),
+        style: order_style,
+        time_in_force: payload.time_in_force,
     };
+    let price = current_price(&stock_data)?;
+    let exchange_rate = check_risk(state, &order, price).await?;
 
-    state.trading_service.place_order(order.clone())
+    let order_record = state.trading_service.place_order(order.clone()).await
         .map_err(|e| Json(ErrorResponse {
             error_type: "PLACING_THE_ORDER_FAILED".into(),
             message: "Failed to place the order".into(),
             details: Some(format!("Error: {:?}, Order: {:?}", e, order)),
         }))?;
+    let order_record =
+        track_placed_order(state, &order, order_record, price, &exchange_rate, journal_entry)?;
 
     Ok(Json(AnalysisResponse {
         message: "Analysis complete".to_string(),
         order_type: format!("{:?}", order.order_type),
         quantity: order.stock_quantity,
-        price: 1.1, // Dummy price; update as needed.
+        price: order_record
+            .average_fill_price
+            .and_then(|price| Money::from_f64(price, order.stock.currency()).ok()),
+        order_style: Some(order.style),
+        order_id: Some(order_record.order_id),
+        order_status: Some(order_record.status),
+        advice: order_advice,
     }))
 }
 
+/// The amount a buy invests with the requested sizing strategy, in the
+/// currency the stock trades in. The amount and the account's net liquidation
+/// value are converted to it first, the net liquidation value is only looked up
+/// for strategies that scale with it.
+async fn size_buy(
+    state: &AppState,
+    payload: &AnalysisRequest,
+    stock_data: &StockData,
+    order_advice: &OrderAdvice,
+) -> Result<Money, Json<ErrorResponse>> {
+    let currency = payload.instrument.0.currency();
+    let amount = Money::new(payload.amount, payload.currency)
+        .map_err(|e| Json(ErrorResponse {
+            error_type: "CONVERTING_MONEY_TO_STOCK_QUANTITY_FAILED".into(),
+            message: "Failed to convert to stock quantity".into(),
+            details: Some(format!("{:?}", e)),
+        }))?;
+    let amount = exchange(state, amount, currency).await?;
+    let equity = match payload.sizing.needs_equity() {
+        true => {
+            let net_liquidation_value = state.trading_service.get_portfolio()
+                .await
+                .map_err(|e| Json(ErrorResponse {
+                    error_type: "GET_PORTFOLIO_FAILED".into(),
+                    message: "Failed to get the net liquidation value to size the order".into(),
+                    details: Some(format!("{:?}", e)),
+                }))?
+                .net_liquidation_value;
+            Some(exchange(state, net_liquidation_value, currency).await?)
+        }
+        false => None,
+    };
+    payload.sizing
+        .buy_amount(&SizingInputs {
+            amount,
+            equity,
+            price: current_price(stock_data)?,
+            bars: &stock_data.stock_price_performance,
+            confidence: order_advice.confidence,
+        })
+        .map_err(position_sizing_error)
+}
+
+/// The money in `currency` at the latest exchange rate.
+async fn exchange(
+    state: &AppState,
+    money: Money,
+    currency: Currency,
+) -> Result<Money, Json<ErrorResponse>> {
+    if money.currency == currency {
+        return Ok(money);
+    }
+    state.trading_service.get_exchange_rate(money.currency, currency)
+        .await
+        .and_then(|exchange_rate| exchange_rate.convert(money))
+        .map_err(|e| Json(ErrorResponse {
+            error_type: "CURRENCY_CONVERSION_FAILED".into(),
+            message: format!("Failed to convert {} to {currency}", money.currency),
+            details: Some(format!("{:?}", e)),
+        }))
+}
+
+/// A quantity below one tradable unit gets its own error type, the amount or
+/// the position is too small rather than the lookup failing.
+fn quantity_error(e: AppErrors) -> Json<ErrorResponse> {
+    match e {
+        AppErrors::QuantityBelowMinimumError(_) => Json(ErrorResponse {
+            error_type: "QUANTITY_BELOW_MINIMUM".into(),
+            message: "The quantity is below one tradable unit".into(),
+            details: Some(format!("{:?}", e)),
+        }),
+        _ => Json(ErrorResponse {
+            error_type: "GETTING_THE_QUANTITY_FAILED".into(),
+            message: "Failed to get the quantity".into(),
+            details: Some(format!("{:?}", e)),
+        }),
+    }
+}
+
+fn position_sizing_error(e: AppErrors) -> Json<ErrorResponse> {
+    Json(ErrorResponse {
+        error_type: "POSITION_SIZING_FAILED".into(),
+        message: "Failed to size the order".into(),
+        details: Some(format!("{:?}", e)),
+    })
+}
+
 async fn refresh_investment(
     State(state): State<AppState>,
     Json(payload): Json<RefreshRequest>,
 ) -> Result<Json<RefreshResponse>, Json<ErrorResponse>> {
-    let stock = Stock { ticker_symbol: payload.ticker.clone() };
+    refresh_and_record(&state, payload, "refresh").await
+}
+
+async fn refresh_and_record(
+    state: &AppState,
+    payload: RefreshRequest,
+    endpoint: &str,
+) -> Result<Json<RefreshResponse>, Json<ErrorResponse>> {
+    let mut journal_entry = JournalEntry::new(endpoint, &payload.instrument.0.to_string());
+    let result = refresh(state, payload, &mut journal_entry).await;
+    record_in_journal(state, journal_entry, &result);
+    result
+}
+
+/// Runs the `/refresh` logic for the job right away and then every
+/// `every_minutes`, recording each run in the journal as `watch`.
+fn spawn_watch_job(state: AppState, job: WatchJob) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(Duration::from_secs(job.every_minutes * 60));
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            let payload = RefreshRequest {
+                instrument: job.instrument.clone(),
+                price_history: job.price_history,
+            };
+            if let Err(Json(error)) = refresh_and_record(&state, payload, "watch").await {
+                eprintln!("Watched refresh of {} failed: {:?}", job.key(), error);
+            }
+        }
+    })
+}
+
+async fn refresh(
+    state: &AppState,
+    payload: RefreshRequest,
+    journal_entry: &mut JournalEntry,
+) -> Result<Json<RefreshResponse>, Json<ErrorResponse>> {
+    let stock = payload.instrument.0;
 
-    let stock_data = state.trading_service.get_stock_data(stock.clone())
+    let stock_data = state.trading_service.get_stock_data(stock.clone(), payload.price_history)
         .await
         .map_err(|e| Json(ErrorResponse {
             error_type: "DATA_FETCH_FAILED".into(),
             message: "Failed to retrieve stock data".into(),
             details: Some(format!("{:?}", e)),
         }))?;
+    journal_entry.stock_data_hash = Some(stock_data.snapshot_hash());
 
-    let order_advice = state.ai_service.get_order_advice(stock_data.clone())
+    let order_advice_response = state.ai_service.get_order_advice(stock_data.clone())
         .await
         .map_err(|e| Json(ErrorResponse {
             error_type: "ORDER_ADVICE_FETCH_FAILED".into(),
             message: "Failed to retrieve order advice".into(),
             details: Some(format!("{:?}", e)),
         }))?;
+    let order_advice = journal_order_advice(journal_entry, order_advice_response);
+
+    let current_price = current_price(&stock_data)?;
 
     let mut action_taken = "No action taken".to_string();
-    if let OrderType::Sell = order_advice {
-        let sell_quantity = state.trading_service.get_quantity_to_sell_everything(stock.clone())
+    if order_advice.action == AdviceAction::Sell {
+        let sell_quantity = state.trading_service
+            .get_quantity_to_sell_everything(stock.clone())
+            .await
             .map_err(|e| Json(ErrorResponse {
                 error_type: "GET_QUANTITY_FAILED".into(),
                 message: "Failed to get quantity to sell".into(),
                 details: Some(format!("{:?}", e)),
             }))?;
+        journal_entry.quantity = Some(sell_quantity);
         let order = Order {
             stock_quantity: sell_quantity,
             stock: stock.clone(),
             order_type: OrderType::Sell,
-            timestamp: SystemTime::now(),
+            style: OrderStyle::Market,
+            time_in_force: TimeInForce::Day,
         };
-        state.trading_service.place_order(order)
+        let exchange_rate = check_risk(state, &order, current_price).await?;
+        let order_record = state.trading_service.place_order(order.clone()).await
             .map_err(|e| Json(ErrorResponse {
                 error_type: "PLACE_ORDER_FAILED".into(),
                 message: "Failed to place sell order".into(),
                 details: Some(format!("{:?}", e)),
             }))?;
+        track_placed_order(
+            state,
+            &order,
+            order_record,
+            current_price,
+            &exchange_rate,
+            journal_entry,
+        )?;
         action_taken = "Sell order placed".to_string();
     }
 
-    let investment = match state.trading_service.get_current_investment(stock.clone()) {
+    let investment = match state.trading_service.get_current_investment(stock.clone()).await {
         Ok(inv) => inv,
         Err(_e) => {
             StockInvestment {
                 stock: stock.clone(),
-                stock_name: stock.ticker_symbol.clone(),
-                current_invested_amount: Money::new(0.0).unwrap(),
+                stock_name: stock.to_string(),
+                current_invested_amount: Money::zero(stock.currency()),
+                quantity: 0.0,
+                average_cost: Money::zero(stock.currency()),
+                market_value: None,
+                unrealized_pnl: None,
+                realized_pnl: None,
             }
         }
     };
 
-    let current_price = stock_data.stock_price_performance
-        .last()
-        .ok_or(
-            Json(ErrorResponse {
-                error_type: "CURRENT_PRICE_FETCH_FAILED".into(),
-                message: "Failed to retrieve the current stock price".into(),
-                details: Some(format!("Last stock price performance data: {:?}", stock_data.clone().stock_price_performance.last())),
-            })
-        )
-        .and_then(|stock_price_performance|
-            stock_price_performance.low
-                .parse::<f64>()
-                .map_err(|e|
-                    Json(ErrorResponse {
-                        error_type: "CURRENT_PRICE_FETCH_FAILED".into(),
-                        message: "Failed to retrieve the current stock price".into(),
-                        details: Some(format!("{:?}", e)),
-                    })
-                )
-        )?;
-
     Ok(Json(RefreshResponse {
         action_taken,
         stock_name: investment.stock_name,
-        invested_amount: investment.current_invested_amount.amount,
-        current_price,
+        invested_amount: investment.current_invested_amount,
+        current_price: stock_data.current_price()
+            .map(|current_price| Money::from_decimal(current_price, stock.currency()))
+            .map_err(|e| Json(ErrorResponse {
+                error_type: "CURRENT_PRICE_FETCH_FAILED".into(),
+                message: "Failed to retrieve the current stock price".into(),
+                details: Some(format!("{:?}", e)),
+            }))?,
+        advice: order_advice,
     }))
 }
 
+fn current_price(stock_data: &StockData) -> Result<f64, Json<ErrorResponse>> {
+    stock_data
+        .current_price()
+        .ok()
+        .and_then(|current_price| current_price.to_f64())
+        .ok_or_else(|| Json(ErrorResponse {
+            error_type: "CURRENT_PRICE_FETCH_FAILED".into(),
+            message: "Failed to retrieve the current stock price".into(),
+            details: Some(format!("Latest stock price performance data: {:?}", stock_data.latest_bar())),
+        }))
+}
+
+/// Runs the order through the risk engine in its base currency and returns the
+/// exchange rate used. The current position is only looked up for the max
+/// position rule, an instrument without a position counts as zero.
+async fn check_risk(
+    state: &AppState,
+    order: &Order,
+    price: f64,
+) -> Result<ExchangeRate, Json<ErrorResponse>> {
+    let base_currency = state.risk_engine.base_currency();
+    let currency = order.stock.currency();
+    let exchange_rate = match currency == base_currency {
+        true => ExchangeRate::identity(currency),
+        false => state.trading_service.get_exchange_rate(currency, base_currency)
+            .await
+            .map_err(|e| Json(ErrorResponse {
+                error_type: "CURRENCY_CONVERSION_FAILED".into(),
+                message: format!("Failed to convert {currency} to {base_currency}"),
+                details: Some(format!("{:?}", e)),
+            }))?,
+    };
+    let position_notional = async {
+        let investment = state.trading_service.get_current_investment(order.stock.clone()).await;
+        let invested_amount = match investment {
+            Ok(investment) => investment.current_invested_amount,
+            Err(_) => return Ok(0.0),
+        };
+        if invested_amount.currency == base_currency {
+            return Ok(invested_amount.to_f64());
+        }
+        let exchange_rate = state.trading_service
+            .get_exchange_rate(invested_amount.currency, base_currency)
+            .await?;
+        Ok(exchange_rate.convert(invested_amount)?.to_f64())
+    };
+    state.risk_engine.check(order, price, &exchange_rate, position_notional, Utc::now()).await
+        .map_err(|e| match e {
+            AppErrors::RiskRejectedError(rejection) => Json(ErrorResponse {
+                error_type: "ORDER_REJECTED_BY_RISK_ENGINE".into(),
+                message: format!("The order was rejected by the {} rule", rejection.rule),
+                details: Some(format!("{}, Order: {:?}", rejection.message, order)),
+            }),
+            e => Json(ErrorResponse {
+                error_type: "RISK_CHECK_FAILED".into(),
+                message: "Failed to check the order against the risk limits".into(),
+                details: Some(format!("{:?}", e)),
+            }),
+        })?;
+    Ok(exchange_rate)
+}
+
+/// Journals the broker's record of a placed order and counts it for the risk
+/// engine at its fill price, keeping the fill for the next start. Orders the
+/// broker cancelled or refused are errors.
+fn track_placed_order(
+    state: &AppState,
+    order: &Order,
+    order_record: OrderRecord,
+    price: f64,
+    exchange_rate: &ExchangeRate,
+    journal_entry: &mut JournalEntry,
+) -> Result<OrderRecord, Json<ErrorResponse>> {
+    journal_entry.order_result = Some(
+        serde_json::to_string(&order_record).unwrap_or_else(|_| format!("{:?}", order_record)),
+    );
+    if matches!(order_record.status, OrderStatus::Cancelled | OrderStatus::Inactive) {
+        return Err(Json(ErrorResponse {
+            error_type: "ORDER_NOT_ACCEPTED".into(),
+            message: format!("The broker didn't accept order {}", order_record.order_id),
+            details: Some(format!(
+                "Status: {:?}, Messages: {:?}",
+                order_record.status, order_record.messages
+            )),
+        }));
+    }
+    let fill_price = order_record.average_fill_price.unwrap_or(price);
+    match state.risk_engine.record_fill(order, fill_price, exchange_rate, Utc::now()) {
+        Ok(fill) => {
+            if let Err(error) = state.journal.record_fill(&fill) {
+                eprintln!("Failed to record the fill in the journal: {}", error);
+            }
+        }
+        Err(error) => eprintln!("Failed to count the fill for the risk limits: {}", error),
+    }
+    Ok(order_record)
+}
+
+fn journal_order_advice(
+    journal_entry: &mut JournalEntry,
+    order_advice_response: OrderAdviceResponse,
+) -> OrderAdvice {
+    journal_entry.prompt = Some(order_advice_response.prompt);
+    journal_entry.raw_response = Some(order_advice_response.raw_response);
+    journal_entry.advice = Some(order_advice_response.order_advice.clone());
+    order_advice_response.order_advice
+}
+
+/// A journal that can't be written must not hide an order that was already
+/// placed, so failures are only logged.
+fn record_in_journal<T>(
+    state: &AppState,
+    mut journal_entry: JournalEntry,
+    result: &Result<T, Json<ErrorResponse>>,
+) {
+    if let Err(Json(error)) = result {
+        journal_entry.error = Some(match &error.details {
+            Some(details) => format!("{}: {}", error.error_type, details),
+            None => error.error_type.clone(),
+        });
+    }
+    if let Err(error) = state.journal.record(&journal_entry) {
+        eprintln!("Failed to record the trade in the journal: {}", error);
+    }
+}
+
+async fn list_watch_jobs(State(state): State<AppState>) -> Json<Vec<WatchJob>> {
+    Json(state.watchlist.jobs())
+}
+
+async fn watch(
+    State(state): State<AppState>,
+    Json(payload): Json<WatchJob>,
+) -> Result<Json<WatchJob>, Json<ErrorResponse>> {
+    let job = payload.validate()
+        .map_err(|e| Json(ErrorResponse {
+            error_type: "INVALID_WATCH_JOB".into(),
+            message: "The watch job is invalid".into(),
+            details: Some(format!("{:?}", e)),
+        }))?;
+    let task = spawn_watch_job(state.clone(), job.clone());
+    state.watchlist.insert(job.clone(), task)
+        .map_err(|e| Json(ErrorResponse {
+            error_type: "WATCHLIST_SAVE_FAILED".into(),
+            message: "Failed to save the watchlist".into(),
+            details: Some(format!("{:?}", e)),
+        }))?;
+    Ok(Json(job))
+}
+
+async fn unwatch(
+    State(state): State<AppState>,
+    Path(ticker): Path<String>,
+) -> Result<Json<WatchJob>, Json<ErrorResponse>> {
+    state.watchlist.remove(&ticker)
+        .map_err(|e| Json(ErrorResponse {
+            error_type: "WATCHLIST_SAVE_FAILED".into(),
+            message: "Failed to save the watchlist".into(),
+            details: Some(format!("{:?}", e)),
+        }))?
+        .map(Json)
+        .ok_or_else(|| Json(ErrorResponse {
+            error_type: "WATCH_JOB_NOT_FOUND".into(),
+            message: format!("{} isn't watched", ticker),
+            details: None,
+        }))
+}
+
+async fn list_trades(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<JournalEntry>>, Json<ErrorResponse>> {
+    state.journal.trades()
+        .map(Json)
+        .map_err(|e| Json(ErrorResponse {
+            error_type: "JOURNAL_READ_FAILED".into(),
+            message: "Failed to read the trade journal".into(),
+            details: Some(format!("{:?}", e)),
+        }))
+}
+
+async fn get_trade(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<JournalEntry>, Json<ErrorResponse>> {
+    state.journal.trade(id)
+        .map_err(|e| Json(ErrorResponse {
+            error_type: "JOURNAL_READ_FAILED".into(),
+            message: "Failed to read the trade journal".into(),
+            details: Some(format!("{:?}", e)),
+        }))?
+        .map(Json)
+        .ok_or_else(|| Json(ErrorResponse {
+            error_type: "TRADE_NOT_FOUND".into(),
+            message: format!("There is no trade with id {}", id),
+            details: None,
+        }))
+}
+
+async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
+    Json(HealthResponse {
+        interactive_brokers: state.ib_connection.health(),
+        market_data_cache: state.market_data_cache.as_ref().map(|cache| cache.stats()),
+    })
+}
+
+async fn get_portfolio(
+    State(state): State<AppState>,
+) -> Result<Json<Portfolio>, Json<ErrorResponse>> {
+    state.trading_service.get_portfolio().await
+        .map(Json)
+        .map_err(|e| Json(ErrorResponse {
+            error_type: "GET_PORTFOLIO_FAILED".into(),
+            message: "Failed to get the portfolio".into(),
+            details: Some(format!("{:?}", e)),
+        }))
+}
+
+async fn get_order(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Json<OrderRecord>, Json<ErrorResponse>> {
+    state.trading_service.get_order(id).await
+        .map(Json)
+        .ok_or_else(|| Json(ErrorResponse {
+            error_type: "ORDER_NOT_FOUND".into(),
+            message: format!("There is no order with id {}", id),
+            details: None,
+        }))
+}
+
+async fn cancel_order(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Json<OrderRecord>, Json<ErrorResponse>> {
+    state.trading_service.cancel_order(id).await
+        .map(Json)
+        .map_err(|e| Json(ErrorResponse {
+            error_type: "CANCEL_ORDER_FAILED".into(),
+            message: format!("Failed to cancel order {}", id),
+            details: Some(format!("{:?}", e)),
+        }))
+}
+
+async fn modify_order(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(payload): Json<OrderModification>,
+) -> Result<Json<OrderRecord>, Json<ErrorResponse>> {
+    state.trading_service.modify_order(id, payload).await
+        .map(Json)
+        .map_err(|e| Json(ErrorResponse {
+            error_type: "MODIFY_ORDER_FAILED".into(),
+            message: format!("Failed to modify order {}", id),
+            details: Some(format!("{:?}", e)),
+        }))
+}
+
 async fn handler() -> Html<&'static str> {
     Html(include_str!("../index.html"))
 }
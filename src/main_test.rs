@@ -0,0 +1,1350 @@
+use crate::models::{
+    AdviceAction, Currency, Instrument, Money, Order, OrderAdvice, OrderLevels, OrderStyle, OrderType,
+    PriceHistory, StockData, StockInvestment, StockPricePerformance, TimeInForce,
+};
+use crate::orders::{Fill, OrderRecord};
+use crate::services_mock::{AiServiceMock, TradingApiServiceMock};
+use crate::ib_connection::IbConnection;
+use crate::journal::TradeJournal;
+use crate::risk::RiskEngine;
+use crate::watchlist::Watchlist;
+use crate::{app, AppState};
+use axum::body::{to_bytes, Body};
+use axum::http::Request;
+use serde_json::Value;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+fn stock() -> Instrument {
+    Instrument::stock("AAPL")
+}
+
+fn stock_data() -> StockData {
+    StockData {
+        stock: stock(),
+        price_history: PriceHistory::default(),
+        stock_price_performance: vec![
+            StockPricePerformance {
+                date: NaiveDate::from_ymd_opt(2024, 12, 31).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+                open: Decimal::new(23727, 2),
+                high: Decimal::new(26010, 2),
+                low: Decimal::new(23716, 2),
+                close: Decimal::new(25042, 2),
+                volume: 977_942_014,
+            },
+            StockPricePerformance {
+                date: NaiveDate::from_ymd_opt(2025, 1, 31).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+                open: Decimal::new(2305, 1),
+                high: Decimal::new(2401, 1),
+                low: Decimal::new(22525, 2),
+                close: Decimal::new(23675, 2),
+                volume: 1_201_356_845,
+            },
+        ],
+        news: vec![],
+    }
+}
+
+fn order_advice(action: AdviceAction) -> OrderAdvice {
+    OrderAdvice {
+        action,
+        confidence: 0.8,
+        rationale: "Earnings beat expectations".to_string(),
+        key_factors: vec!["Apple Earnings Beat".to_string()],
+        levels: OrderLevels::default(),
+    }
+}
+
+fn order(order_type: OrderType, quantity: f64) -> Order {
+    Order {
+        stock_quantity: quantity,
+        stock: stock(),
+        order_type,
+        style: OrderStyle::Market,
+        time_in_force: TimeInForce::Day,
+    }
+}
+
+/// The broker's record of order 7, filled at once with a commission of 1.
+fn filled_order(order_type: OrderType, quantity: f64, price: f64) -> OrderRecord {
+    OrderRecord::new(7, &order(order_type, quantity)).filled(Fill {
+        execution_id: "0001f4e8.67a1c2b3.01.01".to_string(),
+        time: "20250204 15:30:00".to_string(),
+        quantity,
+        price,
+        commission: Some(1.0),
+    })
+}
+
+fn dollars(amount: f64) -> Money {
+    Money::from_f64(amount, Currency::USD).unwrap()
+}
+
+fn investment(stock_name: &str, invested_amount: f64) -> StockInvestment {
+    StockInvestment {
+        stock: stock(),
+        stock_name: stock_name.to_string(),
+        current_invested_amount: dollars(invested_amount),
+        quantity: invested_amount / 236.5,
+        average_cost: dollars(236.5),
+        market_value: None,
+        unrealized_pnl: None,
+        realized_pnl: None,
+    }
+}
+
+fn app_state(
+    trading_service: Arc<TradingApiServiceMock>,
+    ai_service: Arc<AiServiceMock>,
+) -> AppState {
+    AppState {
+        trading_service,
+        ai_service,
+        journal: Arc::new(TradeJournal::open_in_memory().unwrap()),
+        watchlist: Arc::new(Watchlist::in_memory()),
+        risk_engine: Arc::new(RiskEngine::new(Default::default(), Currency::USD)),
+        ib_connection: Arc::new(IbConnection::new("127.0.0.1:4002")),
+        market_data_cache: None,
+    }
+}
+
+async fn send(state: AppState, request: Request<Body>) -> Value {
+    let response = app(state).oneshot(request).await.unwrap();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+async fn post_json(
+    trading_service: Arc<TradingApiServiceMock>,
+    ai_service: Arc<AiServiceMock>,
+    uri: &str,
+    body: Value,
+) -> Value {
+    send(app_state(trading_service, ai_service), post_request(uri, body)).await
+}
+
+fn post_request(uri: &str, body: Value) -> Request<Body> {
+    Request::post(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+mod analyze_investment {
+    use super::{
+        app_state, dollars, filled_order, order_advice, post_json, post_request, stock,
+        stock_data,
+    };
+    use crate::app;
+    use crate::errors::AppErrors;
+    use crate::orders::OrderStatus;
+    use crate::models::{
+        AdviceAction, Currency, ExchangeRate, Instrument, Money, OrderAdvice, OrderLevels,
+        OrderStyle, OrderType, Portfolio, PriceHistory, PriceInterval, StockData, TimeInForce,
+    };
+    use crate::services_mock::{AiServiceMock, TradingApiCall, TradingApiServiceMock};
+    use crate::sizing::LotSize;
+    use rust_decimal::Decimal;
+    use serde_json::json;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_analyze_investment_buy_success() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_stock_quantity(Ok(3.0))
+                .with_place_order(Ok(filled_order(OrderType::Buy, 3.0, 236.5))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service.clone(),
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 700.0 }),
+        )
+        .await;
+
+        assert_eq!(response["order_type"], "Buy");
+        assert_eq!(response["quantity"], 3.0);
+        assert_eq!(response["price"], json!({ "amount": "236.5", "currency": "USD" }));
+        assert_eq!(response["order_id"], 7);
+        assert_eq!(response["order_status"], "filled");
+        assert_eq!(response["advice"]["action"], "BUY");
+        assert_eq!(response["advice"]["rationale"], "Earnings beat expectations");
+        assert_eq!(ai_service.calls().len(), 1);
+        assert!(matches!(
+            trading_service.calls().as_slice(),
+            [
+                TradingApiCall::GetStockData(_, _),
+                TradingApiCall::ConvertMoneyAmountToStockQuantity(amount, _),
+                TradingApiCall::PlaceOrder(_),
+            ] if amount.amount == Decimal::from(700) && amount.currency == Currency::USD
+        ));
+        assert!(trading_service.calls().iter().all(|call| call.stock() == Some(&stock())));
+        let placed_orders = trading_service.placed_orders();
+        assert_eq!(placed_orders[0].stock, stock());
+        assert_eq!(placed_orders[0].order_type, OrderType::Buy);
+        assert_eq!(placed_orders[0].stock_quantity, 3.0)
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_buy_option() {
+        let call: Instrument = serde_json::from_value(json!({
+            "security_type": "option",
+            "symbol": "AAPL",
+            "expiry": "20261218",
+            "strike": 200,
+            "right": "call"
+        }))
+        .unwrap();
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(StockData { stock: call.clone(), ..stock_data() }))
+                .with_stock_quantity(Ok(1.0))
+                .with_place_order(Ok(filled_order(OrderType::Buy, 1.0, 2.5))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({ "instrument": call, "amount": 700.0 }),
+        )
+        .await;
+
+        assert_eq!(response["order_type"], "Buy");
+        assert!(trading_service.calls().iter().all(|call_made| call_made.stock() == Some(&call)));
+        assert_eq!(trading_service.placed_orders()[0].stock, call)
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_sell_success() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_quantity_to_sell_everything(Ok(7.0))
+                .with_place_order(Ok(filled_order(OrderType::Sell, 7.0, 236.5))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Sell))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 700.0 }),
+        )
+        .await;
+
+        assert_eq!(response["order_type"], "Sell");
+        assert_eq!(response["quantity"], 7.0);
+        let placed_orders = trading_service.placed_orders();
+        assert_eq!(placed_orders.len(), 1);
+        assert_eq!(placed_orders[0].order_type, OrderType::Sell);
+        assert_eq!(placed_orders[0].stock_quantity, 7.0)
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_buy_percent_of_equity() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_portfolio(Ok(Portfolio {
+                    base_currency: Currency::USD,
+                    positions: vec![],
+                    cash: dollars(10_000.0),
+                    net_liquidation_value: dollars(10_000.0),
+                }))
+                .with_stock_quantity(Ok(2.0))
+                .with_place_order(Ok(filled_order(OrderType::Buy, 2.0, 236.5))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({
+                "ticker": "AAPL",
+                "amount": 700.0,
+                "sizing": { "strategy": "percent_of_equity", "percent": 5.0 },
+            }),
+        )
+        .await;
+
+        assert_eq!(response["quantity"], 2.0);
+        assert!(matches!(
+            trading_service.calls().as_slice(),
+            [
+                TradingApiCall::GetStockData(_, _),
+                TradingApiCall::GetPortfolio,
+                TradingApiCall::ConvertMoneyAmountToStockQuantity(amount, _),
+                TradingApiCall::PlaceOrder(_),
+            ] if amount.amount == Decimal::from(500)
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_buy_amount_in_another_currency() {
+        let euros: Currency = "EUR".parse().unwrap();
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_exchange_rate(Ok(ExchangeRate {
+                    from: euros,
+                    to: Currency::USD,
+                    rate: Decimal::new(10842, 4),
+                }))
+                .with_stock_quantity(Ok(3.0))
+                .with_place_order(Ok(filled_order(OrderType::Buy, 3.0, 236.5))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 700.0, "currency": "EUR" }),
+        )
+        .await;
+
+        assert_eq!(response["quantity"], 3.0);
+        assert!(matches!(
+            trading_service.calls().as_slice(),
+            [
+                TradingApiCall::GetStockData(_, _),
+                TradingApiCall::GetExchangeRate(from, Currency::USD),
+                TradingApiCall::ConvertMoneyAmountToStockQuantity(amount, _),
+                TradingApiCall::PlaceOrder(_),
+            ] if *from == euros && *amount == dollars(758.94)
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_sizes_the_equity_in_the_stock_currency() {
+        let euros: Currency = "EUR".parse().unwrap();
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_portfolio(Ok(Portfolio {
+                    base_currency: euros,
+                    positions: vec![],
+                    cash: Money::from_decimal(Decimal::from(10_000), euros),
+                    net_liquidation_value: Money::from_decimal(Decimal::from(10_000), euros),
+                }))
+                .with_exchange_rate(Ok(ExchangeRate {
+                    from: euros,
+                    to: Currency::USD,
+                    rate: Decimal::new(11, 1),
+                }))
+                .with_stock_quantity(Ok(2.0))
+                .with_place_order(Ok(filled_order(OrderType::Buy, 2.0, 236.5))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({
+                "ticker": "AAPL",
+                "amount": 700.0,
+                "sizing": { "strategy": "percent_of_equity", "percent": 5.0 },
+            }),
+        )
+        .await;
+
+        // 5% of the 11,000 USD the 10,000 EUR are worth.
+        assert!(matches!(
+            trading_service.calls().as_slice(),
+            [
+                TradingApiCall::GetStockData(_, _),
+                TradingApiCall::GetPortfolio,
+                TradingApiCall::GetExchangeRate(..),
+                TradingApiCall::ConvertMoneyAmountToStockQuantity(amount, _),
+                TradingApiCall::PlaceOrder(_),
+            ] if *amount == dollars(550.0)
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_exchange_rate_failed() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_exchange_rate(Err(AppErrors::RateLimitError("Throttled".to_string()))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 700.0, "currency": "EUR" }),
+        )
+        .await;
+
+        assert_eq!(response["error_type"], "CURRENCY_CONVERSION_FAILED");
+        assert!(trading_service.placed_orders().is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_amount_below_one_share() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_stock_quantity(Err(AppErrors::QuantityBelowMinimumError(
+                    "A quantity of 0.42 is below the minimum order size of 1".to_string(),
+                ))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 100.0 }),
+        )
+        .await;
+
+        assert_eq!(response["error_type"], "QUANTITY_BELOW_MINIMUM");
+        assert!(trading_service.placed_orders().is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_invalid_sizing() {
+        let trading_service = Arc::new(TradingApiServiceMock::new());
+
+        let response = post_json(
+            trading_service.clone(),
+            Arc::new(AiServiceMock::new()),
+            "/analyze",
+            json!({
+                "ticker": "AAPL",
+                "amount": 700.0,
+                "sizing": { "strategy": "percent_of_equity", "percent": 150.0 },
+            }),
+        )
+        .await;
+
+        assert_eq!(response["error_type"], "POSITION_SIZING_FAILED");
+        assert!(trading_service.calls().is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_partial_sell() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_quantity_to_sell_everything(Ok(7.0))
+                .with_lot_size(Ok(LotSize { min_size: 0.5, size_increment: 0.5 }))
+                .with_place_order(Ok(filled_order(OrderType::Sell, 3.5, 236.5))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Sell))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 700.0, "sell_fraction": 0.5 }),
+        )
+        .await;
+
+        assert_eq!(response["quantity"], 3.5);
+        assert_eq!(trading_service.placed_orders()[0].stock_quantity, 3.5)
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_passes_price_history() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_stock_quantity(Ok(3.0))
+                .with_place_order(Ok(filled_order(OrderType::Buy, 3.0, 236.5))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 700.0, "interval": "5min", "lookback": 78 }),
+        )
+        .await;
+
+        assert!(matches!(
+            trading_service.calls().first(),
+            Some(TradingApiCall::GetStockData(
+                _,
+                PriceHistory { interval: PriceInterval::FiveMinutes, lookback: Some(78) },
+            ))
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_bracket_uses_advised_levels() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_stock_quantity(Ok(3.0))
+                .with_place_order(Ok(filled_order(OrderType::Buy, 3.0, 236.0))),
+        );
+        let advice = OrderAdvice {
+            levels: OrderLevels {
+                limit_price: Some(236.0),
+                take_profit_price: Some(250.0),
+                stop_loss_price: Some(225.0),
+                ..OrderLevels::default()
+            },
+            ..order_advice(AdviceAction::Buy)
+        };
+        let ai_service = Arc::new(AiServiceMock::new().with_order_advice(Ok(advice)));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({
+                "ticker": "AAPL",
+                "amount": 700.0,
+                "order_style": "bracket",
+                "time_in_force": "GTC",
+                "limit_price": 235.5,
+            }),
+        )
+        .await;
+
+        let placed_orders = trading_service.placed_orders();
+        assert_eq!(response["order_style"]["style"], "bracket");
+        assert_eq!(
+            placed_orders[0].style,
+            OrderStyle::Bracket {
+                limit_price: 235.5,
+                take_profit_price: 250.0,
+                stop_loss_price: 225.0,
+            }
+        );
+        assert_eq!(placed_orders[0].time_in_force, TimeInForce::Gtc)
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_limit_without_price() {
+        let trading_service =
+            Arc::new(TradingApiServiceMock::new().with_stock_data(Ok(stock_data())));
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 700.0, "order_style": "limit" }),
+        )
+        .await;
+
+        assert_eq!(response["error_type"], "INVALID_ORDER_STYLE");
+        assert!(trading_service.placed_orders().is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_invalid_interval() {
+        let trading_service = Arc::new(TradingApiServiceMock::new());
+        let ai_service = Arc::new(AiServiceMock::new());
+
+        let app_response = app(app_state(trading_service.clone(), ai_service))
+            .oneshot(post_request(
+                "/analyze",
+                json!({ "ticker": "AAPL", "amount": 700.0, "interval": "2min" }),
+            ))
+            .await
+            .unwrap();
+
+        assert!(app_response.status().is_client_error());
+        assert!(trading_service.calls().is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_ticker_and_instrument() {
+        let trading_service = Arc::new(TradingApiServiceMock::new());
+        let ai_service = Arc::new(AiServiceMock::new());
+
+        let app_response = app(app_state(trading_service.clone(), ai_service))
+            .oneshot(post_request(
+                "/analyze",
+                json!({
+                    "ticker": "AAPL",
+                    "instrument": { "security_type": "stock", "symbol": "AAPL" },
+                    "amount": 700.0
+                }),
+            ))
+            .await
+            .unwrap();
+
+        assert!(app_response.status().is_client_error());
+        assert!(trading_service.calls().is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_data_fetch_failure() {
+        let trading_service = Arc::new(TradingApiServiceMock::new().with_stock_data(Err(
+            AppErrors::GetStockDataError("rate limited".to_string()),
+        )));
+        let ai_service = Arc::new(AiServiceMock::new());
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service.clone(),
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 700.0 }),
+        )
+        .await;
+
+        assert_eq!(response["error_type"], "DATA_FETCH_FAILED");
+        assert!(ai_service.calls().is_empty());
+        assert!(trading_service.placed_orders().is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_order_advice_failure() {
+        let trading_service =
+            Arc::new(TradingApiServiceMock::new().with_stock_data(Ok(stock_data())));
+        let ai_service = Arc::new(AiServiceMock::new().with_order_advice(Err(
+            AppErrors::GetOrderAdviceError("unclear".to_string()),
+        )));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 700.0 }),
+        )
+        .await;
+
+        assert_eq!(response["error_type"], "ORDER_ADVICE_FETCH_FAILED");
+        assert!(trading_service.placed_orders().is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_hold_places_no_order() {
+        let trading_service =
+            Arc::new(TradingApiServiceMock::new().with_stock_data(Ok(stock_data())));
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Hold))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 700.0 }),
+        )
+        .await;
+
+        assert_eq!(response["message"], "No trade, the AI advised to hold");
+        assert_eq!(response["order_type"], "Hold");
+        assert_eq!(response["quantity"], 0.0);
+        assert_eq!(response["advice"]["action"], "HOLD");
+        assert_eq!(trading_service.calls().len(), 1)
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_invalid_amount() {
+        let trading_service =
+            Arc::new(TradingApiServiceMock::new().with_stock_data(Ok(stock_data())));
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 10.123 }),
+        )
+        .await;
+
+        assert_eq!(response["error_type"], "CONVERTING_MONEY_TO_STOCK_QUANTITY_FAILED");
+        assert!(trading_service.placed_orders().is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_place_order_failure() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_stock_quantity(Ok(3.0))
+                .with_place_order(Err(AppErrors::PlaceOrderError("rejected".to_string()))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        let response = post_json(
+            trading_service,
+            ai_service,
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 700.0 }),
+        )
+        .await;
+
+        assert_eq!(response["error_type"], "PLACING_THE_ORDER_FAILED")
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_order_not_accepted() {
+        let mut cancelled_order = filled_order(OrderType::Buy, 3.0, 236.5);
+        cancelled_order.status = OrderStatus::Cancelled;
+        cancelled_order.messages.push("Order rejected - no trading permissions".to_string());
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_stock_quantity(Ok(3.0))
+                .with_place_order(Ok(cancelled_order)),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        let response = post_json(
+            trading_service,
+            ai_service,
+            "/analyze",
+            json!({ "ticker": "AAPL", "amount": 700.0 }),
+        )
+        .await;
+
+        assert_eq!(response["error_type"], "ORDER_NOT_ACCEPTED");
+        assert!(response["details"].as_str().unwrap().contains("no trading permissions"))
+    }
+}
+
+mod refresh_investment {
+    use super::{filled_order, investment, order_advice, post_json, stock_data};
+    use crate::errors::AppErrors;
+    use crate::models::{AdviceAction, OrderType, PriceHistory, PriceInterval};
+    use crate::services_mock::{AiServiceMock, TradingApiCall, TradingApiServiceMock};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_refresh_investment_sell_success() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_quantity_to_sell_everything(Ok(4.0))
+                .with_place_order(Ok(filled_order(OrderType::Sell, 4.0, 236.5)))
+                .with_current_investment(Err(AppErrors::GetCurrentInvestmentError(
+                    "No position".to_string(),
+                ))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Sell))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/refresh",
+            json!({ "ticker": "AAPL" }),
+        )
+        .await;
+
+        assert_eq!(response["action_taken"], "Sell order placed");
+        assert_eq!(response["advice"]["action"], "SELL");
+        assert_eq!(response["stock_name"], "AAPL");
+        assert_eq!(response["invested_amount"], json!({ "amount": "0", "currency": "USD" }));
+        assert_eq!(response["current_price"], json!({ "amount": "236.75", "currency": "USD" }));
+        let placed_orders = trading_service.placed_orders();
+        assert_eq!(placed_orders.len(), 1);
+        assert_eq!(placed_orders[0].stock_quantity, 4.0)
+    }
+
+    #[tokio::test]
+    async fn test_refresh_investment_defaults_to_monthly_bars() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_current_investment(Ok(investment("Apple Inc.", 1200.5))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        post_json(trading_service.clone(), ai_service, "/refresh", json!({ "ticker": "AAPL" }))
+            .await;
+
+        assert!(matches!(
+            trading_service.calls().first(),
+            Some(TradingApiCall::GetStockData(_, price_history))
+                if *price_history == PriceHistory::default()
+                    && price_history.interval == PriceInterval::Monthly
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_refresh_investment_buy_takes_no_action() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_current_investment(Ok(investment("Apple Inc.", 1200.5))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/refresh",
+            json!({ "ticker": "AAPL" }),
+        )
+        .await;
+
+        assert_eq!(response["action_taken"], "No action taken");
+        assert_eq!(response["stock_name"], "Apple Inc.");
+        assert_eq!(response["invested_amount"]["amount"], "1200.5");
+        assert!(trading_service.placed_orders().is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_investment_hold_takes_no_action() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_current_investment(Ok(investment("Apple Inc.", 1200.5))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Hold))));
+
+        let response = post_json(
+            trading_service.clone(),
+            ai_service,
+            "/refresh",
+            json!({ "ticker": "AAPL" }),
+        )
+        .await;
+
+        assert_eq!(response["action_taken"], "No action taken");
+        assert_eq!(response["advice"]["action"], "HOLD");
+        assert!(trading_service.placed_orders().is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_investment_place_order_failure() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_quantity_to_sell_everything(Ok(4.0))
+                .with_place_order(Err(AppErrors::PlaceOrderError("rejected".to_string()))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Sell))));
+
+        let response =
+            post_json(trading_service, ai_service, "/refresh", json!({ "ticker": "AAPL" })).await;
+
+        assert_eq!(response["error_type"], "PLACE_ORDER_FAILED")
+    }
+}
+
+mod risk_engine {
+    use super::{
+        app_state, filled_order, investment, order_advice, post_request, send, stock_data,
+    };
+    use crate::errors::AppErrors;
+    use crate::models::{AdviceAction, Currency, ExchangeRate, OrderType, RiskLimits};
+    use crate::risk::RiskEngine;
+    use crate::services_mock::{AiServiceMock, TradingApiCall, TradingApiServiceMock};
+    use axum::body::Body;
+    use axum::http::Request;
+    use rust_decimal::Decimal;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_analyze_investment_rejected_by_max_order_notional() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_stock_quantity(Ok(3.0)),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+        let mut state = app_state(trading_service.clone(), ai_service);
+        let limits = RiskLimits { max_order_notional: Some(500.0), ..RiskLimits::default() };
+        state.risk_engine = Arc::new(RiskEngine::new(limits, Currency::USD));
+
+        let response = send(
+            state.clone(),
+            post_request("/analyze", json!({ "ticker": "AAPL", "amount": 700.0 })),
+        )
+        .await;
+        let trades = send(state, Request::get("/trades").body(Body::empty()).unwrap()).await;
+
+        assert_eq!(response["error_type"], "ORDER_REJECTED_BY_RISK_ENGINE");
+        assert!(response["message"].as_str().unwrap().contains("max_order_notional"));
+        assert!(trading_service.placed_orders().is_empty());
+        assert!(trades[0]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("ORDER_REJECTED_BY_RISK_ENGINE"))
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_rejected_by_max_position_notional() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_stock_quantity(Ok(3.0))
+                .with_current_investment(Ok(investment("AAPL", 1000.0))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+        let mut state = app_state(trading_service.clone(), ai_service);
+        let limits = RiskLimits { max_position_notional: Some(1500.0), ..RiskLimits::default() };
+        state.risk_engine = Arc::new(RiskEngine::new(limits, Currency::USD));
+
+        let response =
+            send(state, post_request("/analyze", json!({ "ticker": "AAPL", "amount": 700.0 })))
+                .await;
+
+        assert_eq!(response["error_type"], "ORDER_REJECTED_BY_RISK_ENGINE");
+        assert!(response["message"].as_str().unwrap().contains("max_position_notional"));
+        assert!(trading_service.placed_orders().is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_analyze_investment_limits_are_in_the_base_currency() {
+        let euros: Currency = "EUR".parse().unwrap();
+        let euro_rate = ExchangeRate { from: Currency::USD, to: euros, rate: Decimal::new(5, 1) };
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_stock_quantity(Ok(3.0))
+                .with_exchange_rate(Ok(euro_rate))
+                .with_current_investment(Ok(investment("AAPL", 1000.0)))
+                .with_exchange_rate(Ok(euro_rate))
+                .with_place_order(Ok(filled_order(OrderType::Buy, 3.0, 236.5))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+        let mut state = app_state(trading_service.clone(), ai_service);
+        // 500 EUR invested plus about 355 EUR for the order.
+        let limits = RiskLimits { max_position_notional: Some(1000.0), ..RiskLimits::default() };
+        state.risk_engine = Arc::new(RiskEngine::new(limits, euros));
+
+        send(state, post_request("/analyze", json!({ "ticker": "AAPL", "amount": 700.0 }))).await;
+
+        assert_eq!(trading_service.placed_orders().len(), 1);
+        assert!(trading_service
+            .calls()
+            .iter()
+            .any(|call| matches!(call, TradingApiCall::GetExchangeRate(Currency::USD, to) if *to == euros)))
+    }
+
+    #[tokio::test]
+    async fn test_refresh_investment_rejected_by_denied_ticker() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_quantity_to_sell_everything(Ok(4.0))
+                .with_current_investment(Err(AppErrors::GetCurrentInvestmentError(
+                    "No position".to_string(),
+                ))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Sell))));
+        let mut state = app_state(trading_service.clone(), ai_service);
+        let limits =
+            RiskLimits { denied_tickers: vec!["AAPL".to_string()], ..RiskLimits::default() };
+        state.risk_engine = Arc::new(RiskEngine::new(limits, Currency::USD));
+
+        let response = send(state, post_request("/refresh", json!({ "ticker": "aapl" }))).await;
+
+        assert_eq!(response["error_type"], "ORDER_REJECTED_BY_RISK_ENGINE");
+        assert!(response["message"].as_str().unwrap().contains("denied_tickers"));
+        assert!(trading_service.placed_orders().is_empty())
+    }
+}
+
+mod trade_journal {
+    use super::{app_state, filled_order, order_advice, post_request, send, stock_data};
+    use crate::errors::AppErrors;
+    use crate::models::{AdviceAction, OrderType};
+    use crate::services_mock::{AiServiceMock, TradingApiServiceMock};
+    use axum::body::Body;
+    use axum::http::Request;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    fn get_request(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_trades_records_analysis() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(stock_data()))
+                .with_stock_quantity(Ok(3.0))
+                .with_place_order(Ok(filled_order(OrderType::Buy, 3.0, 236.5))),
+        );
+        let ai_service =
+            Arc::new(AiServiceMock::new().with_order_advice(Ok(order_advice(AdviceAction::Buy))));
+        let state = app_state(trading_service, ai_service);
+
+        send(state.clone(), post_request("/analyze", json!({ "ticker": "AAPL", "amount": 700.0 })))
+            .await;
+        let trades = send(state.clone(), get_request("/trades")).await;
+        let trade = send(state, get_request(&format!("/trades/{}", trades[0]["id"]))).await;
+
+        assert_eq!(trades.as_array().unwrap().len(), 1);
+        assert_eq!(trade, trades[0]);
+        assert_eq!(trade["endpoint"], "analyze");
+        assert_eq!(trade["ticker"], "AAPL");
+        assert_eq!(trade["stock_data_hash"], stock_data().snapshot_hash());
+        assert!(trade["prompt"].as_str().unwrap().contains("Ticker: AAPL"));
+        assert_eq!(
+            trade["raw_response"],
+            serde_json::to_string(&order_advice(AdviceAction::Buy)).unwrap()
+        );
+        assert_eq!(trade["advice"]["action"], "BUY");
+        assert_eq!(trade["quantity"], 3.0);
+        let order_result: Value =
+            serde_json::from_str(trade["order_result"].as_str().unwrap()).unwrap();
+        assert_eq!(order_result["order_id"], 7);
+        assert_eq!(order_result["status"], "filled");
+        assert!(trade["error"].is_null())
+    }
+
+    #[tokio::test]
+    async fn test_trades_records_errors() {
+        let trading_service = Arc::new(TradingApiServiceMock::new().with_stock_data(Err(
+            AppErrors::GetStockDataError("rate limited".to_string()),
+        )));
+        let state = app_state(trading_service, Arc::new(AiServiceMock::new()));
+
+        send(state.clone(), post_request("/refresh", json!({ "ticker": "AAPL" }))).await;
+        let trades = send(state, get_request("/trades")).await;
+
+        assert_eq!(trades[0]["endpoint"], "refresh");
+        assert!(trades[0]["stock_data_hash"].is_null());
+        assert!(trades[0]["error"].as_str().unwrap().starts_with("DATA_FETCH_FAILED"));
+        assert!(trades[0]["error"].as_str().unwrap().contains("rate limited"))
+    }
+
+    #[tokio::test]
+    async fn test_trade_not_found() {
+        let state = app_state(
+            Arc::new(TradingApiServiceMock::new()),
+            Arc::new(AiServiceMock::new()),
+        );
+        let response = send(state, get_request("/trades/42")).await;
+        assert_eq!(response["error_type"], "TRADE_NOT_FOUND")
+    }
+}
+
+mod orders {
+    use super::{app_state, filled_order, order, send};
+    use crate::errors::AppErrors;
+    use crate::models::{OrderStyle, OrderType};
+    use crate::orders::{OrderModification, OrderStatus};
+    use crate::services::TradingApiService;
+    use crate::services_mock::{AiServiceMock, TradingApiCall, TradingApiServiceMock};
+    use axum::body::Body;
+    use axum::http::Request;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn request(method: &str, uri: &str, body: Body) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(body)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_order_after_placing_it() {
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_place_order(Ok(filled_order(OrderType::Buy, 3.0, 236.5))),
+        );
+        trading_service.place_order(order(OrderType::Buy, 3.0)).await.unwrap();
+        let state = app_state(trading_service, Arc::new(AiServiceMock::new()));
+
+        let response = send(state, request("GET", "/orders/7", Body::empty())).await;
+
+        assert_eq!(response["order_id"], 7);
+        assert_eq!(response["status"], "filled");
+        assert_eq!(response["filled_quantity"], 3.0);
+        assert_eq!(response["average_fill_price"], 236.5);
+        assert_eq!(response["fills"][0]["commission"], 1.0)
+    }
+
+    #[tokio::test]
+    async fn test_get_order_not_found() {
+        let state =
+            app_state(Arc::new(TradingApiServiceMock::new()), Arc::new(AiServiceMock::new()));
+        let response = send(state, request("GET", "/orders/42", Body::empty())).await;
+        assert_eq!(response["error_type"], "ORDER_NOT_FOUND")
+    }
+
+    #[tokio::test]
+    async fn test_cancel_order() {
+        let mut cancelled_order = filled_order(OrderType::Buy, 3.0, 236.5);
+        cancelled_order.status = OrderStatus::Cancelled;
+        let trading_service =
+            Arc::new(TradingApiServiceMock::new().with_cancel_order(Ok(cancelled_order)));
+        let state = app_state(trading_service.clone(), Arc::new(AiServiceMock::new()));
+
+        let response = send(state, request("DELETE", "/orders/7", Body::empty())).await;
+
+        assert_eq!(response["status"], "cancelled");
+        assert!(matches!(trading_service.calls().as_slice(), [TradingApiCall::CancelOrder(7)]))
+    }
+
+    #[tokio::test]
+    async fn test_cancel_order_failure() {
+        let trading_service = Arc::new(TradingApiServiceMock::new().with_cancel_order(Err(
+            AppErrors::CancelOrderError("Order 7 is already Filled".to_string()),
+        )));
+        let state = app_state(trading_service, Arc::new(AiServiceMock::new()));
+
+        let response = send(state, request("DELETE", "/orders/7", Body::empty())).await;
+
+        assert_eq!(response["error_type"], "CANCEL_ORDER_FAILED")
+    }
+
+    #[tokio::test]
+    async fn test_modify_order() {
+        let mut modified_order = filled_order(OrderType::Buy, 5.0, 236.5);
+        modified_order.style = OrderStyle::Limit { limit_price: 230.0 };
+        let trading_service =
+            Arc::new(TradingApiServiceMock::new().with_modify_order(Ok(modified_order)));
+        let state = app_state(trading_service.clone(), Arc::new(AiServiceMock::new()));
+
+        let body = json!({ "quantity": 5.0, "limit_price": 230.0 }).to_string();
+        let response = send(state, request("PATCH", "/orders/7", Body::from(body))).await;
+
+        assert_eq!(response["style"]["limit_price"], 230.0);
+        assert!(matches!(
+            trading_service.calls().as_slice(),
+            [TradingApiCall::ModifyOrder(
+                7,
+                OrderModification {
+                    quantity: Some(5.0),
+                    limit_price: Some(230.0),
+                    stop_price: None,
+                },
+            )]
+        ))
+    }
+}
+
+mod health {
+    use super::{app_state, send};
+    use crate::services_mock::{AiServiceMock, TradingApiServiceMock};
+    use axum::body::Body;
+    use axum::http::Request;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_health_reports_the_ib_connection() {
+        let state =
+            app_state(Arc::new(TradingApiServiceMock::new()), Arc::new(AiServiceMock::new()));
+        let response = send(state, Request::get("/health").body(Body::empty()).unwrap()).await;
+        assert_eq!(response["interactive_brokers"]["connected"], false);
+        assert_eq!(response["interactive_brokers"]["connection_url"], "127.0.0.1:4002");
+        assert_eq!(response["interactive_brokers"]["failed_attempts"], 0);
+        assert!(response["market_data_cache"].is_null())
+    }
+}
+
+mod portfolio {
+    use super::{app_state, dollars, investment, send};
+    use crate::errors::AppErrors;
+    use crate::models::{Currency, Portfolio};
+    use crate::services_mock::{AiServiceMock, TradingApiServiceMock};
+    use axum::body::Body;
+    use axum::http::Request;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn get_portfolio() -> Request<Body> {
+        Request::get("/portfolio").body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_portfolio_success() {
+        let trading_service = Arc::new(TradingApiServiceMock::new().with_portfolio(Ok(Portfolio {
+            base_currency: Currency::USD,
+            positions: vec![investment("AAPL", 946.0)],
+            cash: dollars(-250.75),
+            net_liquidation_value: dollars(750.25),
+        })));
+        let state = app_state(trading_service, Arc::new(AiServiceMock::new()));
+        let response = send(state, get_portfolio()).await;
+        assert_eq!(response["base_currency"], "USD");
+        assert_eq!(response["positions"][0]["stock"]["symbol"], "AAPL");
+        assert_eq!(response["positions"][0]["quantity"], 4.0);
+        assert_eq!(response["positions"][0]["average_cost"]["amount"], "236.5");
+        assert_eq!(response["cash"], json!({ "amount": "-250.75", "currency": "USD" }));
+        assert_eq!(response["net_liquidation_value"]["amount"], "750.25")
+    }
+
+    #[tokio::test]
+    async fn test_get_portfolio_failure() {
+        let trading_service = Arc::new(TradingApiServiceMock::new().with_portfolio(Err(
+            AppErrors::GetPortfolioError("No managed account found".to_string()),
+        )));
+        let state = app_state(trading_service, Arc::new(AiServiceMock::new()));
+        let response = send(state, get_portfolio()).await;
+        assert_eq!(response["error_type"], "GET_PORTFOLIO_FAILED")
+    }
+}
+
+mod watch {
+    use super::{app_state, investment, order_advice, post_request, send, stock_data};
+    use crate::models::{AdviceAction, Instrument, StockData};
+    use crate::services_mock::{AiServiceMock, TradingApiServiceMock};
+    use axum::body::Body;
+    use axum::http::Request;
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn get_request(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    fn delete_request(uri: &str) -> Request<Body> {
+        Request::delete(uri).body(Body::empty()).unwrap()
+    }
+
+    /// Queues the responses of `runs` refreshes that end without an order.
+    fn trading_service_for_refreshes(runs: usize) -> TradingApiServiceMock {
+        (0..runs).fold(TradingApiServiceMock::new(), |trading_service, _| {
+            trading_service
+                .with_stock_data(Ok(stock_data()))
+                .with_current_investment(Ok(investment("Apple Inc.", 100.0)))
+        })
+    }
+
+    fn ai_service_for_refreshes(runs: usize) -> AiServiceMock {
+        (0..runs).fold(AiServiceMock::new(), |ai_service, _| {
+            ai_service.with_order_advice(Ok(order_advice(AdviceAction::Hold)))
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_watch_runs_refresh_every_interval() {
+        let state = app_state(
+            Arc::new(trading_service_for_refreshes(2)),
+            Arc::new(ai_service_for_refreshes(2)),
+        );
+
+        let job = send(
+            state.clone(),
+            post_request(
+                "/watch",
+                json!({ "ticker": "AAPL", "every_minutes": 1, "interval": "5min" }),
+            ),
+        )
+        .await;
+        tokio::time::sleep(Duration::from_secs(90)).await;
+        send(state.clone(), delete_request("/watch/AAPL")).await;
+        let trades = send(state, get_request("/trades")).await;
+
+        assert_eq!(
+            job,
+            json!({
+                "instrument": {
+                    "security_type": "stock",
+                    "symbol": "AAPL",
+                    "exchange": "SMART",
+                    "currency": "USD",
+                },
+                "every_minutes": 1,
+                "interval": "5min",
+                "lookback": null,
+            })
+        );
+        let trades = trades.as_array().unwrap();
+        assert_eq!(trades.len(), 2);
+        assert!(trades.iter().all(|trade| trade["endpoint"] == "watch" && trade["error"].is_null()))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_watch_list_and_unwatch() {
+        let state = app_state(
+            Arc::new(trading_service_for_refreshes(1)),
+            Arc::new(ai_service_for_refreshes(1)),
+        );
+
+        send(state.clone(), post_request("/watch", json!({ "ticker": "AAPL" }))).await;
+        let watch_jobs = send(state.clone(), get_request("/watch")).await;
+        let removed_job = send(state.clone(), delete_request("/watch/AAPL")).await;
+        let remaining_jobs = send(state.clone(), get_request("/watch")).await;
+        let missing_job = send(state, delete_request("/watch/AAPL")).await;
+
+        assert_eq!(watch_jobs[0]["instrument"]["symbol"], "AAPL");
+        assert_eq!(watch_jobs[0]["every_minutes"], 5);
+        assert_eq!(removed_job, watch_jobs[0]);
+        assert_eq!(remaining_jobs, json!([]));
+        assert_eq!(missing_job["error_type"], "WATCH_JOB_NOT_FOUND")
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_watch_refreshes_instrument() {
+        let call: Instrument = serde_json::from_value(json!({
+            "security_type": "option",
+            "symbol": "AAPL",
+            "expiry": "20261218",
+            "strike": 200,
+            "right": "call"
+        }))
+        .unwrap();
+        let trading_service = Arc::new(
+            TradingApiServiceMock::new()
+                .with_stock_data(Ok(StockData { stock: call.clone(), ..stock_data() }))
+                .with_current_investment(Ok(investment("Apple Inc.", 100.0))),
+        );
+        let state = app_state(trading_service.clone(), Arc::new(ai_service_for_refreshes(1)));
+
+        send(state.clone(), post_request("/watch", json!({ "instrument": call }))).await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let removed_job =
+            send(state.clone(), delete_request("/watch/AAPL%2020261218%20200%20C")).await;
+        let trades = send(state, get_request("/trades")).await;
+
+        assert_eq!(removed_job["instrument"], json!(call));
+        assert_eq!(trades[0]["ticker"], "AAPL 20261218 200 C");
+        assert!(trading_service.calls().iter().all(|call_made| call_made.stock() == Some(&call)))
+    }
+
+    #[tokio::test]
+    async fn test_watch_invalid_every_minutes() {
+        let state =
+            app_state(Arc::new(TradingApiServiceMock::new()), Arc::new(AiServiceMock::new()));
+
+        let response = send(
+            state.clone(),
+            post_request("/watch", json!({ "ticker": "AAPL", "every_minutes": 0 })),
+        )
+        .await;
+        let watch_jobs = send(state, get_request("/watch")).await;
+
+        assert_eq!(response["error_type"], "INVALID_WATCH_JOB");
+        assert_eq!(watch_jobs, json!([]))
+    }
+}
@@ -0,0 +1,306 @@
+use crate::alpha_vantage_client::OutputSize;
+use crate::errors::AppErrors;
+use crate::models::{
+    Currency, ExchangeRate, MarketDataCacheConfig, News, PriceHistory, StockPricePerformance,
+};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration as StdDuration;
+
+const TIME_SERIES: &str = "time_series";
+const NEWS: &str = "news";
+const EXCHANGE_RATE: &str = "exchange_rate";
+
+/// How often the cache answered without asking Alpha Vantage, served by
+/// `GET /health`. Incremental updates fetched the latest bars and merged them
+/// into a cached series.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub incremental_updates: u64,
+}
+
+struct CacheEntry<T> {
+    fetched_at: DateTime<Utc>,
+    /// Whether a time series holds the full history, not just the compact tail.
+    complete: bool,
+    value: T,
+}
+
+/// SQLite cache in front of the Alpha Vantage time series, news and exchange
+/// rate fetches, so repeated `/analyze` and `/refresh` calls stay within the
+/// daily quota and the data survives restarts. Stale time series are refreshed
+/// with the compact output size and merged into the cached bars.
+pub struct MarketDataCache {
+    connection: Mutex<Connection>,
+    time_series_ttl: StdDuration,
+    news_ttl: StdDuration,
+    exchange_rate_ttl: StdDuration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    incremental_updates: AtomicU64,
+}
+
+impl MarketDataCache {
+    pub fn open(config: &MarketDataCacheConfig) -> Result<Self, AppErrors> {
+        Self::from_connection(Connection::open(&config.path).map_err(cache_error)?, config)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory(config: &MarketDataCacheConfig) -> Result<Self, AppErrors> {
+        Self::from_connection(Connection::open_in_memory().map_err(cache_error)?, config)
+    }
+
+    fn from_connection(
+        connection: Connection,
+        config: &MarketDataCacheConfig,
+    ) -> Result<Self, AppErrors> {
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS market_data (
+                    endpoint TEXT NOT NULL,
+                    key TEXT NOT NULL,
+                    fetched_at TEXT NOT NULL,
+                    complete INTEGER NOT NULL,
+                    value TEXT NOT NULL,
+                    PRIMARY KEY (endpoint, key)
+                )",
+            )
+            .map_err(cache_error)?;
+        Ok(MarketDataCache {
+            connection: Mutex::new(connection),
+            time_series_ttl: StdDuration::from_secs(config.time_series_ttl_seconds),
+            news_ttl: StdDuration::from_secs(config.news_ttl_seconds),
+            exchange_rate_ttl: StdDuration::from_secs(config.exchange_rate_ttl_seconds),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            incremental_updates: AtomicU64::new(0),
+        })
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            incremental_updates: self.incremental_updates.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The bars of the price history, trimmed to its lookback. `fetch` is only
+    /// called when the cached series is missing, stale or too short.
+    pub async fn time_series<F, Fut>(
+        &self,
+        ticker_symbol: &str,
+        price_history: PriceHistory,
+        fetch: F,
+    ) -> Result<Vec<StockPricePerformance>, AppErrors>
+    where
+        F: Fn(OutputSize) -> Fut,
+        Fut: Future<Output = Result<Vec<StockPricePerformance>, AppErrors>>,
+    {
+        let key = format!("{ticker_symbol}:{}", price_history.interval);
+        let output_size = OutputSize::for_lookback(price_history.lookback);
+        // Intraday bars go stale after one bar, whatever the TTL says.
+        let ttl = match price_history.interval.intraday_minutes() {
+            Some(minutes) => self.time_series_ttl.min(StdDuration::from_secs(minutes as u64 * 60)),
+            None => self.time_series_ttl,
+        };
+        let cached = self
+            .load::<Vec<StockPricePerformance>>(TIME_SERIES, &key)
+            .filter(|entry| entry.complete || output_size == OutputSize::Compact);
+
+        let mut bars = match cached {
+            Some(entry) if is_fresh(&entry, ttl) => {
+                self.record(&self.hits, "hit", TIME_SERIES, &key);
+                entry.value
+            }
+            Some(entry) => {
+                let latest = fetch(OutputSize::Compact).await?;
+                match merge_bars(entry.value, latest) {
+                    Some(bars) => {
+                        self.record(
+                            &self.incremental_updates,
+                            "incremental update",
+                            TIME_SERIES,
+                            &key,
+                        );
+                        self.store(TIME_SERIES, &key, entry.complete, &bars);
+                        bars
+                    }
+                    // The cached bars end before the latest ones begin.
+                    None => self.fetch_time_series(&key, output_size, &fetch).await?,
+                }
+            }
+            None => self.fetch_time_series(&key, output_size, &fetch).await?,
+        };
+        if let Some(lookback) = price_history.lookback {
+            bars.drain(..bars.len().saturating_sub(lookback));
+        }
+        Ok(bars)
+    }
+
+    async fn fetch_time_series<F, Fut>(
+        &self,
+        key: &str,
+        output_size: OutputSize,
+        fetch: &F,
+    ) -> Result<Vec<StockPricePerformance>, AppErrors>
+    where
+        F: Fn(OutputSize) -> Fut,
+        Fut: Future<Output = Result<Vec<StockPricePerformance>, AppErrors>>,
+    {
+        self.record(&self.misses, "miss", TIME_SERIES, key);
+        let bars = fetch(output_size).await?;
+        self.store(TIME_SERIES, key, output_size == OutputSize::Full, &bars);
+        Ok(bars)
+    }
+
+    /// The news about the ticker. `fetch` is only called when the cached news
+    /// are missing or stale.
+    pub async fn news<F, Fut>(&self, ticker_symbol: &str, fetch: F) -> Result<Vec<News>, AppErrors>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<News>, AppErrors>>,
+    {
+        if let Some(entry) = self.load::<Vec<News>>(NEWS, ticker_symbol) {
+            if is_fresh(&entry, self.news_ttl) {
+                self.record(&self.hits, "hit", NEWS, ticker_symbol);
+                return Ok(entry.value);
+            }
+        }
+        self.record(&self.misses, "miss", NEWS, ticker_symbol);
+        let news = fetch().await?;
+        self.store(NEWS, ticker_symbol, true, &news);
+        Ok(news)
+    }
+
+    /// The rate from one currency to another. `fetch` is only called when the
+    /// cached rate is missing or stale.
+    pub async fn exchange_rate<F, Fut>(
+        &self,
+        from: Currency,
+        to: Currency,
+        fetch: F,
+    ) -> Result<ExchangeRate, AppErrors>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<ExchangeRate, AppErrors>>,
+    {
+        let key = format!("{from}:{to}");
+        if let Some(entry) = self.load::<ExchangeRate>(EXCHANGE_RATE, &key) {
+            if is_fresh(&entry, self.exchange_rate_ttl) {
+                self.record(&self.hits, "hit", EXCHANGE_RATE, &key);
+                return Ok(entry.value);
+            }
+        }
+        self.record(&self.misses, "miss", EXCHANGE_RATE, &key);
+        let exchange_rate = fetch().await?;
+        self.store(EXCHANGE_RATE, &key, true, &exchange_rate);
+        Ok(exchange_rate)
+    }
+
+    fn record(&self, counter: &AtomicU64, outcome: &str, endpoint: &str, key: &str) {
+        counter.fetch_add(1, Ordering::Relaxed);
+        eprintln!("Market data cache {outcome} for {endpoint} {key}");
+    }
+
+    /// A broken or unreadable entry counts as missing, the cache never fails a
+    /// fetch.
+    fn load<T: DeserializeOwned>(&self, endpoint: &str, key: &str) -> Option<CacheEntry<T>> {
+        self.read(endpoint, key)
+            .map_err(|error| {
+                eprintln!("Failed to read the market data cache for {endpoint} {key}: {error}")
+            })
+            .ok()
+            .flatten()
+    }
+
+    fn read<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        key: &str,
+    ) -> Result<Option<CacheEntry<T>>, String> {
+        let row: Option<(String, bool, String)> = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT fetched_at, complete, value FROM market_data WHERE endpoint = ?1 AND key = ?2",
+                params![endpoint, key],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .map_err(|error| error.to_string())?;
+        let Some((fetched_at, complete, value)) = row else {
+            return Ok(None);
+        };
+        Ok(Some(CacheEntry {
+            fetched_at: DateTime::parse_from_rfc3339(&fetched_at)
+                .map_err(|error| error.to_string())?
+                .with_timezone(&Utc),
+            complete,
+            value: serde_json::from_str(&value).map_err(|error| error.to_string())?,
+        }))
+    }
+
+    fn store<T: Serialize>(&self, endpoint: &str, key: &str, complete: bool, value: &T) {
+        if let Err(error) = self.write(endpoint, key, complete, value) {
+            eprintln!("Failed to write the market data cache for {endpoint} {key}: {error}");
+        }
+    }
+
+    fn write<T: Serialize>(
+        &self,
+        endpoint: &str,
+        key: &str,
+        complete: bool,
+        value: &T,
+    ) -> Result<(), String> {
+        let value = serde_json::to_string(value).map_err(|error| error.to_string())?;
+        self.connection
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO market_data (endpoint, key, fetched_at, complete, value)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![endpoint, key, Utc::now().to_rfc3339(), complete, value],
+            )
+            .map_err(|error| error.to_string())?;
+        Ok(())
+    }
+}
+
+fn is_fresh<T>(entry: &CacheEntry<T>, ttl: StdDuration) -> bool {
+    (Utc::now() - entry.fetched_at).to_std().is_ok_and(|age| age < ttl)
+}
+
+/// Adds the latest bars to the cached ones, replacing cached bars of the same
+/// date since the newest bar may still have been forming. `None` when the
+/// latest bars start after the cached ones end, which would leave a gap.
+pub fn merge_bars(
+    cached: Vec<StockPricePerformance>,
+    latest: Vec<StockPricePerformance>,
+) -> Option<Vec<StockPricePerformance>> {
+    let (Some(cached_end), Some(latest_start)) =
+        (cached.last().map(|bar| bar.date), latest.first().map(|bar| bar.date))
+    else {
+        return Some(if latest.is_empty() { cached } else { latest });
+    };
+    if latest_start > cached_end {
+        return None;
+    }
+    let mut bars: Vec<StockPricePerformance> =
+        cached.into_iter().filter(|bar| bar.date < latest_start).collect();
+    bars.extend(latest);
+    Some(bars)
+}
+
+fn cache_error(error: rusqlite::Error) -> AppErrors {
+    AppErrors::CacheError(error.to_string())
+}
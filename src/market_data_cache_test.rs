@@ -0,0 +1,277 @@
+use crate::alpha_vantage_client::OutputSize;
+use crate::errors::AppErrors;
+use crate::models::{MarketDataCacheConfig, StockPricePerformance};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::sync::Mutex;
+
+fn cache_config(time_series_ttl_seconds: u64, news_ttl_seconds: u64) -> MarketDataCacheConfig {
+    MarketDataCacheConfig {
+        path: std::env::temp_dir()
+            .join(format!("market_data_cache_{}.sqlite", std::process::id()))
+            .to_string_lossy()
+            .to_string(),
+        time_series_ttl_seconds,
+        news_ttl_seconds,
+        exchange_rate_ttl_seconds: time_series_ttl_seconds,
+    }
+}
+
+/// Daily bars in January that close at the given prices.
+fn bars(days: std::ops::RangeInclusive<u32>, close: i64) -> Vec<StockPricePerformance> {
+    days.map(|day| StockPricePerformance {
+        date: NaiveDate::from_ymd_opt(2025, 1, day).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+        open: Decimal::from(close),
+        high: Decimal::from(close),
+        low: Decimal::from(close),
+        close: Decimal::from(close),
+        volume: 100,
+    })
+    .collect()
+}
+
+/// Hands out queued time series and records the output sizes it was asked for.
+#[derive(Default)]
+struct TimeSeriesFetches {
+    responses: Mutex<Vec<Result<Vec<StockPricePerformance>, AppErrors>>>,
+    output_sizes: Mutex<Vec<OutputSize>>,
+}
+
+impl TimeSeriesFetches {
+    fn new(responses: Vec<Result<Vec<StockPricePerformance>, AppErrors>>) -> Self {
+        TimeSeriesFetches { responses: Mutex::new(responses), output_sizes: Mutex::default() }
+    }
+
+    async fn fetch(
+        &self,
+        output_size: OutputSize,
+    ) -> Result<Vec<StockPricePerformance>, AppErrors> {
+        self.output_sizes.lock().unwrap().push(output_size);
+        self.responses.lock().unwrap().remove(0)
+    }
+
+    fn output_sizes(&self) -> Vec<OutputSize> {
+        self.output_sizes.lock().unwrap().clone()
+    }
+}
+
+mod time_series {
+    use super::{bars, cache_config, TimeSeriesFetches};
+    use crate::alpha_vantage_client::OutputSize;
+    use crate::errors::AppErrors;
+    use crate::market_data_cache::{CacheStats, MarketDataCache};
+    use crate::models::{PriceHistory, PriceInterval};
+    use rust_decimal::Decimal;
+
+    const DAILY: PriceHistory = PriceHistory { interval: PriceInterval::Daily, lookback: Some(3) };
+
+    #[tokio::test]
+    async fn test_time_series_served_from_the_cache_until_stale() {
+        let cache = MarketDataCache::open_in_memory(&cache_config(3_600, 0)).unwrap();
+        let fetches = TimeSeriesFetches::new(vec![Ok(bars(1..=5, 10))]);
+
+        let first = cache.time_series("AAPL", DAILY, |size| fetches.fetch(size)).await.unwrap();
+        let second = cache.time_series("AAPL", DAILY, |size| fetches.fetch(size)).await.unwrap();
+
+        assert_eq!(first, bars(3..=5, 10));
+        assert_eq!(second, first);
+        assert_eq!(fetches.output_sizes(), vec![OutputSize::Compact]);
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1, incremental_updates: 0 })
+    }
+
+    #[tokio::test]
+    async fn test_time_series_stale_cache_merges_the_latest_bars() {
+        let cache = MarketDataCache::open_in_memory(&cache_config(0, 0)).unwrap();
+        let history = PriceHistory { interval: PriceInterval::Daily, lookback: Some(200) };
+        let fetches = TimeSeriesFetches::new(vec![
+            Ok(bars(1..=5, 10)),
+            Ok([bars(5..=5, 20), bars(6..=7, 30)].concat()),
+        ]);
+
+        cache.time_series("AAPL", history, |size| fetches.fetch(size)).await.unwrap();
+        let merged = cache.time_series("AAPL", history, |size| fetches.fetch(size)).await.unwrap();
+
+        let closes: Vec<Decimal> = merged.iter().map(|bar| bar.close).collect();
+        assert_eq!(closes, [10, 10, 10, 10, 20, 30, 30].map(Decimal::from));
+        assert_eq!(fetches.output_sizes(), vec![OutputSize::Full, OutputSize::Compact]);
+        assert_eq!(cache.stats().incremental_updates, 1)
+    }
+
+    #[tokio::test]
+    async fn test_time_series_refetches_when_the_latest_bars_leave_a_gap() {
+        let cache = MarketDataCache::open_in_memory(&cache_config(0, 0)).unwrap();
+        let fetches = TimeSeriesFetches::new(vec![
+            Ok(bars(1..=3, 10)),
+            Ok(bars(10..=12, 20)),
+            Ok(bars(1..=12, 20)),
+        ]);
+
+        cache.time_series("AAPL", DAILY, |size| fetches.fetch(size)).await.unwrap();
+        let refetched = cache.time_series("AAPL", DAILY, |size| fetches.fetch(size)).await.unwrap();
+
+        assert_eq!(refetched, bars(10..=12, 20));
+        assert_eq!(fetches.output_sizes().len(), 3);
+        assert_eq!(cache.stats().misses, 2)
+    }
+
+    #[tokio::test]
+    async fn test_time_series_compact_cache_doesnt_cover_a_long_lookback() {
+        let cache = MarketDataCache::open_in_memory(&cache_config(3_600, 0)).unwrap();
+        let long = PriceHistory { interval: PriceInterval::Daily, lookback: Some(500) };
+        let fetches = TimeSeriesFetches::new(vec![Ok(bars(1..=5, 10)), Ok(bars(1..=9, 10))]);
+
+        cache.time_series("AAPL", DAILY, |size| fetches.fetch(size)).await.unwrap();
+        let full = cache.time_series("AAPL", long, |size| fetches.fetch(size)).await.unwrap();
+
+        assert_eq!(full.len(), 9);
+        assert_eq!(fetches.output_sizes(), vec![OutputSize::Compact, OutputSize::Full])
+    }
+
+    #[tokio::test]
+    async fn test_time_series_keys_by_ticker_and_interval() {
+        let cache = MarketDataCache::open_in_memory(&cache_config(3_600, 0)).unwrap();
+        let weekly = PriceHistory { interval: PriceInterval::Weekly, ..DAILY };
+        let fetches = TimeSeriesFetches::new(vec![
+            Ok(bars(1..=3, 10)),
+            Ok(bars(1..=3, 20)),
+            Ok(bars(1..=3, 30)),
+        ]);
+
+        cache.time_series("AAPL", DAILY, |size| fetches.fetch(size)).await.unwrap();
+        cache.time_series("MSFT", DAILY, |size| fetches.fetch(size)).await.unwrap();
+        cache.time_series("AAPL", weekly, |size| fetches.fetch(size)).await.unwrap();
+
+        assert_eq!(cache.stats().misses, 3)
+    }
+
+    #[tokio::test]
+    async fn test_time_series_failed_fetches_are_not_cached() {
+        let cache = MarketDataCache::open_in_memory(&cache_config(3_600, 0)).unwrap();
+        let fetches = TimeSeriesFetches::new(vec![
+            Err(AppErrors::GetStockDataError("Rate limited".to_string())),
+            Ok(bars(1..=3, 10)),
+        ]);
+
+        let maybe_bars = cache.time_series("AAPL", DAILY, |size| fetches.fetch(size)).await;
+        let bars = cache.time_series("AAPL", DAILY, |size| fetches.fetch(size)).await.unwrap();
+
+        assert!(maybe_bars.is_err());
+        assert_eq!(bars.len(), 3)
+    }
+
+    #[tokio::test]
+    async fn test_time_series_survives_a_restart() {
+        let config = cache_config(3_600, 0);
+        let _ = std::fs::remove_file(&config.path);
+        let fetches = TimeSeriesFetches::new(vec![Ok(bars(1..=3, 10))]);
+        {
+            let cache = MarketDataCache::open(&config).unwrap();
+            cache.time_series("AAPL", DAILY, |size| fetches.fetch(size)).await.unwrap();
+        }
+
+        let reopened = MarketDataCache::open(&config).unwrap();
+        let cached = reopened.time_series("AAPL", DAILY, |size| fetches.fetch(size)).await.unwrap();
+        std::fs::remove_file(&config.path).unwrap();
+
+        assert_eq!(cached, bars(1..=3, 10));
+        assert_eq!(reopened.stats().hits, 1)
+    }
+}
+
+mod news {
+    use super::cache_config;
+    use crate::market_data_cache::{CacheStats, MarketDataCache};
+    use crate::models::News;
+    use chrono::NaiveDateTime;
+
+    fn news(title: &str) -> Vec<News> {
+        vec![News {
+            title: title.to_string(),
+            summary: String::new(),
+            time_published: NaiveDateTime::default(),
+        }]
+    }
+
+    #[tokio::test]
+    async fn test_news_served_from_the_cache_until_stale() {
+        let fresh = MarketDataCache::open_in_memory(&cache_config(0, 3_600)).unwrap();
+        fresh.news("AAPL", || async { Ok(news("First")) }).await.unwrap();
+        let cached = fresh.news("AAPL", || async { Ok(news("Second")) }).await.unwrap();
+
+        let stale = MarketDataCache::open_in_memory(&cache_config(0, 0)).unwrap();
+        stale.news("AAPL", || async { Ok(news("First")) }).await.unwrap();
+        let refetched = stale.news("AAPL", || async { Ok(news("Second")) }).await.unwrap();
+
+        assert_eq!(cached, news("First"));
+        assert_eq!(fresh.stats(), CacheStats { hits: 1, misses: 1, incremental_updates: 0 });
+        assert_eq!(refetched, news("Second"))
+    }
+}
+
+mod exchange_rate {
+    use super::cache_config;
+    use crate::errors::AppErrors;
+    use crate::market_data_cache::MarketDataCache;
+    use crate::models::{Currency, ExchangeRate};
+    use rust_decimal::Decimal;
+
+    fn euro_rate(rate: i64) -> ExchangeRate {
+        ExchangeRate {
+            from: "EUR".parse().unwrap(),
+            to: Currency::USD,
+            rate: Decimal::new(rate, 2),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exchange_rate_served_from_the_cache_until_stale() {
+        let (euros, dollars) = ("EUR".parse().unwrap(), Currency::USD);
+        let fresh = MarketDataCache::open_in_memory(&cache_config(3_600, 0)).unwrap();
+        fresh.exchange_rate(euros, dollars, || async { Ok(euro_rate(108)) }).await.unwrap();
+        let cached =
+            fresh.exchange_rate(euros, dollars, || async { Ok(euro_rate(110)) }).await.unwrap();
+
+        let stale = MarketDataCache::open_in_memory(&cache_config(0, 0)).unwrap();
+        stale.exchange_rate(euros, dollars, || async { Ok(euro_rate(108)) }).await.unwrap();
+        let refetched =
+            stale.exchange_rate(euros, dollars, || async { Ok(euro_rate(110)) }).await.unwrap();
+
+        assert_eq!(cached, euro_rate(108));
+        assert_eq!(fresh.stats().hits, 1);
+        assert_eq!(refetched, euro_rate(110))
+    }
+
+    #[tokio::test]
+    async fn test_exchange_rate_keys_by_direction() {
+        let cache = MarketDataCache::open_in_memory(&cache_config(3_600, 0)).unwrap();
+        let euros: Currency = "EUR".parse().unwrap();
+        cache.exchange_rate(euros, Currency::USD, || async { Ok(euro_rate(108)) }).await.unwrap();
+        let maybe_rate = cache
+            .exchange_rate(Currency::USD, euros, || async {
+                Err(AppErrors::GetExchangeRateError("Not cached".to_string()))
+            })
+            .await;
+        assert!(maybe_rate.is_err())
+    }
+}
+
+mod merge {
+    use super::bars;
+    use crate::market_data_cache::merge_bars;
+
+    #[test]
+    fn test_merge_bars_replaces_overlapping_bars() {
+        let merged = merge_bars(bars(1..=4, 10), bars(3..=6, 20)).unwrap();
+        assert_eq!(merged, [bars(1..=2, 10), bars(3..=6, 20)].concat())
+    }
+
+    #[test]
+    fn test_merge_bars_with_a_gap() {
+        assert_eq!(merge_bars(bars(1..=4, 10), bars(6..=7, 20)), None)
+    }
+
+    #[test]
+    fn test_merge_bars_without_latest_bars() {
+        assert_eq!(merge_bars(bars(1..=4, 10), vec![]), Some(bars(1..=4, 10)))
+    }
+}
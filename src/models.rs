@@ -1,90 +1,1141 @@
 use crate::errors::AppErrors;
-use serde::Deserialize;
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::{Decimal, RoundingStrategy};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
 use std::string::ToString;
-use std::time::SystemTime;
 
-#[derive(Debug)]
+/// An ISO 4217 currency code like `USD`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    pub const USD: Currency = Currency(*b"USD");
+
+    pub fn code(&self) -> &str {
+        std::str::from_utf8(&self.0).unwrap_or_default()
+    }
+
+    /// The digits of the currency's minor unit, 2 for the cents of `USD`.
+    pub fn minor_units(&self) -> u32 {
+        match self.code() {
+            "CLP" | "ISK" | "JPY" | "KRW" | "PYG" | "UGX" | "VND" => 0,
+            "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+            _ => 2,
+        }
+    }
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Currency::USD
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = AppErrors;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let code = code.trim().to_uppercase();
+        match <[u8; 3]>::try_from(code.as_bytes()) {
+            Ok(bytes) if bytes.iter().all(u8::is_ascii_uppercase) => Ok(Currency(bytes)),
+            _ => Err(AppErrors::ModelCreationError(format!(
+                "A currency is a three letter ISO code. Code provided: {code}"
+            ))),
+        }
+    }
+}
+
+impl TryFrom<String> for Currency {
+    type Error = AppErrors;
+
+    fn try_from(code: String) -> Result<Self, Self::Error> {
+        code.parse()
+    }
+}
+
+impl From<Currency> for String {
+    fn from(currency: Currency) -> Self {
+        currency.code().to_string()
+    }
+}
+
+/// An exact amount of a currency. Arithmetic is checked and only combines
+/// amounts of the same currency.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Money {
-    pub amount: f64,
+    pub amount: Decimal,
+    pub currency: Currency,
 }
 
 impl Money {
-    pub fn new(amount: f64) -> Result<Self, AppErrors> {
-        let amount_str = amount.to_string();
-        let digits_count = if let Some(pos) = amount_str.find('.') {
-            amount_str[pos + 1..].trim_end_matches('0').len()
-        } else {
-            0
-        };
-        if amount < 0.0 {
+    /// An amount to invest, at least 0 and in whole minor units.
+    pub fn new(amount: Decimal, currency: Currency) -> Result<Self, AppErrors> {
+        if amount.is_sign_negative() && !amount.is_zero() {
             Err(AppErrors::ModelCreationError(format!(
                 "The money amount can't be below 0. Amount provided: {amount}"
             )))
-        } else if digits_count > 2 {
+        } else if amount.normalize().scale() > currency.minor_units() {
             Err(AppErrors::ModelCreationError(format!(
-                "There were too many digits. Amount provided: {amount}"
+                "There were too many digits for {currency}. Amount provided: {amount}"
             )))
         } else {
-            Ok(Money { amount })
+            Ok(Money { amount, currency })
         }
     }
+
+    /// Takes any amount as is, for balances, profits or losses and prices.
+    pub fn from_decimal(amount: Decimal, currency: Currency) -> Self {
+        Money { amount, currency }
+    }
+
+    /// Converts the floats Interactive Brokers reports, like average costs and
+    /// account values, to the shortest decimal that reads back as the float.
+    pub fn from_f64(amount: f64, currency: Currency) -> Result<Self, AppErrors> {
+        Decimal::from_f64(amount).map(|amount| Money { amount, currency }).ok_or_else(|| {
+            AppErrors::ModelCreationError(format!(
+                "The amount {amount} can't be converted to {currency}"
+            ))
+        })
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Money { amount: Decimal::ZERO, currency }
+    }
+
+    /// Rounds half away from zero to the currency's minor unit.
+    pub fn round(self) -> Self {
+        Money {
+            amount: self.amount.round_dp_with_strategy(
+                self.currency.minor_units(),
+                RoundingStrategy::MidpointAwayFromZero,
+            ),
+            ..self
+        }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.amount.to_f64().unwrap_or_default()
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Self, AppErrors> {
+        self.same_currency(&other, "add")?;
+        self.checked(self.amount.checked_add(other.amount), "add", other.amount)
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Self, AppErrors> {
+        self.same_currency(&other, "subtract")?;
+        self.checked(self.amount.checked_sub(other.amount), "subtract", other.amount)
+    }
+
+    pub fn checked_mul(self, factor: Decimal) -> Result<Self, AppErrors> {
+        self.checked(self.amount.checked_mul(factor), "multiply by", factor)
+    }
+
+    pub fn checked_div(self, divisor: Decimal) -> Result<Self, AppErrors> {
+        self.checked(self.amount.checked_div(divisor), "divide by", divisor)
+    }
+
+    fn same_currency(&self, other: &Money, operation: &str) -> Result<(), AppErrors> {
+        if self.currency == other.currency {
+            return Ok(());
+        }
+        Err(AppErrors::MoneyArithmeticError(format!(
+            "Can't {operation} {self} and {other}, the currencies differ"
+        )))
+    }
+
+    fn checked(
+        self,
+        amount: Option<Decimal>,
+        operation: &str,
+        operand: Decimal,
+    ) -> Result<Self, AppErrors> {
+        amount.map(|amount| Money { amount, ..self }).ok_or_else(|| {
+            AppErrors::MoneyArithmeticError(format!(
+                "{self} overflows when trying to {operation} {operand}"
+            ))
+        })
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct Stock {
-    pub ticker_symbol: String,
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount, self.currency)
+    }
+}
+
+/// Something the bot trades, with everything Interactive Brokers needs to tell
+/// it apart from other contracts. A plain ticker is a stock; the other kinds
+/// are given in full, as `{ "security_type": "option", "symbol": "AAPL", ... }`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(tag = "security_type", rename_all = "snake_case", try_from = "InstrumentFields")]
+pub enum Instrument {
+    Stock {
+        symbol: String,
+        exchange: String,
+        currency: Currency,
+    },
+    Etf {
+        symbol: String,
+        exchange: String,
+        currency: Currency,
+    },
+    Option {
+        symbol: String,
+        exchange: String,
+        currency: Currency,
+        /// The last trading day, as `YYYYMMDD`.
+        expiry: String,
+        strike: Decimal,
+        right: OptionRight,
+        multiplier: Decimal,
+    },
+    Future {
+        symbol: String,
+        exchange: String,
+        currency: Currency,
+        /// The contract month as `YYYYMM` or the last trading day as `YYYYMMDD`.
+        expiry: String,
+        multiplier: Decimal,
+    },
+    Crypto {
+        symbol: String,
+        exchange: String,
+        currency: Currency,
+    },
+    /// The pair `symbol`/`currency`, priced in `currency`.
+    Forex {
+        symbol: Currency,
+        exchange: String,
+        currency: Currency,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum OptionRight {
+    Call,
+    Put,
+}
+
+impl OptionRight {
+    /// The right as Interactive Brokers abbreviates it.
+    pub fn code(&self) -> &'static str {
+        match self {
+            OptionRight::Call => "C",
+            OptionRight::Put => "P",
+        }
+    }
+}
+
+/// An instrument as requests give it. Only the symbol and the contract terms
+/// are required, the exchange and the currency default per security type.
+#[derive(Deserialize)]
+#[serde(tag = "security_type", rename_all = "snake_case")]
+enum InstrumentFields {
+    Stock {
+        symbol: String,
+        exchange: Option<String>,
+        currency: Option<Currency>,
+    },
+    Etf {
+        symbol: String,
+        exchange: Option<String>,
+        currency: Option<Currency>,
+    },
+    Option {
+        symbol: String,
+        exchange: Option<String>,
+        currency: Option<Currency>,
+        expiry: String,
+        strike: Decimal,
+        right: OptionRight,
+        multiplier: Option<Decimal>,
+    },
+    Future {
+        symbol: String,
+        exchange: String,
+        currency: Option<Currency>,
+        expiry: String,
+        multiplier: Decimal,
+    },
+    Crypto {
+        symbol: String,
+        exchange: Option<String>,
+        currency: Option<Currency>,
+    },
+    Forex {
+        symbol: Currency,
+        exchange: Option<String>,
+        currency: Currency,
+    },
+}
+
+/// The exchange suffixes of Yahoo style tickers like `SAP.DE` and Alpha Vantage
+/// tickers like `SAP.DEX`, with the currency the exchange quotes in. London is
+/// left out since it quotes in pence.
+const EXCHANGE_SUFFIXES: &[(&str, Currency)] = &[
+    ("DE", Currency(*b"EUR")),
+    ("DEX", Currency(*b"EUR")),
+    ("F", Currency(*b"EUR")),
+    ("FRK", Currency(*b"EUR")),
+    ("PA", Currency(*b"EUR")),
+    ("AS", Currency(*b"EUR")),
+    ("MI", Currency(*b"EUR")),
+    ("MC", Currency(*b"EUR")),
+    ("SW", Currency(*b"CHF")),
+    ("T", Currency(*b"JPY")),
+    ("HK", Currency(*b"HKD")),
+    ("TO", Currency(*b"CAD")),
+    ("TRT", Currency(*b"CAD")),
+    ("AX", Currency(*b"AUD")),
+];
+
+/// The exchange an instrument is routed to when the request names none.
+const SMART: &str = "SMART";
+
+fn listing(ticker: &str) -> Option<(&str, Currency)> {
+    let (symbol, suffix) = ticker.rsplit_once('.')?;
+    EXCHANGE_SUFFIXES
+        .iter()
+        .find(|(exchange, _)| exchange.eq_ignore_ascii_case(suffix))
+        .map(|(_, currency)| (symbol, *currency))
+}
+
+/// The currency of the ticker's exchange suffix. Tickers without a known
+/// suffix are US listings, quoted in USD.
+fn ticker_currency(ticker: &str) -> Currency {
+    listing(ticker).map_or(Currency::USD, |(_, currency)| currency)
+}
+
+impl Instrument {
+    /// The SMART routed stock behind a ticker like `AAPL` or `SAP.DE`, in the
+    /// currency of its exchange suffix.
+    pub fn stock(ticker: &str) -> Self {
+        Instrument::Stock {
+            symbol: ticker.to_string(),
+            exchange: SMART.to_string(),
+            currency: ticker_currency(ticker),
+        }
+    }
+
+    /// The symbol as the request gave it, the ticker Alpha Vantage knows a
+    /// stock or ETF by, exchange suffix included.
+    pub fn ticker(&self) -> &str {
+        match self {
+            Instrument::Stock { symbol, .. }
+            | Instrument::Etf { symbol, .. }
+            | Instrument::Option { symbol, .. }
+            | Instrument::Future { symbol, .. }
+            | Instrument::Crypto { symbol, .. } => symbol,
+            Instrument::Forex { symbol, .. } => symbol.code(),
+        }
+    }
+
+    /// The symbol Interactive Brokers knows the instrument or its underlying
+    /// by, without a stock's exchange suffix.
+    pub fn symbol(&self) -> &str {
+        match self {
+            Instrument::Stock { symbol, .. } | Instrument::Etf { symbol, .. } => {
+                listing(symbol).map_or(symbol, |(symbol, _)| symbol)
+            }
+            _ => self.ticker(),
+        }
+    }
+
+    pub fn exchange(&self) -> &str {
+        match self {
+            Instrument::Stock { exchange, .. }
+            | Instrument::Etf { exchange, .. }
+            | Instrument::Option { exchange, .. }
+            | Instrument::Future { exchange, .. }
+            | Instrument::Crypto { exchange, .. }
+            | Instrument::Forex { exchange, .. } => exchange,
+        }
+    }
+
+    /// The currency the instrument is priced and traded in.
+    pub fn currency(&self) -> Currency {
+        match self {
+            Instrument::Stock { currency, .. }
+            | Instrument::Etf { currency, .. }
+            | Instrument::Option { currency, .. }
+            | Instrument::Future { currency, .. }
+            | Instrument::Crypto { currency, .. }
+            | Instrument::Forex { currency, .. } => *currency,
+        }
+    }
+
+    /// How many units of the underlying one contract stands for, 1 for
+    /// everything but derivatives.
+    pub fn multiplier(&self) -> Decimal {
+        match self {
+            Instrument::Option { multiplier, .. } | Instrument::Future { multiplier, .. } => {
+                *multiplier
+            }
+            _ => Decimal::ONE,
+        }
+    }
+
+    /// Whether Alpha Vantage has the instrument's price history. The other
+    /// instruments' bars come from Interactive Brokers.
+    pub fn is_equity(&self) -> bool {
+        matches!(self, Instrument::Stock { .. } | Instrument::Etf { .. })
+    }
+
+    /// The Alpha Vantage news ticker of the instrument or its underlying.
+    /// Futures have none.
+    pub fn news_ticker(&self) -> Option<String> {
+        match self {
+            Instrument::Stock { symbol, .. }
+            | Instrument::Etf { symbol, .. }
+            | Instrument::Option { symbol, .. } => Some(symbol.clone()),
+            Instrument::Crypto { symbol, .. } => Some(format!("CRYPTO:{symbol}")),
+            Instrument::Forex { symbol, .. } => Some(format!("FOREX:{symbol}")),
+            Instrument::Future { .. } => None,
+        }
+    }
+}
+
+/// Stocks and ETFs show as their ticker, derivatives with their terms like
+/// `AAPL 20261218 200 C`, crypto and forex as the pair like `EUR.USD`.
+impl fmt::Display for Instrument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instrument::Stock { symbol, .. } | Instrument::Etf { symbol, .. } => {
+                f.write_str(symbol)
+            }
+            Instrument::Option { symbol, expiry, strike, right, .. } => {
+                write!(f, "{symbol} {expiry} {} {}", strike.normalize(), right.code())
+            }
+            Instrument::Future { symbol, expiry, .. } => write!(f, "{symbol} {expiry}"),
+            Instrument::Crypto { symbol, currency, .. } => write!(f, "{symbol}.{currency}"),
+            Instrument::Forex { symbol, currency, .. } => write!(f, "{symbol}.{currency}"),
+        }
+    }
+}
+
+impl TryFrom<InstrumentFields> for Instrument {
+    type Error = AppErrors;
+
+    fn try_from(fields: InstrumentFields) -> Result<Self, Self::Error> {
+        let or_default = |exchange: Option<String>, default: &str| {
+            exchange.unwrap_or_else(|| default.to_string())
+        };
+        let instrument = match fields {
+            InstrumentFields::Stock { symbol, exchange, currency } => Instrument::Stock {
+                currency: currency.unwrap_or_else(|| ticker_currency(&symbol)),
+                exchange: or_default(exchange, SMART),
+                symbol,
+            },
+            InstrumentFields::Etf { symbol, exchange, currency } => Instrument::Etf {
+                currency: currency.unwrap_or_else(|| ticker_currency(&symbol)),
+                exchange: or_default(exchange, SMART),
+                symbol,
+            },
+            InstrumentFields::Option {
+                symbol,
+                exchange,
+                currency,
+                expiry,
+                strike,
+                right,
+                multiplier,
+            } => Instrument::Option {
+                symbol,
+                exchange: or_default(exchange, SMART),
+                currency: currency.unwrap_or_default(),
+                expiry: contract_expiry(expiry, &[8])?,
+                strike: positive("strike", strike)?,
+                right,
+                multiplier: positive("multiplier", multiplier.unwrap_or(Decimal::ONE_HUNDRED))?,
+            },
+            InstrumentFields::Future { symbol, exchange, currency, expiry, multiplier } => {
+                Instrument::Future {
+                    symbol,
+                    exchange,
+                    currency: currency.unwrap_or_default(),
+                    expiry: contract_expiry(expiry, &[6, 8])?,
+                    multiplier: positive("multiplier", multiplier)?,
+                }
+            }
+            InstrumentFields::Crypto { symbol, exchange, currency } => Instrument::Crypto {
+                symbol,
+                exchange: or_default(exchange, "PAXOS"),
+                currency: currency.unwrap_or_default(),
+            },
+            InstrumentFields::Forex { symbol, exchange, currency } => {
+                if symbol == currency {
+                    return Err(AppErrors::ModelCreationError(format!(
+                        "A forex pair needs two currencies. Pair provided: {symbol}.{currency}"
+                    )));
+                }
+                Instrument::Forex { symbol, exchange: or_default(exchange, "IDEALPRO"), currency }
+            }
+        };
+        if instrument.ticker().trim().is_empty() || instrument.exchange().trim().is_empty() {
+            return Err(AppErrors::ModelCreationError(format!(
+                "An instrument needs a symbol and an exchange. Instrument provided: {instrument:?}"
+            )));
+        }
+        Ok(instrument)
+    }
+}
+
+fn contract_expiry(expiry: String, lengths: &[usize]) -> Result<String, AppErrors> {
+    if lengths.contains(&expiry.len()) && expiry.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Ok(expiry);
+    }
+    Err(AppErrors::ModelCreationError(format!(
+        "The expiry is a date as YYYYMMDD{}. Expiry provided: {expiry}",
+        if lengths.contains(&6) { " or a month as YYYYMM" } else { "" }
+    )))
+}
+
+fn positive(name: &str, value: Decimal) -> Result<Decimal, AppErrors> {
+    if value > Decimal::ZERO {
+        return Ok(value);
+    }
+    Err(AppErrors::ModelCreationError(format!(
+        "The {name} has to be above 0. Value provided: {value}"
+    )))
+}
+
+/// The instrument a request trades, given as the `ticker` of a stock or as the
+/// full `instrument`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "TickerOrInstrument", into = "TickerOrInstrument")]
+pub struct RequestedInstrument(pub Instrument);
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct TickerOrInstrument {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ticker: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    instrument: Option<Instrument>,
+}
+
+impl TryFrom<TickerOrInstrument> for RequestedInstrument {
+    type Error = String;
+
+    fn try_from(fields: TickerOrInstrument) -> Result<Self, Self::Error> {
+        match (fields.ticker, fields.instrument) {
+            (Some(ticker), None) => Ok(RequestedInstrument(Instrument::stock(&ticker))),
+            (None, Some(instrument)) => Ok(RequestedInstrument(instrument)),
+            (Some(_), Some(_)) => {
+                Err("Give either a ticker or an instrument, not both".to_string())
+            }
+            (None, None) => Err("missing field `ticker` or `instrument`".to_string()),
+        }
+    }
+}
+
+impl From<RequestedInstrument> for TickerOrInstrument {
+    fn from(requested: RequestedInstrument) -> Self {
+        TickerOrInstrument { ticker: None, instrument: Some(requested.0) }
+    }
+}
+
+/// How much one unit of `from` buys of `to`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ExchangeRate {
+    pub from: Currency,
+    pub to: Currency,
+    pub rate: Decimal,
+}
+
+impl ExchangeRate {
+    pub fn identity(currency: Currency) -> Self {
+        ExchangeRate { from: currency, to: currency, rate: Decimal::ONE }
+    }
+
+    /// The money in `to`, rounded to its minor unit.
+    pub fn convert(&self, money: Money) -> Result<Money, AppErrors> {
+        if money.currency != self.from {
+            return Err(AppErrors::MoneyArithmeticError(format!(
+                "Can't convert {money} at the {} to {} rate",
+                self.from, self.to
+            )));
+        }
+        Ok(Money { currency: self.to, ..money.checked_mul(self.rate)? }.round())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Order {
     pub stock_quantity: f64,
-    pub stock: Stock,
+    pub stock: Instrument,
     pub order_type: OrderType,
-    pub timestamp: SystemTime,
+    pub style: OrderStyle,
+    pub time_in_force: TimeInForce,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum OrderType {
     Buy,
     Sell,
 }
 
+/// How the order is priced. Prices are per share and bracket orders are the
+/// limit entry plus a take profit limit and a stop loss on the opposite side.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(tag = "style", rename_all = "snake_case")]
+pub enum OrderStyle {
+    #[default]
+    Market,
+    Limit {
+        limit_price: f64,
+    },
+    Stop {
+        stop_price: f64,
+    },
+    StopLimit {
+        stop_price: f64,
+        limit_price: f64,
+    },
+    Bracket {
+        limit_price: f64,
+        take_profit_price: f64,
+        stop_loss_price: f64,
+    },
+}
+
+/// The order style picked in a request, before the prices are known.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStyleKind {
+    #[default]
+    Market,
+    Limit,
+    Stop,
+    StopLimit,
+    Bracket,
+}
+
+/// Price levels for an order. Requests and order advices can both set them.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Default)]
+pub struct OrderLevels {
+    /// Optional limit price per share for a limit, stop limit or bracket entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_price: Option<f64>,
+    /// Optional price per share that triggers a stop or stop limit order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_price: Option<f64>,
+    /// Optional price per share to take the profit at after a bracket entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub take_profit_price: Option<f64>,
+    /// Optional price per share to cut the loss at after a bracket entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_loss_price: Option<f64>,
+}
+
+impl OrderLevels {
+    /// Every level set here, the missing ones taken from `fallback`.
+    pub fn or(self, fallback: OrderLevels) -> OrderLevels {
+        OrderLevels {
+            limit_price: self.limit_price.or(fallback.limit_price),
+            stop_price: self.stop_price.or(fallback.stop_price),
+            take_profit_price: self.take_profit_price.or(fallback.take_profit_price),
+            stop_loss_price: self.stop_loss_price.or(fallback.stop_loss_price),
+        }
+    }
+
+    fn validate(&self) -> Result<(), AppErrors> {
+        let levels = [
+            ("limit_price", self.limit_price),
+            ("stop_price", self.stop_price),
+            ("take_profit_price", self.take_profit_price),
+            ("stop_loss_price", self.stop_loss_price),
+        ];
+        for (name, level) in levels {
+            if let Some(price) = level.filter(|price| !price.is_finite() || *price <= 0.0) {
+                return Err(AppErrors::ModelCreationError(format!(
+                    "The {name} has to be above 0. Price provided: {price}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OrderStyleKind {
+    /// Builds the order style from `levels`, failing when a price it needs is
+    /// missing or a bracket's take profit and stop loss are on the wrong side.
+    pub fn with_levels(
+        self,
+        order_type: &OrderType,
+        levels: OrderLevels,
+    ) -> Result<OrderStyle, AppErrors> {
+        levels.validate()?;
+        let require = |level: Option<f64>, name: &str| {
+            level.ok_or_else(|| {
+                AppErrors::ModelCreationError(format!(
+                    "A {} order needs a {name}",
+                    self.to_string().replace('_', " ")
+                ))
+            })
+        };
+        let style = match self {
+            OrderStyleKind::Market => OrderStyle::Market,
+            OrderStyleKind::Limit => {
+                OrderStyle::Limit { limit_price: require(levels.limit_price, "limit_price")? }
+            }
+            OrderStyleKind::Stop => {
+                OrderStyle::Stop { stop_price: require(levels.stop_price, "stop_price")? }
+            }
+            OrderStyleKind::StopLimit => OrderStyle::StopLimit {
+                stop_price: require(levels.stop_price, "stop_price")?,
+                limit_price: require(levels.limit_price, "limit_price")?,
+            },
+            OrderStyleKind::Bracket => {
+                let limit_price = require(levels.limit_price, "limit_price")?;
+                let take_profit_price = require(levels.take_profit_price, "take_profit_price")?;
+                let stop_loss_price = require(levels.stop_loss_price, "stop_loss_price")?;
+                let is_ordered = match order_type {
+                    OrderType::Buy => {
+                        stop_loss_price < limit_price && limit_price < take_profit_price
+                    }
+                    OrderType::Sell => {
+                        take_profit_price < limit_price && limit_price < stop_loss_price
+                    }
+                };
+                if !is_ordered {
+                    return Err(AppErrors::ModelCreationError(format!(
+                        "A {order_type:?} bracket needs the take profit and the stop loss on opposite sides of the limit price. Prices provided: limit {limit_price}, take profit {take_profit_price}, stop loss {stop_loss_price}"
+                    )));
+                }
+                OrderStyle::Bracket { limit_price, take_profit_price, stop_loss_price }
+            }
+        };
+        Ok(style)
+    }
+}
+
+impl fmt::Display for OrderStyleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            OrderStyleKind::Market => "market",
+            OrderStyleKind::Limit => "limit",
+            OrderStyleKind::Stop => "stop",
+            OrderStyleKind::StopLimit => "stop_limit",
+            OrderStyleKind::Bracket => "bracket",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+/// How long the order stays working, as Interactive Brokers names it.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum TimeInForce {
+    #[default]
+    Day,
+    Gtc,
+    Ioc,
+    Fok,
+}
+
+impl fmt::Display for TimeInForce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time_in_force = match self {
+            TimeInForce::Day => "DAY",
+            TimeInForce::Gtc => "GTC",
+            TimeInForce::Ioc => "IOC",
+            TimeInForce::Fok => "FOK",
+        };
+        write!(f, "{time_in_force}")
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum AdviceAction {
+    Buy,
+    Sell,
+    Hold,
+}
+
+impl AdviceAction {
+    /// The order to place for this advice, `None` for `Hold`.
+    pub fn order_type(&self) -> Option<OrderType> {
+        match self {
+            AdviceAction::Buy => Some(OrderType::Buy),
+            AdviceAction::Sell => Some(OrderType::Sell),
+            AdviceAction::Hold => None,
+        }
+    }
+}
+
+/// The model's trading decision. This is also the JSON schema the model has to
+/// answer with, so the field docs double as instructions to the model.
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct OrderAdvice {
+    /// BUY, SELL or HOLD.
+    pub action: AdviceAction,
+    /// How sure the model is about the action, from 0 to 1.
+    pub confidence: f64,
+    /// One or two sentences explaining the action.
+    pub rationale: String,
+    /// The news items and price movements that drove the decision.
+    pub key_factors: Vec<String>,
+    #[serde(flatten)]
+    pub levels: OrderLevels,
+}
+
+impl OrderAdvice {
+    pub fn validate(self) -> Result<Self, AppErrors> {
+        if !(0.0..=1.0).contains(&self.confidence) {
+            Err(AppErrors::GetOrderAdviceError(format!(
+                "The confidence has to be between 0 and 1. Confidence provided: {}",
+                self.confidence
+            )))
+        } else if self.rationale.trim().is_empty() {
+            Err(AppErrors::GetOrderAdviceError("The rationale is empty".to_string()))
+        } else if let Err(error) = self.levels.validate() {
+            Err(AppErrors::GetOrderAdviceError(error.to_string()))
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+/// An order advice with the prompt and the raw model answer it was parsed from.
 #[derive(Debug, Clone)]
+pub struct OrderAdviceResponse {
+    pub order_advice: OrderAdvice,
+    pub prompt: String,
+    pub raw_response: String,
+}
+
+/// One OHLCV bar. `date` is the start of the bar for intraday data and the
+/// trading day at midnight for daily and longer bars.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct StockPricePerformance {
-    pub date: String,
-    pub open: String,
-    pub high: String,
-    pub low: String,
+    pub date: NaiveDateTime,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: u64,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct News {
     pub title: String,
     pub summary: String,
-    pub time_published: String,
+    pub time_published: NaiveDateTime,
 }
 
-#[derive(Deserialize, Debug)]
-pub struct NewsApiResponse {
-    pub feed: Vec<News>,
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum PriceInterval {
+    #[serde(rename = "1min")]
+    OneMinute,
+    #[serde(rename = "5min")]
+    FiveMinutes,
+    #[serde(rename = "15min")]
+    FifteenMinutes,
+    #[serde(rename = "30min")]
+    ThirtyMinutes,
+    #[serde(rename = "60min")]
+    SixtyMinutes,
+    #[serde(rename = "daily")]
+    Daily,
+    #[serde(rename = "weekly")]
+    Weekly,
+    #[default]
+    #[serde(rename = "monthly")]
+    Monthly,
 }
 
-#[derive(Debug, Clone)]
+impl PriceInterval {
+    /// The `interval` parameter of Alpha Vantage's `TIME_SERIES_INTRADAY`, if this
+    /// is an intraday interval.
+    pub fn intraday_minutes(&self) -> Option<u32> {
+        match self {
+            PriceInterval::OneMinute => Some(1),
+            PriceInterval::FiveMinutes => Some(5),
+            PriceInterval::FifteenMinutes => Some(15),
+            PriceInterval::ThirtyMinutes => Some(30),
+            PriceInterval::SixtyMinutes => Some(60),
+            PriceInterval::Daily | PriceInterval::Weekly | PriceInterval::Monthly => None,
+        }
+    }
+
+    /// When the bar dated `date` is complete. Intraday bars are dated by their
+    /// start, daily and longer bars by their last trading day.
+    pub fn bar_end(&self, date: NaiveDateTime) -> NaiveDateTime {
+        match self.intraday_minutes() {
+            Some(minutes) => date + Duration::minutes(minutes.into()),
+            None => date + Duration::days(1),
+        }
+    }
+
+    pub fn alpha_vantage_function(&self) -> &'static str {
+        match self {
+            PriceInterval::Daily => "TIME_SERIES_DAILY",
+            PriceInterval::Weekly => "TIME_SERIES_WEEKLY",
+            PriceInterval::Monthly => "TIME_SERIES_MONTHLY",
+            _ => "TIME_SERIES_INTRADAY",
+        }
+    }
+}
+
+impl fmt::Display for PriceInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.intraday_minutes() {
+            Some(minutes) => write!(f, "{minutes}min"),
+            None => match self {
+                PriceInterval::Daily => write!(f, "daily"),
+                PriceInterval::Weekly => write!(f, "weekly"),
+                _ => write!(f, "monthly"),
+            },
+        }
+    }
+}
+
+/// Which bars to fetch: the bar interval and, optionally, how many of the most
+/// recent bars to keep. Without a lookback every available bar is kept.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct PriceHistory {
+    #[serde(default)]
+    pub interval: PriceInterval,
+    #[serde(default)]
+    pub lookback: Option<usize>,
+}
+
+#[derive(Serialize, Debug, Clone)]
 pub struct StockData {
-    pub stock: Stock,
+    pub stock: Instrument,
+    pub price_history: PriceHistory,
     pub stock_price_performance: Vec<StockPricePerformance>,
     pub news: Vec<News>,
 }
 
-pub struct Config<'a> {
-    pub alpha_vantage_api_key: &'a str,
-    pub interactive_brokers_connection_url_with_port: &'a str,
-    pub ollama_model: &'a str,
+impl StockData {
+    pub fn latest_bar(&self) -> Option<&StockPricePerformance> {
+        self.stock_price_performance.iter().max_by_key(|bar| bar.date)
+    }
+
+    pub fn current_price(&self) -> Result<Decimal, AppErrors> {
+        self.latest_bar().map(|bar| bar.close).ok_or_else(|| {
+            AppErrors::GetStockDataError(format!("No price data available for: {}", self.stock))
+        })
+    }
+
+    /// The data as it was known at `cutoff`: the bars complete by then, trimmed
+    /// to the lookback, and the news published by then. Anything later is left
+    /// out, so replays can't leak the future into the prompt.
+    pub fn as_of(&self, cutoff: NaiveDateTime) -> StockData {
+        let mut bars: Vec<StockPricePerformance> = self
+            .stock_price_performance
+            .iter()
+            .filter(|bar| self.price_history.interval.bar_end(bar.date) <= cutoff)
+            .cloned()
+            .collect();
+        bars.sort_by_key(|bar| bar.date);
+        if let Some(lookback) = self.price_history.lookback {
+            bars.drain(..bars.len().saturating_sub(lookback));
+        }
+        StockData {
+            stock: self.stock.clone(),
+            price_history: self.price_history,
+            stock_price_performance: bars,
+            news: self.news.iter().filter(|item| item.time_published <= cutoff).cloned().collect(),
+        }
+    }
+
+    /// SHA-256 of the JSON serialized snapshot, to tell which market data a
+    /// journaled decision was based on.
+    pub fn snapshot_hash(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        Sha256::digest(json).iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Broker {
+    Live,
+    Paper,
+}
+
+impl FromStr for Broker {
+    type Err = AppErrors;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "live" => Ok(Broker::Live),
+            "paper" => Ok(Broker::Paper),
+            _ => Err(AppErrors::ConfigError(format!(
+                "The broker has to be either live or paper. Broker provided: {value}"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PaperTradingConfig {
+    /// The currency the paper account keeps its cash in.
+    pub base_currency: Currency,
+    pub starting_cash: Decimal,
+    pub commission_per_share: Decimal,
+    pub minimum_commission: Decimal,
+    /// Whether stocks and ETFs are bought in fractions, from `fractional_shares`.
+    pub fractional_shares: bool,
+}
+
+/// The Alpha Vantage call budget and how often a throttled or failed call is
+/// retried. A budget of 0 is unlimited.
+#[derive(Debug, Clone)]
+pub struct AlphaVantageLimits {
+    pub calls_per_minute: u32,
+    pub calls_per_day: u32,
+    pub max_retries: u32,
+}
+
+/// Where fetched market data is kept and for how long it is served before
+/// Alpha Vantage is asked again.
+#[derive(Debug, Clone)]
+pub struct MarketDataCacheConfig {
+    pub path: String,
+    pub time_series_ttl_seconds: u64,
+    pub news_ttl_seconds: u64,
+    pub exchange_rate_ttl_seconds: u64,
+}
+
+/// Pre-trade limits checked by the risk engine. `None` and empty lists turn a
+/// rule off. The notional and loss limits are in the account's currency.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RiskLimits {
+    /// Overrides the paper account's currency. A live account counts in the
+    /// currency Interactive Brokers reports and fails to start when this
+    /// disagrees with it.
+    pub base_currency: Option<Currency>,
+    pub max_order_notional: Option<f64>,
+    pub max_position_notional: Option<f64>,
+    pub max_daily_loss: Option<f64>,
+    pub max_orders_per_hour: Option<u32>,
+    pub trading_hours_utc: Option<TradingHours>,
+    pub allowed_tickers: Vec<String>,
+    pub denied_tickers: Vec<String>,
+}
+
+/// A daily `HH:MM-HH:MM` window. The end is exclusive and a window whose end is
+/// before its start runs over midnight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TradingHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TradingHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+impl FromStr for TradingHours {
+    type Err = AppErrors;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            AppErrors::ConfigError(format!(
+                "Trading hours have to look like 14:30-21:00. Value provided: {value}"
+            ))
+        };
+        let (start, end) = value.split_once('-').ok_or_else(invalid)?;
+        let parse_time = |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M");
+        Ok(TradingHours {
+            start: parse_time(start).map_err(|_| invalid())?,
+            end: parse_time(end).map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl fmt::Display for TradingHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+    }
 }
 
-#[derive(Debug)]
+#[derive(Clone)]
+pub struct Config {
+    pub alpha_vantage_api_key: String,
+    pub alpha_vantage_base_url: String,
+    pub alpha_vantage_limits: AlphaVantageLimits,
+    pub interactive_brokers_connection_url_with_port: String,
+    pub ollama_model: String,
+    /// How long a call to Interactive Brokers or Alpha Vantage may take.
+    pub request_timeout_seconds: u64,
+    pub broker: Broker,
+    /// Whether the account may trade fractional shares of contracts that allow it.
+    pub fractional_shares: bool,
+    pub paper_trading: PaperTradingConfig,
+    pub journal_path: String,
+    pub watchlist_path: String,
+    pub market_data_cache: MarketDataCacheConfig,
+    pub risk: RiskLimits,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("alpha_vantage_api_key", &"<redacted>")
+            .field("alpha_vantage_base_url", &self.alpha_vantage_base_url)
+            .field("alpha_vantage_limits", &self.alpha_vantage_limits)
+            .field(
+                "interactive_brokers_connection_url_with_port",
+                &self.interactive_brokers_connection_url_with_port,
+            )
+            .field("ollama_model", &self.ollama_model)
+            .field("request_timeout_seconds", &self.request_timeout_seconds)
+            .field("broker", &self.broker)
+            .field("fractional_shares", &self.fractional_shares)
+            .field("paper_trading", &self.paper_trading)
+            .field("journal_path", &self.journal_path)
+            .field("watchlist_path", &self.watchlist_path)
+            .field("market_data_cache", &self.market_data_cache)
+            .field("risk", &self.risk)
+            .finish()
+    }
+}
+
+/// A position in one stock. The market value and the profits or losses are
+/// only known when the broker reports them.
+#[derive(Serialize, Debug, Clone)]
 pub struct StockInvestment {
-    pub(crate) stock: Stock,
+    pub(crate) stock: Instrument,
     pub(crate) stock_name: String,
     pub(crate) current_invested_amount: Money,
+    pub(crate) quantity: f64,
+    pub(crate) average_cost: Money,
+    pub(crate) market_value: Option<Money>,
+    pub(crate) unrealized_pnl: Option<Money>,
+    pub(crate) realized_pnl: Option<Money>,
+}
+
+/// Every position of the account plus its cash and net liquidation value, both
+/// in the account's base currency. Positions keep the currency they trade in.
+#[derive(Serialize, Debug, Clone)]
+pub struct Portfolio {
+    pub base_currency: Currency,
+    pub positions: Vec<StockInvestment>,
+    pub cash: Money,
+    pub net_liquidation_value: Money,
 }
\ No newline at end of file
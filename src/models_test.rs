@@ -1,22 +1,430 @@
 mod money_model {
     use crate::errors::AppErrors;
-    use crate::models::Money;
+    use crate::models::{Currency, Money};
+    use rust_decimal::Decimal;
+
+    fn dollars(amount: &str) -> Money {
+        Money::from_decimal(amount.parse().unwrap(), Currency::USD)
+    }
+
     #[test]
     fn test_money_model_new_method_success() {
-        let maybe_money_model: Result<Money, AppErrors> = Money::new(1.00);
+        let maybe_money_model: Result<Money, AppErrors> =
+            Money::new(Decimal::new(100, 2), Currency::USD);
         assert!(maybe_money_model.is_ok())
     }
 
     #[test]
     fn test_money_model_new_method_negative_amount() {
-        let maybe_money_model: Result<Money, AppErrors> = Money::new(-1.0);
+        let maybe_money_model: Result<Money, AppErrors> =
+            Money::new(Decimal::from(-1), Currency::USD);
         assert!(maybe_money_model.is_err())
     }
 
     #[test]
     fn test_money_model_new_method_more_than_two_digits_after_coma() {
-        let maybe_money_model: Result<Money, AppErrors> = Money::new(1.7382300);
+        let maybe_money_model: Result<Money, AppErrors> =
+            Money::new(Decimal::new(17382300, 7), Currency::USD);
         assert!(maybe_money_model.is_err())
     }
+
+    #[test]
+    fn test_money_model_new_method_follows_the_minor_unit() {
+        let yen: Currency = "jpy".parse().unwrap();
+        assert!(Money::new(Decimal::new(1_005, 1), yen).is_err());
+        assert!(Money::new(Decimal::new(1_000, 1), yen).is_ok())
+    }
+
+    #[test]
+    fn test_money_model_from_f64_keeps_amounts_floats_misprint() {
+        let money_model = Money::from_f64(0.1 + 0.2, Currency::USD).unwrap().round();
+        assert_eq!(money_model, dollars("0.30"));
+        assert!(Money::new(Decimal::new(1_007, 2), Currency::USD).is_ok());
+        assert!(Money::from_f64(f64::NAN, Currency::USD).is_err())
+    }
+
+    #[test]
+    fn test_money_model_round_keeps_negative_amounts() {
+        assert_eq!(dollars("-12.345678").round(), dollars("-12.35"));
+        assert_eq!(dollars("0.125").round(), dollars("0.13"))
+    }
+
+    #[test]
+    fn test_money_model_checked_arithmetic() {
+        let euros = Money::from_decimal(Decimal::ONE, "EUR".parse().unwrap());
+        assert_eq!(dollars("1.50").checked_add(dollars("2.25")).unwrap(), dollars("3.75"));
+        assert_eq!(dollars("1.50").checked_sub(dollars("2.25")).unwrap(), dollars("-0.75"));
+        assert_eq!(dollars("1.50").checked_mul(Decimal::from(3)).unwrap(), dollars("4.50"));
+        assert!(matches!(
+            dollars("1.50").checked_add(euros),
+            Err(AppErrors::MoneyArithmeticError(_))
+        ));
+        assert!(dollars("1").checked_mul(Decimal::MAX).unwrap().checked_mul(Decimal::TEN).is_err())
+    }
+
+    #[test]
+    fn test_money_model_serializes_the_currency() {
+        let json = serde_json::to_value(dollars("236.50")).unwrap();
+        assert_eq!(json, serde_json::json!({ "amount": "236.50", "currency": "USD" }));
+        assert_eq!(serde_json::from_value::<Money>(json).unwrap(), dollars("236.50"))
+    }
+
+    #[test]
+    fn test_currency_from_str() {
+        assert_eq!("usd".parse::<Currency>().unwrap(), Currency::USD);
+        assert!("US".parse::<Currency>().is_err());
+        assert!("U$D".parse::<Currency>().is_err())
+    }
+}
+mod instrument {
+    use crate::models::{Currency, Instrument, OptionRight};
+    use rust_decimal::Decimal;
+    use serde_json::json;
+
+    fn instrument(value: serde_json::Value) -> Result<Instrument, serde_json::Error> {
+        serde_json::from_value(value)
+    }
+
+    #[test]
+    fn test_stock_currency_from_the_exchange_suffix() {
+        assert_eq!(Instrument::stock("SAP.DE").currency(), "EUR".parse::<Currency>().unwrap());
+        assert_eq!(Instrument::stock("SAP.DEX").currency(), "EUR".parse::<Currency>().unwrap());
+        assert_eq!(Instrument::stock("7203.T").currency(), "JPY".parse::<Currency>().unwrap());
+        assert_eq!(Instrument::stock("AAPL").currency(), Currency::USD)
+    }
+
+    #[test]
+    fn test_stock_symbol_without_the_exchange_suffix() {
+        assert_eq!(Instrument::stock("SAP.DE").symbol(), "SAP");
+        assert_eq!(Instrument::stock("SAP.DE").ticker(), "SAP.DE");
+        assert_eq!(Instrument::stock("7203.t").symbol(), "7203");
+        // A share class isn't an exchange.
+        assert_eq!(Instrument::stock("BRK.B").symbol(), "BRK.B");
+        assert_eq!(Instrument::stock("BRK.B").currency(), Currency::USD)
+    }
+
+    #[test]
+    fn test_stock_from_json_is_the_stock_of_the_ticker() {
+        let maybe_stock = instrument(json!({ "security_type": "stock", "symbol": "SAP.DE" }));
+        assert_eq!(maybe_stock.unwrap(), Instrument::stock("SAP.DE"))
+    }
+
+    #[test]
+    fn test_option_defaults() {
+        let option = instrument(json!({
+            "security_type": "option",
+            "symbol": "AAPL",
+            "expiry": "20261218",
+            "strike": 200,
+            "right": "call"
+        }))
+        .unwrap();
+        assert_eq!(
+            option,
+            Instrument::Option {
+                symbol: "AAPL".to_string(),
+                exchange: "SMART".to_string(),
+                currency: Currency::USD,
+                expiry: "20261218".to_string(),
+                strike: Decimal::from(200),
+                right: OptionRight::Call,
+                multiplier: Decimal::ONE_HUNDRED,
+            }
+        );
+        assert_eq!(option.to_string(), "AAPL 20261218 200 C")
+    }
+
+    #[test]
+    fn test_instrument_json_round_trip() {
+        let future = instrument(json!({
+            "security_type": "future",
+            "symbol": "ES",
+            "exchange": "CME",
+            "expiry": "202612",
+            "multiplier": 50
+        }))
+        .unwrap();
+        let maybe_future = instrument(serde_json::to_value(&future).unwrap());
+        assert_eq!(maybe_future.unwrap(), future)
+    }
+
+    #[test]
+    fn test_invalid_instruments() {
+        let option = |expiry: &str, strike: i64| {
+            instrument(json!({
+                "security_type": "option",
+                "symbol": "AAPL",
+                "expiry": expiry,
+                "strike": strike,
+                "right": "put"
+            }))
+        };
+        assert!(option("202612", 200).is_err());
+        assert!(option("20261218", 0).is_err());
+        assert!(instrument(json!({
+            "security_type": "future",
+            "symbol": "ES",
+            "expiry": "202612",
+            "multiplier": 50
+        }))
+        .is_err());
+        assert!(instrument(
+            json!({ "security_type": "forex", "symbol": "EUR", "currency": "EUR" })
+        )
+        .is_err());
+        assert!(instrument(json!({ "security_type": "bond", "symbol": "T" })).is_err())
+    }
+
+    #[test]
+    fn test_forex_and_crypto() {
+        let forex =
+            instrument(json!({ "security_type": "forex", "symbol": "EUR", "currency": "USD" }))
+                .unwrap();
+        let crypto = instrument(json!({ "security_type": "crypto", "symbol": "BTC" })).unwrap();
+        assert_eq!((forex.exchange(), forex.to_string()), ("IDEALPRO", "EUR.USD".to_string()));
+        assert_eq!((crypto.exchange(), crypto.to_string()), ("PAXOS", "BTC.USD".to_string()));
+        assert_eq!(forex.news_ticker(), Some("FOREX:EUR".to_string()));
+        assert!(!crypto.is_equity())
+    }
+
+    #[test]
+    fn test_multiplier() {
+        assert_eq!(Instrument::stock("AAPL").multiplier(), Decimal::ONE);
+        let future = instrument(json!({
+            "security_type": "future",
+            "symbol": "ES",
+            "exchange": "CME",
+            "expiry": "20261218",
+            "multiplier": 50
+        }));
+        assert_eq!(future.unwrap().multiplier(), Decimal::from(50))
+    }
+}
+
+mod exchange_rate {
+    use crate::errors::AppErrors;
+    use crate::models::{Currency, ExchangeRate, Money};
+    use rust_decimal::Decimal;
+
+    fn euros() -> Currency {
+        "EUR".parse().unwrap()
+    }
+
+    #[test]
+    fn test_exchange_rate_convert_rounds_to_the_target_currency() {
+        let yen = "JPY".parse::<Currency>().unwrap();
+        let exchange_rate = ExchangeRate { from: euros(), to: yen, rate: Decimal::new(16_234, 2) };
+        let money = Money::new(Decimal::new(1_005, 2), euros()).unwrap();
+        assert_eq!(
+            exchange_rate.convert(money).unwrap(),
+            Money::from_decimal(Decimal::from(1_632), yen)
+        )
+    }
+
+    #[test]
+    fn test_exchange_rate_convert_other_currency() {
+        let exchange_rate =
+            ExchangeRate { from: euros(), to: Currency::USD, rate: Decimal::new(108, 2) };
+        let maybe_money = exchange_rate.convert(Money::zero(Currency::USD));
+        assert!(matches!(maybe_money, Err(AppErrors::MoneyArithmeticError(_))))
+    }
+
+    #[test]
+    fn test_exchange_rate_identity() {
+        let money = Money::new(Decimal::new(1_005, 2), euros()).unwrap();
+        assert_eq!(ExchangeRate::identity(euros()).convert(money).unwrap(), money)
+    }
+}
+
+mod price_history {
+    use crate::models::{PriceHistory, PriceInterval};
+    use serde_json::json;
+
+    #[test]
+    fn test_price_history_deserialize_success() {
+        let maybe_price_history =
+            serde_json::from_value::<PriceHistory>(json!({ "interval": "15min", "lookback": 20 }));
+        assert_eq!(
+            maybe_price_history.unwrap(),
+            PriceHistory { interval: PriceInterval::FifteenMinutes, lookback: Some(20) }
+        )
+    }
+
+    #[test]
+    fn test_price_history_deserialize_defaults_to_all_monthly_bars() {
+        let maybe_price_history = serde_json::from_value::<PriceHistory>(json!({}));
+        assert_eq!(
+            maybe_price_history.unwrap(),
+            PriceHistory { interval: PriceInterval::Monthly, lookback: None }
+        )
+    }
+
+    #[test]
+    fn test_price_history_deserialize_unknown_interval() {
+        let maybe_price_history =
+            serde_json::from_value::<PriceHistory>(json!({ "interval": "hourly" }));
+        assert!(maybe_price_history.is_err())
+    }
+
+    #[test]
+    fn test_price_interval_display_matches_request_names() {
+        let intervals =
+            [PriceInterval::OneMinute, PriceInterval::SixtyMinutes, PriceInterval::Weekly];
+        for interval in intervals {
+            assert_eq!(json!(interval), json!(interval.to_string()));
+        }
+    }
+}
+
+mod stock_data {
+    use crate::models::{
+        Instrument, News, PriceHistory, PriceInterval, StockData, StockPricePerformance,
+    };
+    use crate::services::order_advice_prompt;
+    use chrono::{NaiveDate, NaiveDateTime};
+    use rust_decimal::Decimal;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 1, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    fn news(title: &str, time_published: NaiveDateTime) -> News {
+        News { title: title.to_string(), summary: String::new(), time_published }
+    }
+
+    fn bar(day: u32, close: i64) -> StockPricePerformance {
+        StockPricePerformance {
+            date: NaiveDate::from_ymd_opt(2025, 1, day).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            open: Decimal::from(close),
+            high: Decimal::from(close),
+            low: Decimal::from(close),
+            close: Decimal::from(close),
+            volume: 100,
+        }
+    }
+
+    fn stock_data(stock_price_performance: Vec<StockPricePerformance>) -> StockData {
+        StockData {
+            stock: Instrument::stock("AAPL"),
+            price_history: PriceHistory::default(),
+            stock_price_performance,
+            news: vec![],
+        }
+    }
+
+    #[test]
+    fn test_stock_data_current_price_uses_latest_close() {
+        let stock_data = stock_data(vec![bar(3, 30), bar(1, 10), bar(2, 20)]);
+        assert_eq!(stock_data.current_price().unwrap(), Decimal::from(30))
+    }
+
+    #[test]
+    fn test_stock_data_current_price_without_bars() {
+        let stock_data = stock_data(vec![]);
+        assert!(stock_data.current_price().is_err())
+    }
+
+    #[test]
+    fn test_stock_data_as_of_keeps_completed_bars_and_published_news() {
+        let mut stock_data = stock_data(vec![bar(1, 10), bar(2, 20), bar(3, 30)]);
+        stock_data.price_history.interval = PriceInterval::Daily;
+        stock_data.news = vec![news("Before", at(2, 16)), news("After", at(3, 9))];
+
+        let as_of = stock_data.as_of(at(3, 0));
+        let dates: Vec<NaiveDateTime> =
+            as_of.stock_price_performance.iter().map(|bar| bar.date).collect();
+        assert_eq!(dates, vec![at(1, 0), at(2, 0)]);
+        assert_eq!(as_of.news, vec![news("Before", at(2, 16))]);
+        assert_eq!(as_of.current_price().unwrap(), Decimal::from(20))
+    }
+
+    #[test]
+    fn test_stock_data_as_of_keeps_the_lookback_before_the_cutoff() {
+        let mut stock_data = stock_data((1..=5).map(|day| bar(day, day.into())).collect());
+        stock_data.price_history =
+            PriceHistory { interval: PriceInterval::Daily, lookback: Some(2) };
+
+        let as_of = stock_data.as_of(at(4, 0));
+        let closes: Vec<Decimal> =
+            as_of.stock_price_performance.iter().map(|bar| bar.close).collect();
+        assert_eq!(closes, vec![Decimal::from(2), Decimal::from(3)])
+    }
+
+    #[test]
+    fn test_stock_data_as_of_intraday_bars_end_after_their_interval() {
+        let mut stock_data = stock_data(vec![]);
+        stock_data.price_history.interval = PriceInterval::SixtyMinutes;
+        stock_data.stock_price_performance = vec![
+            StockPricePerformance { date: at(2, 14), ..bar(2, 20) },
+            StockPricePerformance { date: at(2, 15), ..bar(2, 21) },
+        ];
+
+        let as_of = stock_data.as_of(at(2, 15));
+        assert_eq!(as_of.stock_price_performance.len(), 1);
+        assert_eq!(as_of.stock_price_performance[0].date, at(2, 14))
+    }
+
+    #[test]
+    fn test_stock_data_as_of_leaves_later_data_out_of_the_prompt() {
+        let mut stock_data = stock_data(vec![bar(1, 10), bar(2, 20), bar(3, 30), bar(4, 40)]);
+        stock_data.price_history.interval = PriceInterval::Daily;
+        stock_data.news = vec![news("Old news", at(1, 12)), news("Tomorrow's news", at(3, 8))];
+
+        let prompt = order_advice_prompt(&stock_data.as_of(at(3, 0)));
+        assert!(prompt.contains("2025-01-02") && prompt.contains("Old news"));
+        assert!(!prompt.contains("2025-01-03"));
+        assert!(!prompt.contains("2025-01-04"));
+        assert!(!prompt.contains("Tomorrow's news"))
+    }
+}
+
+mod order_style {
+    use crate::models::{OrderLevels, OrderStyle, OrderStyleKind, OrderType};
+
+    fn levels(limit_price: f64, take_profit_price: f64, stop_loss_price: f64) -> OrderLevels {
+        OrderLevels {
+            limit_price: Some(limit_price),
+            take_profit_price: Some(take_profit_price),
+            stop_loss_price: Some(stop_loss_price),
+            ..OrderLevels::default()
+        }
+    }
+
+    #[test]
+    fn test_order_style_with_levels_limit_success() {
+        let maybe_style =
+            OrderStyleKind::Limit.with_levels(&OrderType::Buy, levels(236.0, 0.1, 0.1));
+        assert_eq!(maybe_style.unwrap(), OrderStyle::Limit { limit_price: 236.0 })
+    }
+
+    #[test]
+    fn test_order_style_with_levels_missing_price() {
+        let maybe_style =
+            OrderStyleKind::StopLimit.with_levels(&OrderType::Buy, levels(236.0, 250.0, 225.0));
+        assert!(maybe_style.unwrap_err().to_string().contains("stop_price"))
+    }
+
+    #[test]
+    fn test_order_style_with_levels_bracket_sides() {
+        let buy = OrderStyleKind::Bracket.with_levels(&OrderType::Buy, levels(236.0, 250.0, 225.0));
+        let sell =
+            OrderStyleKind::Bracket.with_levels(&OrderType::Sell, levels(236.0, 250.0, 225.0));
+        assert!(buy.is_ok());
+        assert!(sell.is_err())
+    }
+
+    #[test]
+    fn test_order_style_with_levels_negative_price() {
+        let maybe_style =
+            OrderStyleKind::Market.with_levels(&OrderType::Buy, levels(-1.0, 250.0, 225.0));
+        assert!(maybe_style.is_err())
+    }
+
+    #[test]
+    fn test_order_levels_or_prefers_own_levels() {
+        let requested = OrderLevels { limit_price: Some(230.0), ..OrderLevels::default() };
+        let levels = requested.or(levels(236.0, 250.0, 225.0));
+        assert_eq!(levels.limit_price, Some(230.0));
+        assert_eq!(levels.stop_loss_price, Some(225.0))
+    }
 }
-mod stock {}
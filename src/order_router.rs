@@ -0,0 +1,444 @@
+use crate::errors::AppErrors;
+use crate::ib_connection::{is_connection_error, IbConnection};
+use crate::models::{Order, OrderStyle, OrderType};
+use crate::orders::{Fill, OrderBook, OrderModification, OrderRecord, OrderStatus};
+use crate::services::{ib_contract, ib_orders};
+use ibapi::client::Subscription;
+use ibapi::contracts::Contract;
+use ibapi::orders::{
+    CancelOrder, Execution, ExecutionFilter, Executions, Order as IbOrder, Orders, PlaceOrder,
+};
+use ibapi::{Client as IbClient, Error as IbError};
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const CANCEL_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often the open orders are asked for again while resumed orders work.
+const RESUME_INTERVAL: Duration = Duration::from_secs(5);
+
+type Reply<T> = Sender<Result<T, AppErrors>>;
+
+enum Command {
+    Place(Order, Reply<i32>),
+    Cancel(i32, Reply<()>),
+    Modify(i32, OrderModification, Reply<()>),
+}
+
+/// Places, cancels and modifies orders on a background thread that owns the
+/// IB connection and feeds every status, execution and commission report into
+/// the `OrderBook`. The thread is started on the first command and again after
+/// its connection was lost, each time on a dedicated connection with a new
+/// client id since the updates need a connection that stays subscribed. The
+/// orders that weren't done when the connection closed are resumed from the
+/// open orders IB reports.
+pub struct IbOrderRouter {
+    connection: Arc<IbConnection>,
+    book: Arc<OrderBook>,
+    commands: Mutex<Option<Sender<Command>>>,
+}
+
+impl IbOrderRouter {
+    pub fn new(connection: Arc<IbConnection>) -> Self {
+        IbOrderRouter { connection, book: Arc::new(OrderBook::new()), commands: Mutex::new(None) }
+    }
+
+    pub fn with_book(self, book: OrderBook) -> Self {
+        IbOrderRouter { book: Arc::new(book), ..self }
+    }
+
+    pub fn book(&self) -> &OrderBook {
+        &self.book
+    }
+
+    /// Places the order and returns its IB order id, the parent id for brackets.
+    pub fn place(&self, order: Order) -> Result<i32, AppErrors> {
+        let (reply, response) = mpsc::channel();
+        self.send(Command::Place(order, reply));
+        response.recv().unwrap_or_else(|_| Err(connection_closed(AppErrors::PlaceOrderError)))
+    }
+
+    pub fn cancel(&self, order_id: i32) -> Result<(), AppErrors> {
+        let (reply, response) = mpsc::channel();
+        self.send(Command::Cancel(order_id, reply));
+        response.recv().unwrap_or_else(|_| Err(connection_closed(AppErrors::CancelOrderError)))
+    }
+
+    pub fn modify(&self, order_id: i32, modification: OrderModification) -> Result<(), AppErrors> {
+        let (reply, response) = mpsc::channel();
+        self.send(Command::Modify(order_id, modification, reply));
+        response.recv().unwrap_or_else(|_| Err(connection_closed(AppErrors::ModifyOrderError)))
+    }
+
+    /// Starts the thread right away when the book holds orders of an earlier
+    /// run that aren't done, so they are followed before the next command.
+    pub fn resume(&self) {
+        let mut commands = self.commands.lock().unwrap();
+        if commands.is_none() && !self.book.unfinished().is_empty() {
+            *commands = Some(self.spawn());
+        }
+    }
+
+    fn send(&self, command: Command) {
+        let mut commands = self.commands.lock().unwrap();
+        let command = match commands.as_ref() {
+            Some(sender) => match sender.send(command) {
+                Ok(()) => return,
+                Err(mpsc::SendError(command)) => command,
+            },
+            None => command,
+        };
+        let sender = self.spawn();
+        // The receiver only goes away once the thread ends, after this send.
+        let _ = sender.send(command);
+        *commands = Some(sender);
+    }
+
+    fn spawn(&self) -> Sender<Command> {
+        let (sender, receiver) = mpsc::channel();
+        let connection = self.connection.clone();
+        let book = self.book.clone();
+        thread::spawn(move || run(&connection, &book, receiver));
+        sender
+    }
+}
+
+fn connection_closed(error: fn(String) -> AppErrors) -> AppErrors {
+    error("The connection to Interactive Brokers for orders closed".to_string())
+}
+
+fn run(connection: &IbConnection, book: &OrderBook, commands: Receiver<Command>) {
+    let client = match connection.connect_dedicated() {
+        Ok(client) => client,
+        Err(error) => {
+            // Fails the command that started the thread and any sent meanwhile.
+            for command in commands.try_iter() {
+                fail(command, error.clone());
+            }
+            return;
+        }
+    };
+    let mut router = Router {
+        client: &client,
+        book,
+        working: HashMap::new(),
+        resumed_at: Instant::now(),
+        connection_lost: false,
+    };
+    router.resume();
+    // Ending the thread drops the receiver, so the next command reconnects.
+    while !router.connection_lost {
+        match commands.recv_timeout(POLL_INTERVAL) {
+            Ok(command) => router.handle(command),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        router.poll();
+    }
+}
+
+fn fail(command: Command, message: String) {
+    // A caller that stopped waiting doesn't need the reply.
+    match command {
+        Command::Place(_, reply) => drop(reply.send(Err(AppErrors::PlaceOrderError(message)))),
+        Command::Cancel(_, reply) => drop(reply.send(Err(AppErrors::CancelOrderError(message)))),
+        Command::Modify(_, _, reply) => drop(reply.send(Err(AppErrors::ModifyOrderError(message)))),
+    }
+}
+
+/// An order that is still working, with what is needed to modify it. Resumed
+/// orders have no updates of their own and are followed by `resume`.
+struct WorkingOrder<'a> {
+    contract: Contract,
+    ib_order: IbOrder,
+    updates: Option<Subscription<'a, PlaceOrder>>,
+}
+
+struct Router<'a> {
+    client: &'a IbClient,
+    book: &'a OrderBook,
+    working: HashMap<i32, WorkingOrder<'a>>,
+    resumed_at: Instant,
+    connection_lost: bool,
+}
+
+impl<'a> Router<'a> {
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::Place(order, reply) => drop(reply.send(self.place(order))),
+            Command::Cancel(order_id, reply) => drop(reply.send(self.cancel(order_id))),
+            Command::Modify(order_id, modification, reply) => {
+                drop(reply.send(self.modify(order_id, modification)))
+            }
+        }
+    }
+
+    fn place(&mut self, order: Order) -> Result<i32, AppErrors> {
+        let contract = ib_contract(&order.stock)?;
+        let order_id = self.client.next_order_id();
+        let ib_orders = ib_orders(order_id, &order);
+        // A bracket uses the ids after the parent for its take profit and stop loss.
+        for _ in 1..ib_orders.len() {
+            self.client.next_order_id();
+        }
+
+        for ib_order in ib_orders {
+            self.book.insert(ib_order_record(&ib_order, &order));
+            match self.client.place_order(ib_order.order_id, &contract, &ib_order) {
+                Ok(updates) => {
+                    let working = WorkingOrder {
+                        contract: contract.clone(),
+                        ib_order: ib_order.clone(),
+                        updates: Some(updates),
+                    };
+                    self.working.insert(ib_order.order_id, working);
+                }
+                Err(error) => {
+                    let error = self.failed(error, AppErrors::PlaceOrderError);
+                    self.book.update(ib_order.order_id, |record| {
+                        record.status = OrderStatus::Inactive;
+                        record.messages.push(error.to_string());
+                    });
+                    if ib_order.order_id == order_id {
+                        return Err(error);
+                    }
+                }
+            }
+        }
+        Ok(order_id)
+    }
+
+    fn cancel(&mut self, order_id: i32) -> Result<(), AppErrors> {
+        self.working_record(order_id).map_err(AppErrors::CancelOrderError)?;
+        let confirmations = self
+            .client
+            .cancel_order(order_id, "")
+            .map_err(|error| self.failed(error, AppErrors::CancelOrderError))?;
+        if let Some(confirmation) = confirmations.next_timeout(CANCEL_CONFIRMATION_TIMEOUT) {
+            match confirmation {
+                CancelOrder::OrderStatus(status) => self.book.record_status(
+                    order_id,
+                    OrderStatus::from_ib(&status.status),
+                    status.filled,
+                    status.remaining,
+                    status.average_fill_price,
+                ),
+                CancelOrder::Notice(notice) => {
+                    self.book.record_message(order_id, notice.to_string())
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// IB modifies an order when it is placed again with the same order id.
+    fn modify(&mut self, order_id: i32, modification: OrderModification) -> Result<(), AppErrors> {
+        let modified = self
+            .working_record(order_id)
+            .and_then(|record| record.modified(modification))
+            .map_err(AppErrors::ModifyOrderError)?;
+        let working = self.working.get(&order_id).ok_or_else(|| {
+            AppErrors::ModifyOrderError(format!("Order {order_id} isn't working anymore"))
+        })?;
+        let mut ib_order = working.ib_order.clone();
+        ib_order.total_quantity = modified.quantity;
+        match modified.style {
+            OrderStyle::Limit { limit_price } => ib_order.limit_price = Some(limit_price),
+            OrderStyle::Stop { stop_price } => ib_order.aux_price = Some(stop_price),
+            OrderStyle::StopLimit { stop_price, limit_price } => {
+                ib_order.limit_price = Some(limit_price);
+                ib_order.aux_price = Some(stop_price);
+            }
+            OrderStyle::Bracket { limit_price, .. } => ib_order.limit_price = Some(limit_price),
+            OrderStyle::Market => {}
+        }
+        // Only the last order of a bracket transmits the group, a modification
+        // on its own has to be transmitted.
+        ib_order.transmit = true;
+
+        let contract = working.contract.clone();
+        let updates = self
+            .client
+            .place_order(order_id, &contract, &ib_order)
+            .map_err(|error| self.failed(error, AppErrors::ModifyOrderError))?;
+        self.working.insert(order_id, WorkingOrder { contract, ib_order, updates: Some(updates) });
+        self.book.update(order_id, |record| {
+            record.quantity = modified.quantity;
+            record.remaining_quantity = modified.quantity - record.filled_quantity;
+            record.style = modified.style;
+        });
+        Ok(())
+    }
+
+    /// Remembers when the connection is gone so the thread can end.
+    fn failed(&mut self, error: IbError, app_error: fn(String) -> AppErrors) -> AppErrors {
+        self.connection_lost |= is_connection_error(&error);
+        app_error(error.to_string())
+    }
+
+    fn working_record(&self, order_id: i32) -> Result<OrderRecord, String> {
+        match self.book.get(order_id) {
+            Some(record) if record.status.is_done() => {
+                Err(format!("Order {order_id} is already {:?}", record.status))
+            }
+            Some(record) => Ok(record),
+            None => Err(format!("There is no order with id {order_id}")),
+        }
+    }
+
+    /// Follows the unfinished orders placed before the thread started. Those
+    /// IB still lists as open are working again, the others are closed with
+    /// the executions of today.
+    fn resume(&mut self) {
+        self.resumed_at = Instant::now();
+        let resumed: HashSet<i32> = self
+            .book
+            .unfinished()
+            .into_iter()
+            .filter(|order_id| {
+                self.working.get(order_id).is_none_or(|working| working.updates.is_none())
+            })
+            .collect();
+        if resumed.is_empty() {
+            return;
+        }
+        let open_orders = match self.client.all_open_orders() {
+            Ok(open_orders) => open_orders,
+            Err(error) => {
+                self.connection_lost |= is_connection_error(&error);
+                eprintln!("Failed to resume the open orders: {}", error);
+                return;
+            }
+        };
+        let mut open = HashSet::new();
+        for update in &open_orders {
+            match update {
+                Orders::OrderData(order_data) if resumed.contains(&order_data.order_id) => {
+                    open.insert(order_data.order_id);
+                    let status = OrderStatus::from_ib(&order_data.order_state.status);
+                    self.book.update(order_data.order_id, |record| record.status = status);
+                    self.working.entry(order_data.order_id).or_insert(WorkingOrder {
+                        contract: order_data.contract,
+                        ib_order: order_data.order,
+                        updates: None,
+                    });
+                }
+                Orders::OrderStatus(status) if resumed.contains(&status.order_id) => {
+                    self.book.record_status(
+                        status.order_id,
+                        OrderStatus::from_ib(&status.status),
+                        status.filled,
+                        status.remaining,
+                        status.average_fill_price,
+                    )
+                }
+                _ => {}
+            }
+        }
+
+        let closed: HashSet<i32> = resumed.difference(&open).copied().collect();
+        if closed.is_empty() {
+            return;
+        }
+        match self.client.executions(ExecutionFilter::default()) {
+            Ok(executions) => {
+                for execution in &executions {
+                    match execution {
+                        Executions::ExecutionData(execution_data)
+                            if closed.contains(&execution_data.execution.order_id) =>
+                        {
+                            let execution = execution_data.execution;
+                            self.book.record_fill(execution.order_id, fill(execution));
+                        }
+                        Executions::CommissionReport(report) => {
+                            self.book.record_commission(&report.execution_id, report.commission)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Err(error) => {
+                self.connection_lost |= is_connection_error(&error);
+                eprintln!("Failed to get the executions of the resumed orders: {}", error);
+                return;
+            }
+        }
+        for order_id in closed {
+            self.working.remove(&order_id);
+            self.book.record_closed(order_id);
+        }
+    }
+
+    /// Applies the pending updates and stops following orders that are done.
+    fn poll(&mut self) {
+        for (order_id, working) in &self.working {
+            let Some(updates) = &working.updates else {
+                continue;
+            };
+            while let Some(update) = updates.try_next() {
+                track(self.book, *order_id, update);
+            }
+        }
+        let resumed_working = self.working.values().any(|working| working.updates.is_none());
+        if resumed_working && self.resumed_at.elapsed() >= RESUME_INTERVAL {
+            self.resume();
+        }
+        let book = self.book;
+        self.working.retain(|order_id, _| {
+            book.get(*order_id).is_some_and(|record| !record.status.is_done())
+        });
+    }
+}
+
+/// The record for one of the IB orders built for `order`. The take profit and
+/// stop loss of a bracket are on the opposite side with their own price.
+fn ib_order_record(ib_order: &IbOrder, order: &Order) -> OrderRecord {
+    let mut record = OrderRecord::new(ib_order.order_id, order);
+    if ib_order.parent_id != 0 {
+        record.parent_id = Some(ib_order.parent_id);
+        record.order_type = match order.order_type {
+            OrderType::Buy => OrderType::Sell,
+            OrderType::Sell => OrderType::Buy,
+        };
+        record.style = match (ib_order.limit_price, ib_order.aux_price) {
+            (Some(limit_price), _) => OrderStyle::Limit { limit_price },
+            (None, Some(stop_price)) => OrderStyle::Stop { stop_price },
+            (None, None) => OrderStyle::Market,
+        };
+    }
+    record
+}
+
+pub fn track(book: &OrderBook, order_id: i32, update: PlaceOrder) {
+    match update {
+        PlaceOrder::OrderStatus(status) => book.record_status(
+            status.order_id,
+            OrderStatus::from_ib(&status.status),
+            status.filled,
+            status.remaining,
+            status.average_fill_price,
+        ),
+        PlaceOrder::ExecutionData(execution_data) => {
+            let execution = execution_data.execution;
+            book.record_fill(execution.order_id, fill(execution))
+        }
+        PlaceOrder::CommissionReport(report) => {
+            book.record_commission(&report.execution_id, report.commission)
+        }
+        PlaceOrder::Message(notice) => book.record_message(order_id, notice.to_string()),
+        PlaceOrder::OpenOrder(_) => {}
+    }
+}
+
+fn fill(execution: Execution) -> Fill {
+    Fill {
+        execution_id: execution.execution_id,
+        time: execution.time,
+        quantity: execution.shares,
+        price: execution.price,
+        commission: None,
+    }
+}
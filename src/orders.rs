@@ -0,0 +1,315 @@
+use crate::errors::AppErrors;
+use crate::journal::TradeJournal;
+use crate::models::{Order, OrderStyle, OrderType, TimeInForce};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Where an order is in its lifecycle, following the IB order status names.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    PendingSubmit,
+    PreSubmitted,
+    Submitted,
+    PendingCancel,
+    Cancelled,
+    Filled,
+    Inactive,
+    /// Loaded from the journal before IB reported on the order again.
+    Unknown,
+}
+
+impl OrderStatus {
+    /// Maps the IB status. `ApiPending` counts as pending and `ApiCancelled` as
+    /// cancelled, unknown statuses as submitted.
+    pub fn from_ib(status: &str) -> Self {
+        match status {
+            "ApiPending" | "PendingSubmit" => OrderStatus::PendingSubmit,
+            "PreSubmitted" => OrderStatus::PreSubmitted,
+            "PendingCancel" => OrderStatus::PendingCancel,
+            "ApiCancelled" | "Cancelled" => OrderStatus::Cancelled,
+            "Filled" => OrderStatus::Filled,
+            "Inactive" => OrderStatus::Inactive,
+            _ => OrderStatus::Submitted,
+        }
+    }
+
+    /// Filled, cancelled and inactive orders won't change anymore.
+    pub fn is_done(&self) -> bool {
+        matches!(self, OrderStatus::Cancelled | OrderStatus::Filled | OrderStatus::Inactive)
+    }
+}
+
+/// One execution of an order. The commission arrives in a separate report.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Fill {
+    pub execution_id: String,
+    pub time: String,
+    pub quantity: f64,
+    pub price: f64,
+    pub commission: Option<f64>,
+}
+
+/// The broker's view of a placed order, updated as status, execution and
+/// commission reports come in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderRecord {
+    pub order_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<i32>,
+    pub ticker: String,
+    pub order_type: OrderType,
+    pub quantity: f64,
+    pub style: OrderStyle,
+    pub time_in_force: TimeInForce,
+    pub status: OrderStatus,
+    pub filled_quantity: f64,
+    pub remaining_quantity: f64,
+    pub average_fill_price: Option<f64>,
+    pub commission: f64,
+    pub fills: Vec<Fill>,
+    pub messages: Vec<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl OrderRecord {
+    pub fn new(order_id: i32, order: &Order) -> Self {
+        OrderRecord {
+            order_id,
+            parent_id: None,
+            ticker: order.stock.to_string(),
+            order_type: order.order_type.clone(),
+            quantity: order.stock_quantity,
+            style: order.style,
+            time_in_force: order.time_in_force,
+            status: OrderStatus::PendingSubmit,
+            filled_quantity: 0.0,
+            remaining_quantity: order.stock_quantity,
+            average_fill_price: None,
+            commission: 0.0,
+            fills: vec![],
+            messages: vec![],
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// Status, quantities and average price of the reported fills. Used by
+    /// brokers that fill at once.
+    pub fn filled(mut self, fill: Fill) -> Self {
+        self.status = OrderStatus::Filled;
+        self.filled_quantity = fill.quantity;
+        self.remaining_quantity = self.quantity - fill.quantity;
+        self.average_fill_price = Some(fill.price);
+        self.commission = fill.commission.unwrap_or(0.0);
+        self.fills.push(fill);
+        self
+    }
+
+    /// A copy with the modification applied. Prices can only change on styles
+    /// that have them and the quantity can't drop below what is already filled.
+    pub fn modified(&self, modification: OrderModification) -> Result<OrderRecord, String> {
+        let mut modified = self.clone();
+        if let Some(quantity) = modification.quantity {
+            if quantity <= 0.0 || quantity < self.filled_quantity {
+                return Err(format!(
+                    "The quantity {quantity} has to be positive and at least the filled {}",
+                    self.filled_quantity
+                ));
+            }
+            modified.quantity = quantity;
+            modified.remaining_quantity = quantity - self.filled_quantity;
+        }
+        for price in [modification.limit_price, modification.stop_price].into_iter().flatten() {
+            if price <= 0.0 {
+                return Err(format!("The price {price} has to be positive"));
+            }
+        }
+        modified.style = match (self.style, modification.limit_price, modification.stop_price) {
+            (style, None, None) => style,
+            (OrderStyle::Limit { .. }, Some(limit_price), None) => {
+                OrderStyle::Limit { limit_price }
+            }
+            (OrderStyle::Stop { .. }, None, Some(stop_price)) => OrderStyle::Stop { stop_price },
+            (OrderStyle::StopLimit { stop_price, limit_price }, new_limit, new_stop) => {
+                OrderStyle::StopLimit {
+                    stop_price: new_stop.unwrap_or(stop_price),
+                    limit_price: new_limit.unwrap_or(limit_price),
+                }
+            }
+            (
+                OrderStyle::Bracket { take_profit_price, stop_loss_price, .. },
+                Some(limit_price),
+                None,
+            ) => OrderStyle::Bracket { limit_price, take_profit_price, stop_loss_price },
+            (style, _, _) => {
+                return Err(format!("The prices of a {:?} order can't be changed like that", style))
+            }
+        };
+        Ok(modified)
+    }
+}
+
+/// A change to a working order. Fields left out keep their value.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct OrderModification {
+    pub quantity: Option<f64>,
+    pub limit_price: Option<f64>,
+    pub stop_price: Option<f64>,
+}
+
+/// Every order placed, by IB order id. Readers can wait for an order to be
+/// done while the broker connection updates it. With a trade journal every
+/// change is saved and the orders of earlier runs are loaded back, the ones
+/// that weren't done as `Unknown` until IB reports on them.
+#[derive(Default)]
+pub struct OrderBook {
+    records: Mutex<BTreeMap<i32, OrderRecord>>,
+    changed: Condvar,
+    journal: Option<Arc<TradeJournal>>,
+}
+
+impl OrderBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_journal(journal: Arc<TradeJournal>) -> Result<Self, AppErrors> {
+        let records = journal
+            .orders()?
+            .into_iter()
+            .map(|mut record| {
+                if !record.status.is_done() {
+                    record.status = OrderStatus::Unknown;
+                }
+                (record.order_id, record)
+            })
+            .collect();
+        Ok(OrderBook { records: Mutex::new(records), journal: Some(journal), ..Self::default() })
+    }
+
+    pub fn insert(&self, record: OrderRecord) {
+        self.save(&record);
+        self.records.lock().unwrap().insert(record.order_id, record);
+        self.changed.notify_all();
+    }
+
+    pub fn get(&self, order_id: i32) -> Option<OrderRecord> {
+        self.records.lock().unwrap().get(&order_id).cloned()
+    }
+
+    /// The ids of the orders that aren't done yet.
+    pub fn unfinished(&self) -> Vec<i32> {
+        let records = self.records.lock().unwrap();
+        records
+            .values()
+            .filter(|record| !record.status.is_done())
+            .map(|record| record.order_id)
+            .collect()
+    }
+
+    /// Applies `update` to the order, ignoring unknown order ids.
+    pub fn update(&self, order_id: i32, update: impl FnOnce(&mut OrderRecord)) {
+        if let Some(record) = self.records.lock().unwrap().get_mut(&order_id) {
+            update(record);
+            record.updated_at = Utc::now();
+            self.save(record);
+        }
+        self.changed.notify_all();
+    }
+
+    /// The broker already has the order, so a journal that can't be written is
+    /// only logged.
+    fn save(&self, record: &OrderRecord) {
+        if let Some(journal) = &self.journal {
+            if let Err(error) = journal.record_order(record) {
+                eprintln!("Failed to record order {} in the journal: {}", record.order_id, error);
+            }
+        }
+    }
+
+    pub fn record_status(
+        &self,
+        order_id: i32,
+        status: OrderStatus,
+        filled_quantity: f64,
+        remaining_quantity: f64,
+        average_fill_price: f64,
+    ) {
+        self.update(order_id, |record| {
+            record.status = status;
+            record.filled_quantity = filled_quantity;
+            record.remaining_quantity = remaining_quantity;
+            if filled_quantity > 0.0 {
+                record.average_fill_price = Some(average_fill_price);
+            }
+        });
+    }
+
+    /// Adds an execution. IB can report the same execution twice, for the
+    /// order stream and for the executions request, so duplicates are skipped.
+    pub fn record_fill(&self, order_id: i32, fill: Fill) {
+        self.update(order_id, |record| {
+            if record.fills.iter().all(|known| known.execution_id != fill.execution_id) {
+                record.fills.push(fill);
+            }
+        });
+    }
+
+    /// Commission reports only name the execution, not the order.
+    pub fn record_commission(&self, execution_id: &str, commission: f64) {
+        let order_id = self.records.lock().unwrap().values().find_map(|record| {
+            record
+                .fills
+                .iter()
+                .any(|fill| fill.execution_id == execution_id)
+                .then_some(record.order_id)
+        });
+        if let Some(order_id) = order_id {
+            self.update(order_id, |record| {
+                for fill in &mut record.fills {
+                    if fill.execution_id == execution_id {
+                        fill.commission = Some(commission);
+                    }
+                }
+                record.commission = record.fills.iter().filter_map(|fill| fill.commission).sum();
+            });
+        }
+    }
+
+    /// Closes an order IB doesn't list as open anymore. It is filled when its
+    /// fills add up to the quantity and was cancelled otherwise.
+    pub fn record_closed(&self, order_id: i32) {
+        self.update(order_id, |record| {
+            let filled_quantity: f64 = record.fills.iter().map(|fill| fill.quantity).sum();
+            record.filled_quantity = filled_quantity.max(record.filled_quantity);
+            record.remaining_quantity = (record.quantity - record.filled_quantity).max(0.0);
+            record.status = if record.remaining_quantity == 0.0 {
+                OrderStatus::Filled
+            } else {
+                OrderStatus::Cancelled
+            };
+        });
+    }
+
+    pub fn record_message(&self, order_id: i32, message: String) {
+        self.update(order_id, |record| record.messages.push(message));
+    }
+
+    /// Waits up to `timeout` for the order to be done and returns its latest
+    /// state either way.
+    pub fn wait_until_done(&self, order_id: i32, timeout: Duration) -> Option<OrderRecord> {
+        let deadline = Instant::now() + timeout;
+        let mut records = self.records.lock().unwrap();
+        loop {
+            let record = records.get(&order_id)?;
+            let now = Instant::now();
+            if record.status.is_done() || now >= deadline {
+                return Some(record.clone());
+            }
+            records = self.changed.wait_timeout(records, deadline - now).unwrap().0;
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use crate::models::{Instrument, Order, OrderStyle, OrderType, TimeInForce};
+use crate::orders::{Fill, OrderRecord};
+
+fn limit_order(quantity: f64, limit_price: f64) -> Order {
+    Order {
+        stock_quantity: quantity,
+        stock: Instrument::stock("AAPL"),
+        order_type: OrderType::Buy,
+        style: OrderStyle::Limit { limit_price },
+        time_in_force: TimeInForce::Gtc,
+    }
+}
+
+fn fill(execution_id: &str, quantity: f64, price: f64) -> Fill {
+    Fill {
+        execution_id: execution_id.to_string(),
+        time: "20250204 15:30:00".to_string(),
+        quantity,
+        price,
+        commission: None,
+    }
+}
+
+mod order_status {
+    use crate::orders::OrderStatus;
+
+    #[test]
+    fn test_from_ib() {
+        assert_eq!(OrderStatus::from_ib("ApiPending"), OrderStatus::PendingSubmit);
+        assert_eq!(OrderStatus::from_ib("PreSubmitted"), OrderStatus::PreSubmitted);
+        assert_eq!(OrderStatus::from_ib("ApiCancelled"), OrderStatus::Cancelled);
+        assert_eq!(OrderStatus::from_ib("Filled"), OrderStatus::Filled);
+        assert_eq!(OrderStatus::from_ib("Submitted"), OrderStatus::Submitted)
+    }
+
+    #[test]
+    fn test_is_done() {
+        assert!(OrderStatus::Filled.is_done());
+        assert!(OrderStatus::Inactive.is_done());
+        assert!(!OrderStatus::PendingCancel.is_done())
+    }
+}
+
+mod order_book {
+    use super::{fill, limit_order, OrderRecord};
+    use crate::journal::TradeJournal;
+    use crate::orders::{OrderBook, OrderStatus};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_record_status_and_fills() {
+        let book = OrderBook::new();
+        book.insert(OrderRecord::new(5, &limit_order(10.0, 230.0)));
+        book.record_status(5, OrderStatus::Submitted, 4.0, 6.0, 229.5);
+        book.record_fill(5, fill("0001.01", 4.0, 229.5));
+        book.record_fill(5, fill("0001.01", 4.0, 229.5));
+        book.record_commission("0001.01", 1.25);
+        let record = book.get(5).unwrap();
+        assert_eq!(record.status, OrderStatus::Submitted);
+        assert_eq!(record.remaining_quantity, 6.0);
+        assert_eq!(record.average_fill_price, Some(229.5));
+        assert_eq!(record.fills.len(), 1);
+        assert_eq!(record.commission, 1.25)
+    }
+
+    #[test]
+    fn test_updates_for_unknown_orders_are_ignored() {
+        let book = OrderBook::new();
+        book.record_status(5, OrderStatus::Filled, 1.0, 0.0, 100.0);
+        book.record_commission("0001.01", 1.0);
+        assert!(book.get(5).is_none())
+    }
+
+    #[test]
+    fn test_wait_until_done() {
+        let book = Arc::new(OrderBook::new());
+        book.insert(OrderRecord::new(5, &limit_order(10.0, 230.0)));
+        let updater = {
+            let book = book.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                book.record_status(5, OrderStatus::Filled, 10.0, 0.0, 229.9);
+            })
+        };
+        let record = book.wait_until_done(5, Duration::from_secs(5)).unwrap();
+        updater.join().unwrap();
+        assert_eq!(record.status, OrderStatus::Filled);
+        assert_eq!(record.average_fill_price, Some(229.9))
+    }
+
+    #[test]
+    fn test_wait_until_done_times_out_with_the_working_order() {
+        let book = OrderBook::new();
+        book.insert(OrderRecord::new(5, &limit_order(10.0, 230.0)));
+        let record = book.wait_until_done(5, Duration::from_millis(10)).unwrap();
+        assert_eq!(record.status, OrderStatus::PendingSubmit);
+        assert!(book.wait_until_done(6, Duration::from_millis(10)).is_none())
+    }
+
+    #[test]
+    fn test_orders_are_kept_in_the_journal_and_loaded_back() {
+        let journal = Arc::new(TradeJournal::open_in_memory().unwrap());
+        let book = OrderBook::with_journal(journal.clone()).unwrap();
+        book.insert(OrderRecord::new(5, &limit_order(10.0, 230.0)));
+        book.record_status(5, OrderStatus::Filled, 10.0, 0.0, 229.9);
+        book.record_fill(5, fill("0001.01", 10.0, 229.9));
+        let reloaded = OrderBook::with_journal(journal).unwrap();
+        assert_eq!(reloaded.get(5), book.get(5))
+    }
+
+    #[test]
+    fn test_unfinished_orders_are_loaded_back_as_unknown() {
+        let journal = Arc::new(TradeJournal::open_in_memory().unwrap());
+        let book = OrderBook::with_journal(journal.clone()).unwrap();
+        book.insert(OrderRecord::new(5, &limit_order(10.0, 230.0)));
+        book.insert(OrderRecord::new(6, &limit_order(10.0, 230.0)));
+        book.record_status(5, OrderStatus::Submitted, 4.0, 6.0, 229.5);
+        book.record_status(6, OrderStatus::Cancelled, 0.0, 10.0, 0.0);
+        let reloaded = OrderBook::with_journal(journal).unwrap();
+        assert_eq!(reloaded.get(5).unwrap().status, OrderStatus::Unknown);
+        assert_eq!(reloaded.get(5).unwrap().filled_quantity, 4.0);
+        assert_eq!(reloaded.get(6).unwrap().status, OrderStatus::Cancelled);
+        assert_eq!(reloaded.unfinished(), vec![5])
+    }
+
+    #[test]
+    fn test_record_closed_is_filled_when_the_fills_cover_the_quantity() {
+        let book = OrderBook::new();
+        book.insert(OrderRecord::new(5, &limit_order(10.0, 230.0)));
+        book.record_fill(5, fill("0001.01", 4.0, 229.5));
+        book.record_fill(5, fill("0001.02", 6.0, 229.8));
+        book.record_closed(5);
+        let record = book.get(5).unwrap();
+        assert_eq!(record.status, OrderStatus::Filled);
+        assert_eq!(record.remaining_quantity, 0.0);
+        assert!(book.unfinished().is_empty())
+    }
+
+    #[test]
+    fn test_record_closed_is_cancelled_with_a_remaining_quantity() {
+        let book = OrderBook::new();
+        book.insert(OrderRecord::new(5, &limit_order(10.0, 230.0)));
+        book.record_fill(5, fill("0001.01", 4.0, 229.5));
+        book.record_closed(5);
+        let record = book.get(5).unwrap();
+        assert_eq!(record.status, OrderStatus::Cancelled);
+        assert_eq!((record.filled_quantity, record.remaining_quantity), (4.0, 6.0))
+    }
+}
+
+mod order_modification {
+    use super::{fill, limit_order, OrderRecord};
+    use crate::models::OrderStyle;
+    use crate::orders::{OrderModification, OrderStatus};
+
+    #[test]
+    fn test_modified_limit_price_and_quantity() {
+        let record = OrderRecord::new(5, &limit_order(10.0, 230.0));
+        let modified = record
+            .modified(OrderModification {
+                quantity: Some(12.0),
+                limit_price: Some(228.0),
+                stop_price: None,
+            })
+            .unwrap();
+        assert_eq!(modified.quantity, 12.0);
+        assert_eq!(modified.remaining_quantity, 12.0);
+        assert_eq!(modified.style, OrderStyle::Limit { limit_price: 228.0 })
+    }
+
+    #[test]
+    fn test_modified_rejects_a_stop_price_on_a_limit_order() {
+        let record = OrderRecord::new(5, &limit_order(10.0, 230.0));
+        let modification = OrderModification { stop_price: Some(220.0), ..Default::default() };
+        assert!(record.modified(modification).is_err())
+    }
+
+    #[test]
+    fn test_modified_quantity_below_filled() {
+        let record = OrderRecord::new(5, &limit_order(10.0, 230.0));
+        let mut partially_filled = record.filled(fill("0001.01", 4.0, 229.5));
+        partially_filled.status = OrderStatus::Submitted;
+        let modification = OrderModification { quantity: Some(3.0), ..Default::default() };
+        assert!(partially_filled.modified(modification).is_err())
+    }
+}
@@ -0,0 +1,404 @@
+use crate::errors::AppErrors;
+use crate::models::{
+    Currency, ExchangeRate, Instrument, Money, Order, OrderStyle, OrderType, PaperTradingConfig,
+    Portfolio, PriceHistory, StockData, StockInvestment,
+};
+use crate::orders::{Fill, OrderBook, OrderModification, OrderRecord};
+use crate::services::TradingApiService;
+use crate::sizing::LotSize;
+use async_trait::async_trait;
+use chrono::Utc;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// A paper position. The average cost is per contract, the price times the
+/// multiplier for derivatives.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaperPosition {
+    pub quantity: Decimal,
+    pub average_cost: Money,
+}
+
+/// Amounts are exact decimals, so what the fills add up to is what the
+/// account holds; floats only appear on the way out.
+#[derive(Debug)]
+struct PaperAccount {
+    cash: Money,
+    positions: HashMap<Instrument, PaperPosition>,
+    latest_prices: HashMap<Instrument, Decimal>,
+    realized_pnl: HashMap<Instrument, Money>,
+    next_order_id: i32,
+}
+
+/// Simulated broker that keeps cash and positions in memory. Market data and
+/// exchange rates are still fetched through the wrapped service; orders fill at
+/// the latest bar. Cash is kept in the configured base currency, positions and
+/// their costs in the currency the stock trades in.
+pub struct PaperTradingService {
+    market_data: Arc<dyn TradingApiService + Send + Sync>,
+    config: PaperTradingConfig,
+    account: Mutex<PaperAccount>,
+    orders: OrderBook,
+}
+
+impl PaperTradingService {
+    pub fn new(
+        market_data: Arc<dyn TradingApiService + Send + Sync>,
+        config: PaperTradingConfig,
+    ) -> Self {
+        PaperTradingService {
+            market_data,
+            account: Mutex::new(PaperAccount {
+                cash: Money::from_decimal(config.starting_cash, config.base_currency),
+                positions: HashMap::new(),
+                latest_prices: HashMap::new(),
+                realized_pnl: HashMap::new(),
+                next_order_id: 1,
+            }),
+            orders: OrderBook::new(),
+            config,
+        }
+    }
+
+    pub fn cash(&self) -> Money {
+        self.account.lock().unwrap().cash
+    }
+
+    pub fn position(&self, stock: &Instrument) -> Option<PaperPosition> {
+        self.account.lock().unwrap().positions.get(stock).cloned()
+    }
+
+    pub fn update_market_data(&self, stock_data: &StockData) -> Result<Decimal, AppErrors> {
+        let latest_price = stock_data.current_price()?;
+        self.account.lock().unwrap().latest_prices.insert(stock_data.stock.clone(), latest_price);
+        Ok(latest_price)
+    }
+
+    /// The commission in the base currency, rounded to its minor unit.
+    pub fn commission(&self, quantity: Decimal) -> Result<Money, AppErrors> {
+        let base_currency = self.config.base_currency;
+        let commission = Money::from_decimal(self.config.commission_per_share, base_currency)
+            .checked_mul(quantity)?
+            .round();
+        if commission.amount < self.config.minimum_commission {
+            return Ok(Money::from_decimal(self.config.minimum_commission, base_currency));
+        }
+        Ok(commission)
+    }
+
+    /// The rate from `from` to `to`, only asked for when they differ.
+    async fn exchange_rate(&self, from: Currency, to: Currency) -> Result<ExchangeRate, AppErrors> {
+        if from == to {
+            return Ok(ExchangeRate::identity(from));
+        }
+        self.market_data.get_exchange_rate(from, to).await
+    }
+
+    fn latest_price(account: &PaperAccount, stock: &Instrument) -> Option<Decimal> {
+        account.latest_prices.get(stock).copied()
+    }
+
+    /// Crypto and forex trade in fractions, stocks and ETFs when fractional
+    /// shares are on, options and futures in whole contracts.
+    fn lot_size(&self, stock: &Instrument) -> LotSize {
+        match stock {
+            Instrument::Crypto { .. } | Instrument::Forex { .. } => LotSize::FRACTIONAL,
+            Instrument::Stock { .. } | Instrument::Etf { .. } if self.config.fractional_shares => {
+                LotSize::FRACTIONAL
+            }
+            _ => LotSize::WHOLE_SHARES,
+        }
+    }
+
+    /// What one contract is worth at the price.
+    fn contract_value(stock: &Instrument, price: Decimal) -> Result<Money, AppErrors> {
+        Money::from_decimal(price, stock.currency()).checked_mul(stock.multiplier())
+    }
+
+    /// The position valued at the latest price. The realized profit or loss
+    /// counts every sale of the instrument since the server started.
+    fn investment(
+        account: &PaperAccount,
+        stock: Instrument,
+        position: &PaperPosition,
+    ) -> Result<StockInvestment, AppErrors> {
+        let quantity = position.quantity;
+        let invested_amount = position.average_cost.checked_mul(quantity)?.round();
+        let market_value = match Self::latest_price(account, &stock) {
+            Some(price) => {
+                Some(Self::contract_value(&stock, price)?.checked_mul(quantity)?.round())
+            }
+            None => None,
+        };
+        let realized_pnl = account
+            .realized_pnl
+            .get(&stock)
+            .copied()
+            .unwrap_or_else(|| Money::zero(stock.currency()));
+        Ok(StockInvestment {
+            stock_name: stock.to_string(),
+            current_invested_amount: invested_amount,
+            quantity: quantity.to_f64().unwrap_or_default(),
+            average_cost: position.average_cost,
+            market_value,
+            unrealized_pnl: market_value
+                .map(|market_value| market_value.checked_sub(invested_amount))
+                .transpose()?,
+            realized_pnl: Some(realized_pnl.round()),
+            stock,
+        })
+    }
+
+    /// Paper orders are filled when they are placed, so there is nothing left
+    /// to cancel or modify.
+    fn filled_order_error(&self, order_id: i32, error: fn(String) -> AppErrors) -> AppErrors {
+        match self.orders.get(order_id) {
+            Some(_) => error(format!("Paper order {order_id} is already filled")),
+            None => error(format!("There is no paper order with id {order_id}")),
+        }
+    }
+}
+
+/// Paper orders fill at once or not at all. Market orders and limit orders the
+/// latest price already satisfies fill at that price; nothing rests on a book.
+fn fill_price(order: &Order, latest_price: Decimal) -> Result<Decimal, AppErrors> {
+    match order.style {
+        OrderStyle::Market => Ok(latest_price),
+        OrderStyle::Limit { limit_price } => {
+            let limit_price = Decimal::from_f64(limit_price).ok_or_else(|| {
+                AppErrors::PlaceOrderError(format!("Invalid limit price: {limit_price}"))
+            })?;
+            let is_marketable = match order.order_type {
+                OrderType::Buy => latest_price <= limit_price,
+                OrderType::Sell => latest_price >= limit_price,
+            };
+            if is_marketable {
+                Ok(latest_price)
+            } else {
+                Err(AppErrors::PlaceOrderError(format!(
+                    "The paper limit order at {limit_price:.2} doesn't fill at the latest price {latest_price:.2}"
+                )))
+            }
+        }
+        style => Err(AppErrors::PlaceOrderError(format!(
+            "Paper trading only simulates market and limit orders. Order style provided: {style:?}"
+        ))),
+    }
+}
+
+#[async_trait]
+impl TradingApiService for PaperTradingService {
+    async fn get_stock_data(
+        &self,
+        stock: Instrument,
+        price_history: PriceHistory,
+    ) -> Result<StockData, AppErrors> {
+        let stock_data = self.market_data.get_stock_data(stock, price_history).await?;
+        self.update_market_data(&stock_data)?;
+        Ok(stock_data)
+    }
+
+    async fn place_order(&self, order: Order) -> Result<OrderRecord, AppErrors> {
+        // Rounded like the live broker rounds it, so a paper order fails or
+        // fills the same quantity Interactive Brokers would take.
+        let stock_quantity = self.lot_size(&order.stock).normalize(order.stock_quantity)?;
+        let order = Order { stock_quantity, ..order };
+        let quantity = Decimal::from_f64(stock_quantity).ok_or_else(|| {
+            AppErrors::PlaceOrderError(format!("Invalid order quantity: {stock_quantity}"))
+        })?;
+
+        // The cash moves in the base currency, the position is kept in the
+        // stock's currency. The commission is charged in the base currency.
+        let exchange_rate =
+            self.exchange_rate(order.stock.currency(), self.config.base_currency).await?;
+        if exchange_rate.rate <= Decimal::ZERO {
+            return Err(AppErrors::PlaceOrderError(format!(
+                "Cannot fill the paper order at an exchange rate of {} from {} to {}",
+                exchange_rate.rate, exchange_rate.from, exchange_rate.to
+            )));
+        }
+
+        let mut account = self.account.lock().unwrap();
+        let latest_price = Self::latest_price(&account, &order.stock).ok_or_else(|| {
+            AppErrors::PlaceOrderError(format!(
+                "No market data to fill the paper order for: {}",
+                order.stock
+            ))
+        })?;
+        let fill_price = fill_price(&order, latest_price)?;
+        let commission = self.commission(quantity)?;
+        let notional = Self::contract_value(&order.stock, fill_price)?.checked_mul(quantity)?;
+        let cash_notional = exchange_rate.convert(notional)?;
+        let commission_in_stock_currency =
+            Money::from_decimal(commission.amount, notional.currency)
+                .checked_div(exchange_rate.rate)?;
+
+        match order.order_type {
+            OrderType::Buy => {
+                let cost = cash_notional.checked_add(commission)?;
+                if account.cash.amount < cost.amount {
+                    return Err(AppErrors::PlaceOrderError(format!(
+                        "Insufficient paper trading cash. Needed: {}, available: {}",
+                        cost,
+                        account.cash.round()
+                    )));
+                }
+                let position =
+                    account.positions.get(&order.stock).cloned().unwrap_or(PaperPosition {
+                        quantity: Decimal::ZERO,
+                        average_cost: Money::zero(notional.currency),
+                    });
+                let total_cost = position
+                    .average_cost
+                    .checked_mul(position.quantity)?
+                    .checked_add(notional)?
+                    .checked_add(commission_in_stock_currency)?;
+                let quantity = position.quantity + quantity;
+                let position =
+                    PaperPosition { quantity, average_cost: total_cost.checked_div(quantity)? };
+                account.cash = account.cash.checked_sub(cost)?;
+                account.positions.insert(order.stock.clone(), position);
+            }
+            OrderType::Sell => {
+                let held_quantity = account
+                    .positions
+                    .get(&order.stock)
+                    .map(|position| position.quantity)
+                    .unwrap_or(Decimal::ZERO);
+                if held_quantity < quantity {
+                    return Err(AppErrors::PlaceOrderError(format!(
+                        "Insufficient paper trading position for: {}. Held: {}, requested: {}",
+                        order.stock, held_quantity, quantity
+                    )));
+                }
+                let average_cost = account.positions[&order.stock].average_cost;
+                let realized_pnl = notional
+                    .checked_sub(commission_in_stock_currency)?
+                    .checked_sub(average_cost.checked_mul(quantity)?)?;
+                let realized_pnl = match account.realized_pnl.get(&order.stock) {
+                    Some(earlier) => earlier.checked_add(realized_pnl)?,
+                    None => realized_pnl,
+                };
+                account.cash = account.cash.checked_add(cash_notional)?.checked_sub(commission)?;
+                account.realized_pnl.insert(order.stock.clone(), realized_pnl);
+                if held_quantity == quantity {
+                    account.positions.remove(&order.stock);
+                } else if let Some(position) = account.positions.get_mut(&order.stock) {
+                    position.quantity -= quantity;
+                }
+            }
+        }
+
+        let order_id = account.next_order_id;
+        account.next_order_id += 1;
+        let record = OrderRecord::new(order_id, &order).filled(Fill {
+            execution_id: format!("paper-{order_id}"),
+            time: Utc::now().to_rfc3339(),
+            quantity: order.stock_quantity,
+            price: fill_price.to_f64().unwrap_or_default(),
+            commission: Some(commission.to_f64()),
+        });
+        self.orders.insert(record.clone());
+        Ok(record)
+    }
+
+    async fn get_order(&self, order_id: i32) -> Option<OrderRecord> {
+        self.orders.get(order_id)
+    }
+
+    async fn cancel_order(&self, order_id: i32) -> Result<OrderRecord, AppErrors> {
+        Err(self.filled_order_error(order_id, AppErrors::CancelOrderError))
+    }
+
+    async fn modify_order(
+        &self,
+        order_id: i32,
+        _modification: OrderModification,
+    ) -> Result<OrderRecord, AppErrors> {
+        Err(self.filled_order_error(order_id, AppErrors::ModifyOrderError))
+    }
+
+    async fn convert_money_amount_to_stock_quantity(
+        &self,
+        amount: Money,
+        stock: Instrument,
+    ) -> Result<f64, AppErrors> {
+        if amount.currency != stock.currency() {
+            return Err(AppErrors::ConvertMoneyToStockQuantityError(format!(
+                "{stock} trades in {}, the amount is {amount}",
+                stock.currency()
+            )));
+        }
+        let account = self.account.lock().unwrap();
+        let latest_price = Self::latest_price(&account, &stock).ok_or_else(|| {
+            AppErrors::ConvertMoneyToStockQuantityError(format!(
+                "No market data to convert the amount for: {stock}"
+            ))
+        })?;
+        let contract_value = Self::contract_value(&stock, latest_price)?;
+        let quantity = amount.amount.checked_div(contract_value.amount).ok_or_else(|| {
+            AppErrors::ConvertMoneyToStockQuantityError(format!(
+                "Cannot convert {amount} at a contract value of {contract_value}"
+            ))
+        })?;
+        self.lot_size(&stock).normalize(quantity.to_f64().unwrap_or_default())
+    }
+
+    async fn get_exchange_rate(
+        &self,
+        from: Currency,
+        to: Currency,
+    ) -> Result<ExchangeRate, AppErrors> {
+        self.exchange_rate(from, to).await
+    }
+
+    async fn get_quantity_to_sell_everything(&self, stock: Instrument) -> Result<f64, AppErrors> {
+        let position = self.position(&stock).ok_or_else(|| {
+            AppErrors::GetQuantityToSellEverythingError(format!(
+                "No paper trading position for: {stock}"
+            ))
+        })?;
+        Ok(position.quantity.to_f64().unwrap_or_default())
+    }
+
+    async fn get_lot_size(&self, stock: Instrument) -> Result<LotSize, AppErrors> {
+        Ok(self.lot_size(&stock))
+    }
+
+    async fn get_current_investment(
+        &self,
+        stock: Instrument,
+    ) -> Result<StockInvestment, AppErrors> {
+        let account = self.account.lock().unwrap();
+        let position = account.positions.get(&stock).ok_or_else(|| {
+            AppErrors::GetCurrentInvestmentError(format!("No position found for: {stock}"))
+        })?;
+        Self::investment(&account, stock.clone(), position)
+    }
+
+    /// Cash plus the positions at their latest price, converted to the base
+    /// currency.
+    async fn get_portfolio(&self) -> Result<Portfolio, AppErrors> {
+        let base_currency = self.config.base_currency;
+        let (mut positions, cash) = {
+            let account = self.account.lock().unwrap();
+            let positions = account
+                .positions
+                .iter()
+                .map(|(stock, position)| Self::investment(&account, stock.clone(), position))
+                .collect::<Result<Vec<StockInvestment>, AppErrors>>()?;
+            (positions, account.cash.round())
+        };
+        positions.sort_by_key(|investment| investment.stock.to_string());
+        let mut net_liquidation_value = cash;
+        for investment in &positions {
+            let value = investment.market_value.unwrap_or(investment.current_invested_amount);
+            let exchange_rate = self.exchange_rate(value.currency, base_currency).await?;
+            net_liquidation_value =
+                net_liquidation_value.checked_add(exchange_rate.convert(value)?)?;
+        }
+        Ok(Portfolio { base_currency, positions, cash, net_liquidation_value })
+    }
+}
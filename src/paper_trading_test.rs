@@ -0,0 +1,398 @@
+use crate::models::{
+    Currency, Instrument, Money, Order, OrderStyle, OrderType, PaperTradingConfig, PriceHistory, StockData,
+    StockPricePerformance, TimeInForce,
+};
+use crate::paper_trading::PaperTradingService;
+use crate::services_mock::TradingApiServiceMock;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::sync::Arc;
+
+fn dollars(amount: f64) -> Money {
+    Money::from_f64(amount, Currency::USD).unwrap()
+}
+
+fn paper_trading_service() -> PaperTradingService {
+    paper_trading_service_with_fractional_shares(false)
+}
+
+fn paper_trading_service_with_fractional_shares(fractional_shares: bool) -> PaperTradingService {
+    PaperTradingService::new(
+        Arc::new(TradingApiServiceMock::new()),
+        PaperTradingConfig {
+            base_currency: Currency::USD,
+            starting_cash: Decimal::from(1000),
+            commission_per_share: Decimal::new(1, 2),
+            minimum_commission: Decimal::ONE,
+            fractional_shares,
+        },
+    )
+}
+
+fn stock() -> Instrument {
+    Instrument::stock("AAPL")
+}
+
+fn stock_data_with_price(price: Decimal) -> StockData {
+    StockData {
+        stock: stock(),
+        price_history: PriceHistory::default(),
+        stock_price_performance: vec![StockPricePerformance {
+            date: NaiveDate::from_ymd_opt(2025, 1, 31).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: 1_000,
+        }],
+        news: vec![],
+    }
+}
+
+fn order(order_type: OrderType, stock_quantity: f64) -> Order {
+    Order {
+        stock_quantity,
+        stock: stock(),
+        order_type,
+        style: OrderStyle::Market,
+        time_in_force: TimeInForce::Day,
+    }
+}
+
+mod paper_trading_service {
+    use super::{
+        dollars, order, paper_trading_service, paper_trading_service_with_fractional_shares, stock,
+        stock_data_with_price,
+    };
+    use crate::errors::AppErrors;
+    use rust_decimal::Decimal;
+    use crate::models::{
+        Currency, ExchangeRate, Instrument, Money, Order, OrderStyle, OrderType,
+        PaperTradingConfig, PriceHistory, StockData,
+    };
+    use crate::orders::{OrderModification, OrderStatus};
+    use crate::paper_trading::{PaperPosition, PaperTradingService};
+    use crate::services::TradingApiService;
+    use crate::services_mock::{TradingApiCall, TradingApiServiceMock};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_place_order_buy_fills_at_latest_price() {
+        let service = paper_trading_service();
+        service.update_market_data(&stock_data_with_price(Decimal::from(100))).unwrap();
+        let record = service.place_order(order(OrderType::Buy, 5.0)).await.unwrap();
+        assert_eq!(record.status, OrderStatus::Filled);
+        assert_eq!(record.average_fill_price, Some(100.0));
+        assert_eq!(record.commission, 1.0);
+        assert_eq!(service.cash(), dollars(1000.0 - 500.0 - 1.0));
+        assert_eq!(
+            service.position(&stock()),
+            Some(PaperPosition { quantity: Decimal::from(5), average_cost: dollars(100.2) })
+        )
+    }
+
+    #[tokio::test]
+    async fn test_place_order_sell_everything_closes_position() {
+        let service = paper_trading_service();
+        service.update_market_data(&stock_data_with_price(Decimal::from(100))).unwrap();
+        service.place_order(order(OrderType::Buy, 5.0)).await.unwrap();
+        service.update_market_data(&stock_data_with_price(Decimal::from(120))).unwrap();
+        let quantity = service.get_quantity_to_sell_everything(stock()).await.unwrap();
+        let maybe_fill = service.place_order(order(OrderType::Sell, quantity)).await;
+        assert!(maybe_fill.is_ok());
+        assert_eq!(service.cash(), dollars(1000.0 - 501.0 + 600.0 - 1.0));
+        assert!(service.position(&stock()).is_none())
+    }
+
+    #[tokio::test]
+    async fn test_place_order_sell_what_the_fractions_add_up_to_closes_position() {
+        let service = paper_trading_service_with_fractional_shares(true);
+        service.update_market_data(&stock_data_with_price(Decimal::from(100))).unwrap();
+        service.place_order(order(OrderType::Buy, 0.1)).await.unwrap();
+        service.place_order(order(OrderType::Buy, 0.2)).await.unwrap();
+        let maybe_fill = service.place_order(order(OrderType::Sell, 0.3)).await;
+        assert!(maybe_fill.is_ok());
+        assert!(service.position(&stock()).is_none())
+    }
+
+    #[tokio::test]
+    async fn test_place_order_rounds_down_to_whole_shares() {
+        let service = paper_trading_service();
+        service.update_market_data(&stock_data_with_price(Decimal::from(100))).unwrap();
+        let record = service.place_order(order(OrderType::Buy, 2.7)).await.unwrap();
+        assert_eq!(record.quantity, 2.0);
+        assert_eq!(service.position(&stock()).unwrap().quantity, Decimal::from(2))
+    }
+
+    #[tokio::test]
+    async fn test_place_order_below_one_share() {
+        let service = paper_trading_service();
+        service.update_market_data(&stock_data_with_price(Decimal::from(100))).unwrap();
+        let maybe_fill = service.place_order(order(OrderType::Buy, 0.5)).await;
+        assert!(matches!(maybe_fill, Err(AppErrors::QuantityBelowMinimumError(_))));
+        assert_eq!(service.cash(), dollars(1000.0))
+    }
+
+    #[tokio::test]
+    async fn test_place_order_without_market_data() {
+        let service = paper_trading_service();
+        let maybe_fill = service.place_order(order(OrderType::Buy, 1.0)).await;
+        assert!(maybe_fill.is_err())
+    }
+
+    #[tokio::test]
+    async fn test_place_order_insufficient_cash() {
+        let service = paper_trading_service();
+        service.update_market_data(&stock_data_with_price(Decimal::from(100))).unwrap();
+        let maybe_fill = service.place_order(order(OrderType::Buy, 10.0)).await;
+        assert!(maybe_fill.is_err());
+        assert_eq!(service.cash(), dollars(1000.0))
+    }
+
+    #[tokio::test]
+    async fn test_place_order_sell_more_than_held() {
+        let service = paper_trading_service();
+        service.update_market_data(&stock_data_with_price(Decimal::from(100))).unwrap();
+        service.place_order(order(OrderType::Buy, 2.0)).await.unwrap();
+        let maybe_fill = service.place_order(order(OrderType::Sell, 3.0)).await;
+        assert!(maybe_fill.is_err())
+    }
+
+    #[tokio::test]
+    async fn test_place_order_limit_fills_when_marketable() {
+        let service = paper_trading_service();
+        service.update_market_data(&stock_data_with_price(Decimal::from(100))).unwrap();
+        let buy_at = |limit_price| Order {
+            style: OrderStyle::Limit { limit_price },
+            ..order(OrderType::Buy, 1.0)
+        };
+        assert!(service.place_order(buy_at(99.0)).await.is_err());
+        assert!(service.place_order(buy_at(101.0)).await.is_ok());
+        assert_eq!(service.position(&stock()).unwrap().average_cost, dollars(101.0))
+    }
+
+    #[tokio::test]
+    async fn test_place_order_stop_is_not_simulated() {
+        let service = paper_trading_service();
+        service.update_market_data(&stock_data_with_price(Decimal::from(100))).unwrap();
+        let maybe_fill = service.place_order(Order {
+            style: OrderStyle::Stop { stop_price: 90.0 },
+            ..order(OrderType::Buy, 1.0)
+        }).await;
+        assert!(maybe_fill.is_err())
+    }
+
+    #[tokio::test]
+    async fn test_paper_orders_are_kept_but_cannot_be_cancelled() {
+        let service = paper_trading_service();
+        service.update_market_data(&stock_data_with_price(Decimal::from(100))).unwrap();
+        let first = service.place_order(order(OrderType::Buy, 1.0)).await.unwrap();
+        let second = service.place_order(order(OrderType::Buy, 1.0)).await.unwrap();
+        assert_ne!(first.order_id, second.order_id);
+        assert_eq!(service.get_order(first.order_id).await, Some(first.clone()));
+        assert!(service.cancel_order(first.order_id).await.is_err());
+        assert!(service.modify_order(first.order_id, OrderModification::default()).await.is_err())
+    }
+
+    #[test]
+    fn test_commission_uses_minimum() {
+        let service = paper_trading_service();
+        assert_eq!(service.commission(Decimal::from(10)).unwrap(), dollars(1.0));
+        assert_eq!(service.commission(Decimal::from(1000)).unwrap(), dollars(10.0))
+    }
+
+    #[tokio::test]
+    async fn test_convert_money_amount_to_stock_quantity_success() {
+        let service = paper_trading_service();
+        service.update_market_data(&stock_data_with_price(Decimal::from(30))).unwrap();
+        let maybe_quantity = service
+            .convert_money_amount_to_stock_quantity(dollars(100.0), stock()).await;
+        assert_eq!(maybe_quantity.unwrap(), 3.0)
+    }
+
+    #[tokio::test]
+    async fn test_convert_money_amount_to_stock_quantity_below_one_share() {
+        let service = paper_trading_service();
+        service.update_market_data(&stock_data_with_price(Decimal::from(300))).unwrap();
+        let maybe_quantity = service
+            .convert_money_amount_to_stock_quantity(dollars(100.0), stock()).await;
+        assert!(matches!(maybe_quantity, Err(AppErrors::QuantityBelowMinimumError(_))))
+    }
+
+    #[tokio::test]
+    async fn test_convert_money_amount_to_stock_quantity_with_fractional_shares() {
+        let service = paper_trading_service_with_fractional_shares(true);
+        service.update_market_data(&stock_data_with_price(Decimal::from(300))).unwrap();
+        let maybe_quantity =
+            service.convert_money_amount_to_stock_quantity(dollars(100.0), stock()).await;
+        assert_eq!(maybe_quantity.unwrap(), 0.3333)
+    }
+
+    #[tokio::test]
+    async fn test_convert_money_amount_to_crypto_quantity_is_fractional() {
+        let service = paper_trading_service();
+        let bitcoin = Instrument::Crypto {
+            symbol: "BTC".to_string(),
+            exchange: "PAXOS".to_string(),
+            currency: Currency::USD,
+        };
+        service
+            .update_market_data(&StockData {
+                stock: bitcoin.clone(),
+                ..stock_data_with_price(Decimal::from(60_000))
+            })
+            .unwrap();
+        let maybe_quantity =
+            service.convert_money_amount_to_stock_quantity(dollars(100.0), bitcoin).await;
+        assert_eq!(maybe_quantity.unwrap(), 0.0016)
+    }
+
+    #[tokio::test]
+    async fn test_convert_money_amount_to_stock_quantity_in_another_currency() {
+        let service = paper_trading_service();
+        service.update_market_data(&stock_data_with_price(Decimal::from(30))).unwrap();
+        let euros = Money::new(Decimal::from(100), "EUR".parse::<Currency>().unwrap()).unwrap();
+        let maybe_quantity = service.convert_money_amount_to_stock_quantity(euros, stock()).await;
+        assert!(maybe_quantity.is_err())
+    }
+
+    #[tokio::test]
+    async fn test_get_stock_data_updates_latest_price() {
+        let market_data = Arc::new(
+            TradingApiServiceMock::new().with_stock_data(Ok(stock_data_with_price(Decimal::from(40)))),
+        );
+        let service = PaperTradingService::new(
+            market_data.clone(),
+            PaperTradingConfig {
+                base_currency: Currency::USD,
+                starting_cash: Decimal::from(1000),
+                commission_per_share: Decimal::ZERO,
+                minimum_commission: Decimal::ZERO,
+                fractional_shares: false,
+            },
+        );
+        service.get_stock_data(stock(), PriceHistory::default()).await.unwrap();
+        service.place_order(order(OrderType::Buy, 10.0)).await.unwrap();
+        assert_eq!(service.cash(), dollars(600.0));
+        assert!(market_data.placed_orders().is_empty())
+    }
+
+    #[tokio::test]
+    async fn test_get_portfolio_values_positions_at_latest_price() {
+        let service = paper_trading_service();
+        service.update_market_data(&stock_data_with_price(Decimal::from(100))).unwrap();
+        service.place_order(order(OrderType::Buy, 5.0)).await.unwrap();
+        service.update_market_data(&stock_data_with_price(Decimal::from(110))).unwrap();
+        service.place_order(order(OrderType::Sell, 2.0)).await.unwrap();
+        let portfolio = service.get_portfolio().await.unwrap();
+        let position = &portfolio.positions[0];
+        assert_eq!(position.quantity, 3.0);
+        assert_eq!(position.market_value, Some(dollars(330.0)));
+        assert_eq!(position.unrealized_pnl, Some(dollars(29.4)));
+        assert_eq!(position.realized_pnl, Some(dollars(18.6)));
+        assert_eq!(portfolio.cash, dollars(718.0));
+        assert_eq!(portfolio.net_liquidation_value, dollars(1048.0))
+    }
+
+    #[tokio::test]
+    async fn test_stocks_in_another_currency_are_paid_and_valued_at_the_exchange_rate() {
+        let euros: Currency = "EUR".parse().unwrap();
+        let euro_rate = ExchangeRate { from: euros, to: Currency::USD, rate: Decimal::new(11, 1) };
+        let market_data = Arc::new(
+            TradingApiServiceMock::new()
+                .with_exchange_rate(Ok(euro_rate))
+                .with_exchange_rate(Ok(euro_rate)),
+        );
+        let service = PaperTradingService::new(
+            market_data.clone(),
+            PaperTradingConfig {
+                base_currency: Currency::USD,
+                starting_cash: Decimal::from(1000),
+                commission_per_share: Decimal::ZERO,
+                minimum_commission: Decimal::ZERO,
+                fractional_shares: false,
+            },
+        );
+        let sap = Instrument::stock("SAP.DE");
+        service
+            .update_market_data(&StockData {
+                stock: sap.clone(),
+                ..stock_data_with_price(Decimal::from(100))
+            })
+            .unwrap();
+        let buy = Order { stock: sap.clone(), ..order(OrderType::Buy, 5.0) };
+        service.place_order(buy).await.unwrap();
+        let portfolio = service.get_portfolio().await.unwrap();
+        assert_eq!(service.cash(), dollars(450.0));
+        assert_eq!(
+            portfolio.positions[0].market_value,
+            Some(Money::from_decimal(Decimal::from(500), euros))
+        );
+        assert_eq!(portfolio.net_liquidation_value, dollars(1000.0));
+        assert!(matches!(
+            market_data.calls().as_slice(),
+            [TradingApiCall::GetExchangeRate(..), TradingApiCall::GetExchangeRate(..)]
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_place_order_with_a_zero_exchange_rate() {
+        let euros: Currency = "EUR".parse().unwrap();
+        let zero_rate = ExchangeRate { from: euros, to: Currency::USD, rate: Decimal::ZERO };
+        let service = PaperTradingService::new(
+            Arc::new(TradingApiServiceMock::new().with_exchange_rate(Ok(zero_rate))),
+            PaperTradingConfig {
+                base_currency: Currency::USD,
+                starting_cash: Decimal::from(1000),
+                commission_per_share: Decimal::ZERO,
+                minimum_commission: Decimal::ZERO,
+                fractional_shares: false,
+            },
+        );
+        let sap = Instrument::stock("SAP.DE");
+        service
+            .update_market_data(&StockData {
+                stock: sap.clone(),
+                ..stock_data_with_price(Decimal::from(100))
+            })
+            .unwrap();
+        let maybe_fill =
+            service.place_order(Order { stock: sap, ..order(OrderType::Buy, 5.0) }).await;
+        assert!(matches!(maybe_fill, Err(AppErrors::PlaceOrderError(_))));
+        assert_eq!(service.cash(), dollars(1000.0))
+    }
+
+    #[tokio::test]
+    async fn test_options_are_priced_per_contract_and_kept_apart_from_the_stock() {
+        let service = paper_trading_service();
+        let call: Instrument = serde_json::from_value(serde_json::json!({
+            "security_type": "option",
+            "symbol": "AAPL",
+            "expiry": "20261218",
+            "strike": 200,
+            "right": "call"
+        }))
+        .unwrap();
+        service
+            .update_market_data(&StockData {
+                stock: call.clone(),
+                ..stock_data_with_price(Decimal::new(25, 1))
+            })
+            .unwrap();
+        let quantity =
+            service.convert_money_amount_to_stock_quantity(dollars(600.0), call.clone()).await;
+        assert_eq!(quantity.unwrap(), 2.0);
+        let buy = Order { stock: call.clone(), ..order(OrderType::Buy, 2.0) };
+        service.place_order(buy).await.unwrap();
+        let investment = service.get_current_investment(call).await.unwrap();
+        assert_eq!(service.cash(), dollars(499.0));
+        assert_eq!(investment.market_value, Some(dollars(500.0)));
+        assert!(service.get_current_investment(stock()).await.is_err())
+    }
+
+    #[tokio::test]
+    async fn test_get_current_investment_without_position() {
+        let service = paper_trading_service();
+        assert!(service.get_current_investment(stock()).await.is_err())
+    }
+}
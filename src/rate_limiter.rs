@@ -0,0 +1,57 @@
+use crate::errors::AppErrors;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration as StdDuration;
+use tokio::time::{sleep, Instant};
+
+const MINUTE: StdDuration = StdDuration::from_secs(60);
+const DAY: StdDuration = StdDuration::from_secs(24 * 60 * 60);
+
+/// Keeps calls within a budget per minute and per day, counted over the last
+/// minute and the last 24 hours. A call waits until the minute's budget has
+/// room again, but fails right away once the day's budget is used up. A budget
+/// of 0 is unlimited.
+pub struct RateLimiter {
+    calls_per_minute: u32,
+    calls_per_day: u32,
+    calls: Mutex<VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(calls_per_minute: u32, calls_per_day: u32) -> Self {
+        RateLimiter { calls_per_minute, calls_per_day, calls: Mutex::new(VecDeque::new()) }
+    }
+
+    /// Waits for a free slot and takes it.
+    pub async fn acquire(&self) -> Result<(), AppErrors> {
+        loop {
+            match self.try_acquire(Instant::now())? {
+                None => return Ok(()),
+                Some(wait) => sleep(wait).await,
+            }
+        }
+    }
+
+    /// Takes a slot, or returns how long to wait for the next one.
+    fn try_acquire(&self, now: Instant) -> Result<Option<StdDuration>, AppErrors> {
+        let mut calls = self.calls.lock().unwrap();
+        let window = if self.calls_per_day > 0 { DAY } else { MINUTE };
+        while calls.front().is_some_and(|call| now.duration_since(*call) >= window) {
+            calls.pop_front();
+        }
+        if self.calls_per_day > 0 && calls.len() >= self.calls_per_day as usize {
+            return Err(AppErrors::RateLimitError(format!(
+                "The budget of {} Alpha Vantage calls per day is used up",
+                self.calls_per_day
+            )));
+        }
+        let calls_this_minute =
+            calls.iter().rev().take_while(|call| now.duration_since(**call) < MINUTE).count();
+        if self.calls_per_minute > 0 && calls_this_minute >= self.calls_per_minute as usize {
+            let oldest_this_minute = calls[calls.len() - calls_this_minute];
+            return Ok(Some(MINUTE - now.duration_since(oldest_this_minute)));
+        }
+        calls.push_back(now);
+        Ok(None)
+    }
+}
@@ -0,0 +1,40 @@
+use crate::rate_limiter::RateLimiter;
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[tokio::test(start_paused = true)]
+async fn test_acquire_waits_for_the_minute_budget() {
+    let rate_limiter = RateLimiter::new(2, 0);
+    let start = Instant::now();
+    for _ in 0..3 {
+        rate_limiter.acquire().await.unwrap();
+    }
+    assert_eq!(start.elapsed(), Duration::from_secs(60))
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_acquire_fails_once_the_day_budget_is_used_up() {
+    let rate_limiter = RateLimiter::new(0, 2);
+    rate_limiter.acquire().await.unwrap();
+    rate_limiter.acquire().await.unwrap();
+    let maybe_slot = rate_limiter.acquire().await;
+    assert!(maybe_slot.unwrap_err().to_string().contains("2 Alpha Vantage calls per day"))
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_acquire_day_budget_frees_up_after_a_day() {
+    let rate_limiter = RateLimiter::new(0, 1);
+    rate_limiter.acquire().await.unwrap();
+    tokio::time::advance(Duration::from_secs(24 * 60 * 60)).await;
+    assert!(rate_limiter.acquire().await.is_ok())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_acquire_without_budgets() {
+    let rate_limiter = RateLimiter::new(0, 0);
+    let start = Instant::now();
+    for _ in 0..100 {
+        rate_limiter.acquire().await.unwrap();
+    }
+    assert_eq!(start.elapsed(), Duration::ZERO)
+}
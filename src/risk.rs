@@ -0,0 +1,316 @@
+use crate::errors::AppErrors;
+use crate::models::{Currency, ExchangeRate, Instrument, Order, OrderType, RiskLimits};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::sync::Mutex;
+
+/// The limit that vetoed an order.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskRule {
+    MaxOrderNotional,
+    MaxPositionNotional,
+    MaxDailyLoss,
+    MaxOrdersPerHour,
+    TradingHours,
+    AllowedTickers,
+    DeniedTickers,
+}
+
+impl fmt::Display for RiskRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rule = match self {
+            RiskRule::MaxOrderNotional => "max_order_notional",
+            RiskRule::MaxPositionNotional => "max_position_notional",
+            RiskRule::MaxDailyLoss => "max_daily_loss",
+            RiskRule::MaxOrdersPerHour => "max_orders_per_hour",
+            RiskRule::TradingHours => "trading_hours",
+            RiskRule::AllowedTickers => "allowed_tickers",
+            RiskRule::DeniedTickers => "denied_tickers",
+        };
+        write!(f, "{rule}")
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RiskRejection {
+    pub rule: RiskRule,
+    pub message: String,
+}
+
+impl fmt::Display for RiskRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Order rejected by the {} rule: {}", self.rule, self.message)
+    }
+}
+
+/// A filled order as the risk engine counts it, kept in the trade journal so the
+/// hourly and daily limits survive a restart. The price is per contract and in
+/// the base currency of the limits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskFill {
+    pub filled_at: DateTime<Utc>,
+    pub instrument: String,
+    pub order_type: OrderType,
+    pub quantity: f64,
+    pub price: f64,
+}
+
+impl RiskFill {
+    pub fn new(
+        order: &Order,
+        price: f64,
+        exchange_rate: &ExchangeRate,
+        filled_at: DateTime<Utc>,
+    ) -> Result<Self, AppErrors> {
+        Ok(RiskFill {
+            filled_at,
+            instrument: instrument_key(&order.stock),
+            order_type: order.order_type.clone(),
+            quantity: order.stock_quantity,
+            price: contract_price(order, price) * rate(order, exchange_rate)?,
+        })
+    }
+}
+
+/// What the engine has seen filled so far. Cost basis only covers the fills in
+/// the trade journal, positions bought before it don't count towards the loss.
+#[derive(Default)]
+struct RiskHistory {
+    order_times: VecDeque<DateTime<Utc>>,
+    positions: HashMap<String, (f64, f64)>,
+    day: Option<NaiveDate>,
+    realized_today: f64,
+}
+
+/// Checks every order against the configured `RiskLimits` before it is placed.
+pub struct RiskEngine {
+    limits: RiskLimits,
+    base_currency: Currency,
+    history: Mutex<RiskHistory>,
+}
+
+impl RiskEngine {
+    /// Counts the limits in `base_currency`, the account's currency.
+    pub fn new(limits: RiskLimits, base_currency: Currency) -> Self {
+        RiskEngine { limits, base_currency, history: Mutex::new(RiskHistory::default()) }
+    }
+
+    /// Replays fills from an earlier run, oldest first.
+    pub fn with_fills(self, fills: &[RiskFill]) -> Self {
+        for fill in fills {
+            self.count_fill(fill);
+        }
+        self
+    }
+
+    /// The currency the notional and loss limits are counted in.
+    pub fn base_currency(&self) -> Currency {
+        self.base_currency
+    }
+
+    /// Returns the first rule the order breaks. The price is in the instrument's
+    /// currency and `exchange_rate` converts it into the base currency.
+    /// `position_notional` is the value currently invested in the instrument, in
+    /// the base currency, and is only asked for when that limit is set.
+    pub async fn check(
+        &self,
+        order: &Order,
+        price: f64,
+        exchange_rate: &ExchangeRate,
+        position_notional: impl Future<Output = Result<f64, AppErrors>>,
+        now: DateTime<Utc>,
+    ) -> Result<(), AppErrors> {
+        let instrument = instrument_key(&order.stock);
+        let notional = order.stock_quantity
+            * contract_price(order, price)
+            * self.rate(order, exchange_rate)?;
+        let is_buy = order.order_type == OrderType::Buy;
+
+        if is_listed(&self.limits.denied_tickers, &order.stock) {
+            return reject(RiskRule::DeniedTickers, format!("{instrument} is on the deny list"));
+        }
+        if !self.limits.allowed_tickers.is_empty()
+            && !is_listed(&self.limits.allowed_tickers, &order.stock)
+        {
+            return reject(
+                RiskRule::AllowedTickers,
+                format!("{instrument} is not on the allow list"),
+            );
+        }
+        if let Some(trading_hours) = self.limits.trading_hours_utc {
+            if !trading_hours.contains(now.time()) {
+                return reject(
+                    RiskRule::TradingHours,
+                    format!(
+                        "{} UTC is outside of the trading hours {trading_hours} UTC",
+                        now.format("%H:%M")
+                    ),
+                );
+            }
+        }
+        if let Some(max_order_notional) = self.limits.max_order_notional {
+            if notional > max_order_notional {
+                return reject(
+                    RiskRule::MaxOrderNotional,
+                    format!(
+                        "The order is worth {notional:.2}, the limit is {max_order_notional:.2}"
+                    ),
+                );
+            }
+        }
+
+        self.check_history(is_buy, now)?;
+
+        if let Some(max_position_notional) = self.limits.max_position_notional {
+            let position = position_notional.await? + notional;
+            if is_buy && position > max_position_notional {
+                return reject(
+                    RiskRule::MaxPositionNotional,
+                    format!(
+                        "The position in {instrument} would be worth {position:.2}, the limit is {max_position_notional:.2}"
+                    ),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The rules that depend on the orders filled so far. Kept apart from `check`
+    /// so the history lock is released before the position is looked up.
+    fn check_history(&self, is_buy: bool, now: DateTime<Utc>) -> Result<(), AppErrors> {
+        let mut history = self.history.lock().unwrap();
+        history.start_day(now);
+        if let Some(max_orders_per_hour) = self.limits.max_orders_per_hour {
+            history.forget_orders_before(now - Duration::hours(1));
+            if history.order_times.len() >= max_orders_per_hour as usize {
+                return reject(
+                    RiskRule::MaxOrdersPerHour,
+                    format!("{max_orders_per_hour} orders were already placed in the last hour"),
+                );
+            }
+        }
+        if let Some(max_daily_loss) = self.limits.max_daily_loss {
+            let loss = -history.realized_today;
+            if is_buy && loss >= max_daily_loss {
+                return reject(
+                    RiskRule::MaxDailyLoss,
+                    format!("{loss:.2} was already lost today, the limit is {max_daily_loss:.2}"),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts a placed order towards the hourly limit and the daily loss and
+    /// returns the fill to keep in the trade journal.
+    pub fn record_fill(
+        &self,
+        order: &Order,
+        price: f64,
+        exchange_rate: &ExchangeRate,
+        now: DateTime<Utc>,
+    ) -> Result<RiskFill, AppErrors> {
+        self.rate(order, exchange_rate)?;
+        let fill = RiskFill::new(order, price, exchange_rate, now)?;
+        self.count_fill(&fill);
+        Ok(fill)
+    }
+
+    /// The rate has to convert the instrument's currency into the base currency.
+    fn rate(&self, order: &Order, exchange_rate: &ExchangeRate) -> Result<f64, AppErrors> {
+        if exchange_rate.to != self.base_currency {
+            return Err(AppErrors::GetExchangeRateError(format!(
+                "The risk limits are in {}, the exchange rate converts into {}",
+                self.base_currency, exchange_rate.to
+            )));
+        }
+        rate(order, exchange_rate)
+    }
+
+    fn count_fill(&self, fill: &RiskFill) {
+        let mut history = self.history.lock().unwrap();
+        history.start_day(fill.filled_at);
+        history.order_times.push_back(fill.filled_at);
+        history.forget_orders_before(fill.filled_at - Duration::hours(1));
+
+        let (quantity, average_cost) =
+            history.positions.get(&fill.instrument).copied().unwrap_or_default();
+        match fill.order_type {
+            OrderType::Buy => {
+                let total_quantity = quantity + fill.quantity;
+                let total_cost = quantity * average_cost + fill.quantity * fill.price;
+                history
+                    .positions
+                    .insert(fill.instrument.clone(), (total_quantity, total_cost / total_quantity));
+            }
+            OrderType::Sell => {
+                let sold_quantity = fill.quantity.min(quantity);
+                history.realized_today += sold_quantity * (fill.price - average_cost);
+                let remaining_quantity = quantity - sold_quantity;
+                if remaining_quantity > 0.0 {
+                    history
+                        .positions
+                        .insert(fill.instrument.clone(), (remaining_quantity, average_cost));
+                } else {
+                    history.positions.remove(&fill.instrument);
+                }
+            }
+        }
+    }
+}
+
+impl RiskHistory {
+    fn start_day(&mut self, now: DateTime<Utc>) {
+        let today = now.date_naive();
+        if self.day != Some(today) {
+            self.day = Some(today);
+            self.realized_today = 0.0;
+        }
+    }
+
+    fn forget_orders_before(&mut self, cutoff: DateTime<Utc>) {
+        while self.order_times.front().is_some_and(|time| *time <= cutoff) {
+            self.order_times.pop_front();
+        }
+    }
+}
+
+fn reject(rule: RiskRule, message: String) -> Result<(), AppErrors> {
+    Err(AppErrors::RiskRejectedError(RiskRejection { rule, message }))
+}
+
+/// How the engine names an instrument, the full contract so an option's fills
+/// are kept apart from its underlying.
+fn instrument_key(instrument: &Instrument) -> String {
+    instrument.to_string().trim().to_uppercase()
+}
+
+/// Ticker lists match the symbol, so listing a stock covers its derivatives, or
+/// the full instrument.
+fn is_listed(tickers: &[String], instrument: &Instrument) -> bool {
+    let symbol = instrument.ticker().trim().to_uppercase();
+    let key = instrument_key(instrument);
+    tickers.iter().any(|ticker| *ticker == symbol || *ticker == key)
+}
+
+fn rate(order: &Order, exchange_rate: &ExchangeRate) -> Result<f64, AppErrors> {
+    let currency = order.stock.currency();
+    match exchange_rate.rate.to_f64() {
+        Some(rate) if rate > 0.0 && exchange_rate.from == currency => Ok(rate),
+        _ => Err(AppErrors::GetExchangeRateError(format!(
+            "Can't convert the {currency} of {} at the {} {} to {} rate",
+            order.stock, exchange_rate.rate, exchange_rate.from, exchange_rate.to
+        ))),
+    }
+}
+
+/// What one unit of the order is worth, the price times the multiplier of a
+/// derivative.
+fn contract_price(order: &Order, price: f64) -> f64 {
+    price * order.stock.multiplier().to_f64().unwrap_or(1.0)
+}
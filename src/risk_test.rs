@@ -0,0 +1,347 @@
+use crate::errors::AppErrors;
+use crate::models::{
+    Currency, ExchangeRate, Instrument, Order, OrderStyle, OrderType, RiskLimits, TimeInForce,
+};
+use crate::risk::{RiskEngine, RiskRule};
+use chrono::{DateTime, TimeZone, Utc};
+
+fn order(order_type: OrderType, stock_quantity: f64) -> Order {
+    Order {
+        stock_quantity,
+        stock: Instrument::stock("AAPL"),
+        order_type,
+        style: OrderStyle::Market,
+        time_in_force: TimeInForce::Day,
+    }
+}
+
+fn usd() -> ExchangeRate {
+    ExchangeRate::identity(Currency::USD)
+}
+
+fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 2, 4, hour, minute, 0).unwrap()
+}
+
+fn rejected_rule(result: Result<(), AppErrors>) -> Option<RiskRule> {
+    match result {
+        Err(AppErrors::RiskRejectedError(rejection)) => Some(rejection.rule),
+        _ => None,
+    }
+}
+
+mod check {
+    use super::{at, order, rejected_rule, usd};
+    use crate::models::{Currency, OrderType, RiskLimits, TradingHours};
+    use crate::risk::{RiskEngine, RiskRule};
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn test_check_without_limits_allows_everything() {
+        let engine = RiskEngine::new(RiskLimits::default(), Currency::USD);
+        let maybe_allowed = engine
+            .check(&order(OrderType::Buy, 1_000.0), 250.0, &usd(), async { Ok(1e9) }, at(3, 0))
+            .await;
+        assert!(maybe_allowed.is_ok())
+    }
+
+    #[tokio::test]
+    async fn test_check_max_order_notional() {
+        let engine = RiskEngine::new(
+            RiskLimits { max_order_notional: Some(1_000.0), ..RiskLimits::default() },
+            Currency::USD,
+        );
+        assert!(engine
+            .check(&order(OrderType::Buy, 4.0), 250.0, &usd(), async { Ok(0.0) }, at(15, 0))
+            .await
+            .is_ok());
+        let maybe_allowed = engine
+            .check(&order(OrderType::Sell, 5.0), 250.0, &usd(), async { Ok(0.0) }, at(15, 0))
+            .await;
+        assert_eq!(rejected_rule(maybe_allowed), Some(RiskRule::MaxOrderNotional))
+    }
+
+    #[tokio::test]
+    async fn test_check_max_position_notional_only_limits_buys() {
+        let engine = RiskEngine::new(
+            RiskLimits { max_position_notional: Some(1_000.0), ..RiskLimits::default() },
+            Currency::USD,
+        );
+        let maybe_buy = engine
+            .check(&order(OrderType::Buy, 1.0), 250.0, &usd(), async { Ok(800.0) }, at(15, 0))
+            .await;
+        let maybe_sell = engine
+            .check(&order(OrderType::Sell, 1.0), 250.0, &usd(), async { Ok(800.0) }, at(15, 0))
+            .await;
+        assert_eq!(rejected_rule(maybe_buy), Some(RiskRule::MaxPositionNotional));
+        assert!(maybe_sell.is_ok())
+    }
+
+    #[tokio::test]
+    async fn test_check_position_is_only_looked_up_when_limited() {
+        let engine = RiskEngine::new(RiskLimits::default(), Currency::USD);
+        let maybe_allowed = engine
+            .check(&order(OrderType::Buy, 1.0), 250.0, &usd(), async { panic!() }, at(15, 0))
+            .await;
+        assert!(maybe_allowed.is_ok())
+    }
+
+    #[tokio::test]
+    async fn test_check_trading_hours() {
+        let engine = RiskEngine::new(
+            RiskLimits {
+                trading_hours_utc: Some("14:30-21:00".parse().unwrap()),
+                ..RiskLimits::default()
+            },
+            Currency::USD,
+        );
+        assert!(engine
+            .check(&order(OrderType::Buy, 1.0), 1.0, &usd(), async { Ok(0.0) }, at(14, 30))
+            .await
+            .is_ok());
+        let maybe_allowed = engine
+            .check(&order(OrderType::Buy, 1.0), 1.0, &usd(), async { Ok(0.0) }, at(21, 0))
+            .await;
+        assert_eq!(rejected_rule(maybe_allowed), Some(RiskRule::TradingHours))
+    }
+
+    #[test]
+    fn test_trading_hours_over_midnight() {
+        let trading_hours: TradingHours = "22:00-02:00".parse().unwrap();
+        assert!(trading_hours.contains(at(23, 0).time()));
+        assert!(trading_hours.contains(at(1, 59).time()));
+        assert!(!trading_hours.contains(at(12, 0).time()))
+    }
+
+    #[tokio::test]
+    async fn test_check_allowed_and_denied_tickers() {
+        let allow_list = RiskEngine::new(
+            RiskLimits { allowed_tickers: vec!["MSFT".to_string()], ..RiskLimits::default() },
+            Currency::USD,
+        );
+        let deny_list = RiskEngine::new(
+            RiskLimits { denied_tickers: vec!["AAPL".to_string()], ..RiskLimits::default() },
+            Currency::USD,
+        );
+        let maybe_allowed = allow_list
+            .check(&order(OrderType::Buy, 1.0), 1.0, &usd(), async { Ok(0.0) }, at(15, 0))
+            .await;
+        let maybe_denied = deny_list
+            .check(&order(OrderType::Buy, 1.0), 1.0, &usd(), async { Ok(0.0) }, at(15, 0))
+            .await;
+        assert_eq!(rejected_rule(maybe_allowed), Some(RiskRule::AllowedTickers));
+        assert_eq!(rejected_rule(maybe_denied), Some(RiskRule::DeniedTickers))
+    }
+
+    #[tokio::test]
+    async fn test_check_max_orders_per_hour() {
+        let engine = RiskEngine::new(
+            RiskLimits { max_orders_per_hour: Some(2), ..RiskLimits::default() },
+            Currency::USD,
+        );
+        engine.record_fill(&order(OrderType::Buy, 1.0), 100.0, &usd(), at(15, 0)).unwrap();
+        engine.record_fill(&order(OrderType::Buy, 1.0), 100.0, &usd(), at(15, 30)).unwrap();
+        let maybe_allowed = engine
+            .check(&order(OrderType::Sell, 1.0), 100.0, &usd(), async { Ok(0.0) }, at(15, 45))
+            .await;
+        assert_eq!(rejected_rule(maybe_allowed), Some(RiskRule::MaxOrdersPerHour));
+        let an_hour_later = at(15, 0) + Duration::hours(1);
+        assert!(engine
+            .check(&order(OrderType::Sell, 1.0), 100.0, &usd(), async { Ok(0.0) }, an_hour_later)
+            .await
+            .is_ok())
+    }
+
+    #[tokio::test]
+    async fn test_check_max_daily_loss_blocks_buys_until_the_next_day() {
+        let engine = RiskEngine::new(
+            RiskLimits { max_daily_loss: Some(50.0), ..RiskLimits::default() },
+            Currency::USD,
+        );
+        engine.record_fill(&order(OrderType::Buy, 10.0), 100.0, &usd(), at(15, 0)).unwrap();
+        engine.record_fill(&order(OrderType::Sell, 10.0), 94.0, &usd(), at(16, 0)).unwrap();
+        let maybe_buy = engine
+            .check(&order(OrderType::Buy, 1.0), 94.0, &usd(), async { Ok(0.0) }, at(17, 0))
+            .await;
+        let maybe_sell = engine
+            .check(&order(OrderType::Sell, 1.0), 94.0, &usd(), async { Ok(0.0) }, at(17, 0))
+            .await;
+        let next_day = at(17, 0) + Duration::days(1);
+        assert_eq!(rejected_rule(maybe_buy), Some(RiskRule::MaxDailyLoss));
+        assert!(maybe_sell.is_ok());
+        assert!(engine
+            .check(&order(OrderType::Buy, 1.0), 94.0, &usd(), async { Ok(0.0) }, next_day)
+            .await
+            .is_ok())
+    }
+
+    #[tokio::test]
+    async fn test_check_daily_gains_offset_losses() {
+        let engine = RiskEngine::new(
+            RiskLimits { max_daily_loss: Some(50.0), ..RiskLimits::default() },
+            Currency::USD,
+        );
+        engine.record_fill(&order(OrderType::Buy, 10.0), 100.0, &usd(), at(15, 0)).unwrap();
+        engine.record_fill(&order(OrderType::Sell, 5.0), 110.0, &usd(), at(15, 10)).unwrap();
+        engine.record_fill(&order(OrderType::Sell, 5.0), 90.0, &usd(), at(15, 20)).unwrap();
+        assert!(engine
+            .check(&order(OrderType::Buy, 1.0), 90.0, &usd(), async { Ok(0.0) }, at(16, 0))
+            .await
+            .is_ok())
+    }
+}
+
+mod base_currency {
+    use super::{at, order, rejected_rule, usd};
+    use crate::errors::AppErrors;
+    use crate::models::{Currency, ExchangeRate, Instrument, Order, OrderType, RiskLimits};
+    use crate::risk::{RiskEngine, RiskRule};
+    use rust_decimal::Decimal;
+
+    fn euro_order(stock_quantity: f64) -> Order {
+        Order { stock: Instrument::stock("SAP.DE"), ..order(OrderType::Buy, stock_quantity) }
+    }
+
+    fn euro_rate() -> ExchangeRate {
+        ExchangeRate { from: "EUR".parse().unwrap(), to: Currency::USD, rate: Decimal::new(11, 1) }
+    }
+
+    #[tokio::test]
+    async fn test_check_converts_the_notional_into_the_base_currency() {
+        let engine = RiskEngine::new(
+            RiskLimits { max_order_notional: Some(1_000.0), ..RiskLimits::default() },
+            Currency::USD,
+        );
+        let maybe_allowed = engine
+            .check(&euro_order(10.0), 100.0, &euro_rate(), async { Ok(0.0) }, at(15, 0))
+            .await;
+        assert_eq!(rejected_rule(maybe_allowed), Some(RiskRule::MaxOrderNotional))
+    }
+
+    #[tokio::test]
+    async fn test_check_needs_a_rate_from_the_instrument_currency() {
+        let engine = RiskEngine::new(RiskLimits::default(), Currency::USD);
+        let maybe_allowed =
+            engine.check(&euro_order(1.0), 100.0, &usd(), async { Ok(0.0) }, at(15, 0)).await;
+        assert!(matches!(maybe_allowed, Err(AppErrors::GetExchangeRateError(_))))
+    }
+
+    #[tokio::test]
+    async fn test_daily_loss_is_counted_in_the_base_currency() {
+        let engine = RiskEngine::new(
+            RiskLimits { max_daily_loss: Some(52.0), ..RiskLimits::default() },
+            Currency::USD,
+        );
+        let sell = Order { order_type: OrderType::Sell, ..euro_order(10.0) };
+        engine.record_fill(&euro_order(10.0), 100.0, &euro_rate(), at(15, 0)).unwrap();
+        engine.record_fill(&sell, 95.0, &euro_rate(), at(16, 0)).unwrap();
+        let maybe_buy = engine
+            .check(&order(OrderType::Buy, 1.0), 1.0, &usd(), async { Ok(0.0) }, at(17, 0))
+            .await;
+        assert_eq!(rejected_rule(maybe_buy), Some(RiskRule::MaxDailyLoss))
+    }
+}
+
+mod fills {
+    use super::{at, order, rejected_rule, usd};
+    use crate::models::{Currency, Instrument, OptionRight, Order, OrderType, RiskLimits};
+    use crate::risk::{RiskEngine, RiskFill, RiskRule};
+    use rust_decimal::Decimal;
+
+    fn option() -> Instrument {
+        Instrument::Option {
+            symbol: "AAPL".to_string(),
+            exchange: "SMART".to_string(),
+            currency: Currency::USD,
+            expiry: "20261218".to_string(),
+            strike: Decimal::from(200),
+            right: OptionRight::Call,
+            multiplier: Decimal::ONE_HUNDRED,
+        }
+    }
+
+    #[test]
+    fn test_fill_is_keyed_by_instrument_and_priced_per_contract() {
+        let option_order = Order { stock: option(), ..order(OrderType::Buy, 2.0) };
+        let fill = RiskFill::new(&option_order, 5.0, &usd(), at(15, 0)).unwrap();
+        assert_eq!(
+            fill,
+            RiskFill {
+                filled_at: at(15, 0),
+                instrument: "AAPL 20261218 200 C".to_string(),
+                order_type: OrderType::Buy,
+                quantity: 2.0,
+                price: 500.0,
+            }
+        )
+    }
+
+    #[tokio::test]
+    async fn test_max_daily_loss_survives_a_restart() {
+        let limits = RiskLimits { max_daily_loss: Some(50.0), ..RiskLimits::default() };
+        let engine = RiskEngine::new(limits.clone(), Currency::USD);
+        let fills = vec![
+            engine.record_fill(&order(OrderType::Buy, 10.0), 100.0, &usd(), at(15, 0)).unwrap(),
+            engine.record_fill(&order(OrderType::Sell, 10.0), 94.0, &usd(), at(16, 0)).unwrap(),
+        ];
+        let restarted = RiskEngine::new(limits, Currency::USD).with_fills(&fills);
+        let maybe_buy = restarted
+            .check(&order(OrderType::Buy, 1.0), 94.0, &usd(), async { Ok(0.0) }, at(17, 0))
+            .await;
+        assert_eq!(rejected_rule(maybe_buy), Some(RiskRule::MaxDailyLoss))
+    }
+
+    #[tokio::test]
+    async fn test_option_sells_are_not_counted_against_the_stock() {
+        let engine = RiskEngine::new(
+            RiskLimits { max_daily_loss: Some(50.0), ..RiskLimits::default() },
+            Currency::USD,
+        );
+        engine.record_fill(&order(OrderType::Buy, 10.0), 100.0, &usd(), at(15, 0)).unwrap();
+        let option_sell = Order { stock: option(), ..order(OrderType::Sell, 1.0) };
+        engine.record_fill(&option_sell, 0.5, &usd(), at(15, 10)).unwrap();
+        assert!(engine
+            .check(&order(OrderType::Buy, 1.0), 100.0, &usd(), async { Ok(0.0) }, at(16, 0))
+            .await
+            .is_ok())
+    }
+
+    #[tokio::test]
+    async fn test_deny_list_matches_the_symbol_or_the_full_instrument() {
+        let engine = RiskEngine::new(
+            RiskLimits {
+                denied_tickers: vec!["AAPL 20261218 200 C".to_string()],
+                ..RiskLimits::default()
+            },
+            Currency::USD,
+        );
+        let option_buy = Order { stock: option(), ..order(OrderType::Buy, 1.0) };
+        let maybe_option =
+            engine.check(&option_buy, 5.0, &usd(), async { Ok(0.0) }, at(15, 0)).await;
+        let maybe_stock = engine
+            .check(&order(OrderType::Buy, 1.0), 5.0, &usd(), async { Ok(0.0) }, at(15, 0))
+            .await;
+        assert_eq!(rejected_rule(maybe_option), Some(RiskRule::DeniedTickers));
+        assert!(maybe_stock.is_ok())
+    }
+}
+
+mod rejection {
+    use super::{at, order, usd, RiskEngine, RiskLimits};
+    use crate::models::{Currency, OrderType};
+
+    #[tokio::test]
+    async fn test_rejection_message_names_the_rule() {
+        let engine = RiskEngine::new(
+            RiskLimits { max_order_notional: Some(100.0), ..RiskLimits::default() },
+            Currency::USD,
+        );
+        let error = engine
+            .check(&order(OrderType::Buy, 2.0), 75.0, &usd(), async { Ok(0.0) }, at(15, 0))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("max_order_notional"));
+        assert!(error.contains("150.00"))
+    }
+}
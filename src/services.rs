@@ -1,249 +1,1004 @@
-use crate::config::CONFIG;
+use crate::alpha_vantage_client::AlphaVantageClient;
 use crate::errors::AppErrors;
-use crate::models::{Money, News, NewsApiResponse, Order, OrderType, Stock, StockData, StockInvestment, StockPricePerformance};
-use alpha_vantage::stock_time::StockFunction;
+use crate::models::{
+    Config, Currency, ExchangeRate, Instrument, Money, OptionRight, Order, OrderAdvice,
+    OrderAdviceResponse, OrderStyle, OrderType, Portfolio, PriceHistory, PriceInterval, StockData,
+    StockInvestment, StockPricePerformance,
+};
+use crate::ib_connection::IbConnection;
+use crate::journal::TradeJournal;
+use crate::market_data_cache::MarketDataCache;
+use crate::order_router::IbOrderRouter;
+use crate::orders::{OrderBook, OrderModification, OrderRecord};
+use crate::sizing::LotSize;
 use async_trait::async_trait;
-use ibapi::accounts::PositionUpdate;
-use ibapi::contracts::Contract;
-use ibapi::market_data::historical::{BarSize, ToDuration, WhatToShow};
-use ibapi::orders::{order_builder, Action};
-use ibapi::Client as IbClient;
+use chrono::DateTime;
+use ibapi::accounts::{
+    AccountPortfolioValue, AccountSummaries, AccountSummary, AccountSummaryTags, AccountUpdate,
+    Position, PositionUpdate,
+};
+use ibapi::contracts::{Contract, ContractDetails, SecurityType};
+use ibapi::market_data::historical::{Bar, BarSize, Duration, ToDuration, WhatToShow};
+use ibapi::orders::{order_builder, Action, Order as IbOrder};
+use ibapi::{Client as IbClient, Error as IbError};
+use ollama_rs::error::OllamaError;
 use ollama_rs::generation::completion::request::GenerationRequest;
 use ollama_rs::generation::options::GenerationOptions;
+use ollama_rs::generation::parameters::{FormatType, JsonStructure};
 use ollama_rs::Ollama;
-use reqwest::Client;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration as StdDuration;
 
 #[async_trait]
 pub trait TradingApiService: Send + Sync {
-    async fn get_stock_data(&self, stock_id: Stock) -> Result<StockData, AppErrors>;
-    fn place_order(&self, order: Order) -> Result<String, AppErrors>;
-    fn convert_money_amount_to_stock_quantity(
+    async fn get_stock_data(
+        &self,
+        stock_id: Instrument,
+        price_history: PriceHistory,
+    ) -> Result<StockData, AppErrors>;
+    /// Places the order and waits a moment for it to fill. The returned record
+    /// can still be working, `get_order` follows it afterwards.
+    async fn place_order(&self, order: Order) -> Result<OrderRecord, AppErrors>;
+    async fn get_order(&self, order_id: i32) -> Option<OrderRecord>;
+    async fn cancel_order(&self, order_id: i32) -> Result<OrderRecord, AppErrors>;
+    async fn modify_order(
+        &self,
+        order_id: i32,
+        modification: OrderModification,
+    ) -> Result<OrderRecord, AppErrors>;
+    async fn convert_money_amount_to_stock_quantity(
         &self,
         amount: Money,
-        stock: Stock,
+        stock: Instrument,
     ) -> Result<f64, AppErrors>;
-    fn get_quantity_to_sell_everything(&self, stock: Stock) -> Result<f64, AppErrors>;
-    fn get_current_investment(&self, stock: Stock) -> Result<StockInvestment, AppErrors>;
+    async fn get_quantity_to_sell_everything(&self, stock: Instrument) -> Result<f64, AppErrors>;
+    /// The order sizes the instrument trades in.
+    async fn get_lot_size(&self, stock: Instrument) -> Result<LotSize, AppErrors>;
+    async fn get_current_investment(&self, stock: Instrument)
+        -> Result<StockInvestment, AppErrors>;
+    async fn get_portfolio(&self) -> Result<Portfolio, AppErrors>;
+    /// The rate to convert `from` into `to`.
+    async fn get_exchange_rate(
+        &self,
+        from: Currency,
+        to: Currency,
+    ) -> Result<ExchangeRate, AppErrors>;
 }
 
 #[derive(Clone)]
-pub struct TradingApiServiceLive;
+pub struct TradingApiServiceLive {
+    pub config: Config,
+    pub ib_connection: Arc<IbConnection>,
+    pub order_router: Arc<IbOrderRouter>,
+    pub alpha_vantage_client: AlphaVantageClient,
+    /// Serves repeated market data fetches without asking Alpha Vantage again.
+    pub market_data_cache: Option<Arc<MarketDataCache>>,
+    /// The lot sizes looked up so far, by instrument.
+    pub lot_sizes: Arc<Mutex<HashMap<Instrument, LotSize>>>,
+}
 
 #[async_trait]
 pub trait AiService: Send + Sync {
     async fn get_order_advice(
         &self,
         stock_data: StockData,
-    ) -> Result<OrderType, AppErrors>;
+    ) -> Result<OrderAdviceResponse, AppErrors>;
 }
 
 #[derive(Clone)]
-pub struct AiServiceLive;
+pub struct AiServiceLive {
+    pub config: Config,
+}
+
+/// How long `place_order` waits for the order to be done before returning it
+/// as still working.
+pub const ORDER_FILL_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+
+impl TradingApiServiceLive {
+    pub fn new(config: Config, ib_connection: Arc<IbConnection>) -> Self {
+        let order_router = Arc::new(IbOrderRouter::new(ib_connection.clone()));
+        let alpha_vantage_client = AlphaVantageClient::new(&config);
+        TradingApiServiceLive {
+            config,
+            ib_connection,
+            order_router,
+            alpha_vantage_client,
+            market_data_cache: None,
+            lot_sizes: Arc::default(),
+        }
+    }
+
+    pub fn with_market_data_cache(self, market_data_cache: Arc<MarketDataCache>) -> Self {
+        TradingApiServiceLive { market_data_cache: Some(market_data_cache), ..self }
+    }
+
+    /// Keeps the orders in the trade journal and loads the ones placed before,
+    /// resuming those that weren't done.
+    pub fn with_journal(self, journal: Arc<TradeJournal>) -> Result<Self, AppErrors> {
+        let book = OrderBook::with_journal(journal)?;
+        let order_router = IbOrderRouter::new(self.ib_connection.clone()).with_book(book);
+        order_router.resume();
+        Ok(TradingApiServiceLive { order_router: Arc::new(order_router), ..self })
+    }
+
+    /// The currency Interactive Brokers keeps the first managed account in, the
+    /// one it reports the net liquidation value in.
+    pub async fn get_account_base_currency(&self) -> Result<Currency, AppErrors> {
+        self.blocking("Getting the account summary", self.request_timeout(), |service| {
+            service.account_base_currency_blocking()
+        })
+        .await
+    }
+
+    fn request_timeout(&self) -> StdDuration {
+        StdDuration::from_secs(self.config.request_timeout_seconds)
+    }
+
+    /// Runs the blocking `call` on tokio's blocking pool so it doesn't stall the
+    /// async workers. After a timeout only the caller stops waiting, the call
+    /// itself can't be interrupted and finishes in the background.
+    async fn blocking<T: Send + 'static>(
+        &self,
+        operation: &str,
+        timeout: StdDuration,
+        call: impl FnOnce(TradingApiServiceLive) -> Result<T, AppErrors> + Send + 'static,
+    ) -> Result<T, AppErrors> {
+        let service = self.clone();
+        with_timeout(operation, timeout, async move {
+            tokio::task::spawn_blocking(move || call(service))
+                .await
+                .unwrap_or_else(|error| std::panic::resume_unwind(error.into_panic()))
+        })
+        .await
+    }
+
+    /// The order sizes the instrument trades in, from its contract details. They
+    /// are looked up once per instrument.
+    fn lot_size(&self, stock: &Instrument) -> Result<LotSize, AppErrors> {
+        if let Some(lot_size) = self.lot_sizes.lock().unwrap().get(stock) {
+            return Ok(*lot_size);
+        }
+        let contract = ib_contract(stock)?;
+        let contract_details = self
+            .ib_connection
+            .request(|client| client.contract_details(&contract))
+            .map_err(AppErrors::GetContractDetailsError)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                AppErrors::GetContractDetailsError(format!(
+                    "No contract details found for: {stock}"
+                ))
+            })?;
+        let lot_size = lot_size(stock, &contract_details, self.config.fractional_shares);
+        self.lot_sizes.lock().unwrap().insert(stock.clone(), lot_size);
+        Ok(lot_size)
+    }
+
+    /// The open positions of the account, up to the end of the position list.
+    fn positions(&self) -> Result<Vec<Position>, String> {
+        self.ib_connection.request(|client| {
+            Ok(client
+                .positions()?
+                .iter()
+                .take_while(|position_update| {
+                    !matches!(position_update, PositionUpdate::PositionEnd)
+                })
+                .filter_map(|position_update| match position_update {
+                    PositionUpdate::Position(position) => Some(position),
+                    _ => None,
+                })
+                .collect())
+        })
+    }
+
+    fn order_record(
+        &self,
+        order_id: i32,
+        error: fn(String) -> AppErrors,
+    ) -> Result<OrderRecord, AppErrors> {
+        self.order_router
+            .book()
+            .get(order_id)
+            .ok_or_else(|| error(format!("There is no order with id {order_id}")))
+    }
+
+    pub fn get_ib_stock_price_performance(
+        &self,
+        stock: &Instrument,
+        price_history: PriceHistory,
+    ) -> Result<Vec<StockPricePerformance>, AppErrors> {
+        let contract = ib_contract(stock)?;
+        let what_to_show = ib_what_to_show(stock);
+        let historical_data = self
+            .ib_connection
+            .request(|client| {
+                client.historical_data(
+                    &contract,
+                    None,
+                    ib_duration(price_history),
+                    ib_bar_size(price_history.interval),
+                    what_to_show,
+                    true,
+                )
+            })
+            .map_err(AppErrors::GetStockDataError)?;
+        let mut bars = historical_data
+            .bars
+            .iter()
+            .map(stock_price_performance_from_ib_bar)
+            .collect::<Result<Vec<StockPricePerformance>, AppErrors>>()?;
+        if let Some(lookback) = price_history.lookback {
+            bars.drain(..bars.len().saturating_sub(lookback));
+        }
+        Ok(bars)
+    }
+}
 
 #[async_trait]
 impl TradingApiService for TradingApiServiceLive {
-    async fn get_stock_data(&self, stock: Stock) -> Result<StockData, AppErrors> {
-        let ticker_symbol = stock.ticker_symbol;
-        let api_key = alpha_vantage::set_api(CONFIG.alpha_vantage_api_key, reqwest::Client::new());
-        let stock_price_performance: Result<Vec<StockPricePerformance>, AppErrors> = api_key
-            .stock_time(StockFunction::Monthly, &ticker_symbol)
-            .json()
-            .await
-            .map(|time_series|
-                time_series
-                    .data()
-                    .iter()
-                    .map(|stock_price| StockPricePerformance {
-                        date: stock_price.time().to_string(),
-                        high: stock_price.high().to_string(),
-                        low: stock_price.low().to_string(),
-                        open: stock_price.open().to_string(),
+    /// Stocks and ETFs get their bars from Alpha Vantage, the other instruments
+    /// from Interactive Brokers. The news is about the instrument or its
+    /// underlying, futures go without.
+    async fn get_stock_data(
+        &self,
+        stock: Instrument,
+        price_history: PriceHistory,
+    ) -> Result<StockData, AppErrors> {
+        let ticker_symbol = stock.ticker();
+        let alpha_vantage_client = &self.alpha_vantage_client;
+
+        let time_series = async {
+            if !stock.is_equity() {
+                let stock = stock.clone();
+                return self
+                    .blocking("Getting the bars", self.request_timeout(), move |service| {
+                        service.get_ib_stock_price_performance(&stock, price_history)
                     })
-                    .collect()
-            ).map_err(|error|
-            AppErrors::GetStockDataError(error.to_string())
-        );
-
-        let client = Client::new();
-        let url = "https://www.alphavantage.co/query";
-        let params = [
-            ("function", "NEWS_SENTIMENT"),
-            ("tickers", &*ticker_symbol),
-            ("apikey", CONFIG.alpha_vantage_api_key),
-        ];
-
-        let response = client.get(url).query(&params).send().await;
-
-        let news: Result<Vec<News>, AppErrors> = match response {
-            Ok(news_response) => match news_response.json::<NewsApiResponse>().await {
-                Ok(news_api_response) => Ok(news_api_response.feed),
-                Err(error) => Err(AppErrors::GetStockDataError(error.to_string())),
-            },
-            Err(error) => Err(AppErrors::GetStockDataError(error.to_string())),
+                    .await;
+            }
+            match &self.market_data_cache {
+                Some(cache) => {
+                    cache
+                        .time_series(ticker_symbol, price_history, |output_size| {
+                            alpha_vantage_client.fetch_time_series(
+                                ticker_symbol,
+                                price_history.interval,
+                                output_size,
+                            )
+                        })
+                        .await
+                }
+                None => alpha_vantage_client.get_time_series(ticker_symbol, price_history).await,
+            }
+        };
+        let news_ticker = stock.news_ticker();
+        let news = async {
+            let Some(news_ticker) = &news_ticker else {
+                return Ok(Vec::new());
+            };
+            match &self.market_data_cache {
+                Some(cache) => {
+                    cache
+                        .news(news_ticker, || alpha_vantage_client.get_news_sentiment(news_ticker))
+                        .await
+                }
+                None => alpha_vantage_client.get_news_sentiment(news_ticker).await,
+            }
         };
+        let stock_price_performance = time_series.await?;
+        let news = news.await?;
 
-        Ok(StockData {
-            stock: Stock { ticker_symbol: ticker_symbol.to_string() },
-            stock_price_performance: stock_price_performance?,
-            news: news?,
+        Ok(StockData { stock: stock.clone(), price_history, stock_price_performance, news })
+    }
+
+    async fn place_order(&self, order: Order) -> Result<OrderRecord, AppErrors> {
+        let timeout = self.request_timeout() + ORDER_FILL_TIMEOUT;
+        self.blocking("Placing the order", timeout, move |service| {
+            service.place_order_blocking(order)
         })
+        .await
+    }
+
+    async fn get_order(&self, order_id: i32) -> Option<OrderRecord> {
+        self.order_router.book().get(order_id)
     }
 
-    fn place_order(&self, order: Order) -> Result<String, AppErrors> {
-        let ticker = order.stock.ticker_symbol;
-        let contract = Contract::stock(&*ticker);
+    async fn cancel_order(&self, order_id: i32) -> Result<OrderRecord, AppErrors> {
+        self.blocking("Cancelling the order", self.request_timeout(), move |service| {
+            service.cancel_order_blocking(order_id)
+        })
+        .await
+    }
 
-        let client = IbClient::connect(CONFIG.interactive_brokers_connection_url_with_port, 1)
-            .map_err(|e| AppErrors::PlaceOrderError(e.to_string()))?;
+    async fn modify_order(
+        &self,
+        order_id: i32,
+        modification: OrderModification,
+    ) -> Result<OrderRecord, AppErrors> {
+        self.blocking("Modifying the order", self.request_timeout(), move |service| {
+            service.modify_order_blocking(order_id, modification)
+        })
+        .await
+    }
 
-        let order_id = client.next_order_id(); // Now using mutable borrow
+    async fn convert_money_amount_to_stock_quantity(
+        &self,
+        amount: Money,
+        stock: Instrument,
+    ) -> Result<f64, AppErrors> {
+        self.blocking("Getting the latest price", self.request_timeout(), move |service| {
+            service.convert_money_amount_to_stock_quantity_blocking(amount, stock)
+        })
+        .await
+    }
 
-        let action = match order.order_type {
-            OrderType::Buy => Action::Buy,
-            OrderType::Sell => Action::Sell,
-        };
+    async fn get_quantity_to_sell_everything(&self, stock: Instrument) -> Result<f64, AppErrors> {
+        self.blocking("Getting the positions", self.request_timeout(), move |service| {
+            service.get_quantity_to_sell_everything_blocking(stock)
+        })
+        .await
+    }
 
-        let order = order_builder::market_order(action, order.stock_quantity);
+    async fn get_lot_size(&self, stock: Instrument) -> Result<LotSize, AppErrors> {
+        self.blocking("Getting the contract details", self.request_timeout(), move |service| {
+            service.lot_size(&stock)
+        })
+        .await
+    }
+
+    async fn get_current_investment(
+        &self,
+        stock: Instrument,
+    ) -> Result<StockInvestment, AppErrors> {
+        self.blocking("Getting the positions", self.request_timeout(), move |service| {
+            service.get_current_investment_blocking(stock)
+        })
+        .await
+    }
+
+    async fn get_portfolio(&self) -> Result<Portfolio, AppErrors> {
+        self.blocking("Getting the portfolio", self.request_timeout(), |service| {
+            service.get_portfolio_blocking()
+        })
+        .await
+    }
+
+    async fn get_exchange_rate(
+        &self,
+        from: Currency,
+        to: Currency,
+    ) -> Result<ExchangeRate, AppErrors> {
+        if from == to {
+            return Ok(ExchangeRate::identity(from));
+        }
+        let alpha_vantage_client = &self.alpha_vantage_client;
+        match &self.market_data_cache {
+            Some(cache) => {
+                cache
+                    .exchange_rate(from, to, || alpha_vantage_client.get_exchange_rate(from, to))
+                    .await
+            }
+            None => alpha_vantage_client.get_exchange_rate(from, to).await,
+        }
+    }
+}
 
-        client
-            .place_order(order_id, &contract, &order)
-            .map(|_| format!("{:?}", order))
-            .map_err(|e| AppErrors::PlaceOrderError(e.to_string()))
+/// The blocking `ibapi` calls behind the trait methods, which run them on
+/// tokio's blocking pool.
+impl TradingApiServiceLive {
+    fn place_order_blocking(&self, order: Order) -> Result<OrderRecord, AppErrors> {
+        let stock_quantity = self.lot_size(&order.stock)?.normalize(order.stock_quantity)?;
+        let order_id = self.order_router.place(Order { stock_quantity, ..order })?;
+        self.order_router
+            .book()
+            .wait_until_done(order_id, ORDER_FILL_TIMEOUT)
+            .ok_or_else(|| AppErrors::PlaceOrderError(format!("Order {order_id} wasn't recorded")))
     }
 
-    fn convert_money_amount_to_stock_quantity(
+    fn cancel_order_blocking(&self, order_id: i32) -> Result<OrderRecord, AppErrors> {
+        self.order_router.cancel(order_id)?;
+        self.order_record(order_id, AppErrors::CancelOrderError)
+    }
+
+    fn modify_order_blocking(
+        &self,
+        order_id: i32,
+        modification: OrderModification,
+    ) -> Result<OrderRecord, AppErrors> {
+        self.order_router.modify(order_id, modification)?;
+        self.order_record(order_id, AppErrors::ModifyOrderError)
+    }
+
+    fn convert_money_amount_to_stock_quantity_blocking(
         &self,
         amount: Money,
-        stock: Stock,
+        stock: Instrument,
     ) -> Result<f64, AppErrors> {
-        let contract = Contract::stock(&*stock.ticker_symbol);
-        let current_close =
-            IbClient::connect(CONFIG.interactive_brokers_connection_url_with_port, 1)
-                .and_then(|client: IbClient| {
-                    client
-                        .historical_data(
-                            &contract,
-                            None,
-                            1.days(),
-                            BarSize::Day,
-                            WhatToShow::Trades,
-                            true,
-                        )
-                        .and_then(|historical_data| {
-                            Ok(historical_data.bars.iter().next().map(|bar| bar.close))
-                        })
-                })
-                .map_err(|error| {
-                    AppErrors::ConvertMoneyToStockQuantityError(
-                        error.to_string() + " for ticker: " + &stock.ticker_symbol,
-                    )
-                })
-                .and_then(|close| {
-                    close.ok_or(AppErrors::ConvertMoneyToStockQuantityError(
-                        "There was an error while trying to get the latest closing amount"
-                            .to_string(),
-                    ))
-                })?;
-        Ok((current_close / amount.amount).floor())
+        if amount.currency != stock.currency() {
+            return Err(AppErrors::ConvertMoneyToStockQuantityError(format!(
+                "{stock} trades in {}, the amount is {amount}",
+                stock.currency()
+            )));
+        }
+        let latest_bar = self
+            .get_ib_stock_price_performance(
+                &stock,
+                PriceHistory { interval: PriceInterval::Daily, lookback: Some(1) },
+            )
+            .map_err(|error| {
+                AppErrors::ConvertMoneyToStockQuantityError(format!("{error} for: {stock}"))
+            })?
+            .pop()
+            .ok_or(AppErrors::ConvertMoneyToStockQuantityError(
+                "There was an error while trying to get the latest closing amount".to_string(),
+            ))?;
+        let contract_value = (latest_bar.close * stock.multiplier()).to_f64().ok_or_else(|| {
+            AppErrors::ConvertMoneyToStockQuantityError(format!(
+                "The closing amount {} can't be converted",
+                latest_bar.close
+            ))
+        })?;
+        self.lot_size(&stock)?.normalize(amount.to_f64() / contract_value)
     }
 
-    fn get_quantity_to_sell_everything(&self, stock: Stock) -> Result<f64, AppErrors> {
-        let client = IbClient::connect(CONFIG.interactive_brokers_connection_url_with_port, 1)
-            .map_err(|error| AppErrors::GetQuantityToSellEverythingError(error.to_string()))?;
-        let positions = client
+    fn get_quantity_to_sell_everything_blocking(
+        &self,
+        stock: Instrument,
+    ) -> Result<f64, AppErrors> {
+        let positions = self
             .positions()
-            .map_err(|error| AppErrors::GetQuantityToSellEverythingError(error.to_string()))?;
-
-        let position = positions.iter()
-            .take_while(|position_update|
-                !matches!(position_update, PositionUpdate::PositionEnd)
-            ).find(|position_update|
-            match position_update {
-                PositionUpdate::Position(position) => position.contract.symbol == stock.ticker_symbol,
-                _ => false
-            }
-        ).and_then(|position_update|
-            match position_update {
-                PositionUpdate::Position(position) => Some(position),
-                _ => None
-            }
-        ).ok_or(
+            .map_err(AppErrors::GetQuantityToSellEverythingError)?;
+
+        let position = positions.into_iter()
+            .find(|position| is_listing_of(&position.contract, &stock))
+            .ok_or(
             AppErrors::GetQuantityToSellEverythingError(
-                "There was an error while trying to get the latest closing amount. Possibly there are no positions available or not the position with this ticker_symbol: ".to_string() + &*stock.ticker_symbol
+                "There was an error while trying to get the latest closing amount. Possibly there are no positions available or not the position with this instrument: ".to_string() + &stock.to_string()
             )
         )?;
-        Ok(position.position.abs())
+        self.lot_size(&stock)?.normalize(position.position.abs())
     }
 
-    fn get_current_investment(&self, stock: Stock) -> Result<StockInvestment, AppErrors> {
-        let client = IbClient::connect(CONFIG.interactive_brokers_connection_url_with_port, 1)
-            .map_err(|error| AppErrors::GetCurrentInvestmentError(error.to_string()))?;
-
-        let ticker_symbol = stock.clone().ticker_symbol;
-        let positions = client
+    fn get_current_investment_blocking(
+        &self,
+        stock: Instrument,
+    ) -> Result<StockInvestment, AppErrors> {
+        let positions = self
             .positions()
-            .map_err(|error| AppErrors::GetCurrentInvestmentError(error.to_string()))?;
+            .map_err(AppErrors::GetCurrentInvestmentError)?;
 
-        let position = positions.iter()
-            .take_while(|position_update| !matches!(position_update, PositionUpdate::PositionEnd))
-            .find_map(|position_update| match position_update {
-                PositionUpdate::Position(p) if p.contract.symbol == ticker_symbol => Some(p),
-                _ => None
-            })
+        let position = positions.into_iter()
+            .find(|position| is_listing_of(&position.contract, &stock))
             .ok_or_else(|| AppErrors::GetCurrentInvestmentError(
-                format!("No position found for ticker: {}", ticker_symbol)
+                format!("No position found for: {stock}")
             ))?;
 
-        let stock_name = client.contract_details(&position.contract)
-            .map_err(|e| AppErrors::GetCurrentInvestmentError(e.to_string()))?
+        let stock_name = self.ib_connection
+            .request(|client| client.contract_details(&position.contract))
+            .map_err(AppErrors::GetCurrentInvestmentError)?
             .into_iter()
             .next()
             .ok_or(AppErrors::GetCurrentInvestmentError("No contract details found".to_string()))?
             .long_name;
 
+        let currency = &position.contract.currency;
         let invested_amount = position.position * position.average_cost;
 
         Ok(StockInvestment {
             stock: stock.clone(),
-            stock_name: stock_name,
-            current_invested_amount: Money::new(invested_amount)?,
+            stock_name,
+            current_invested_amount: ib_money(invested_amount, currency)?.round(),
+            quantity: position.position,
+            average_cost: ib_money(position.average_cost, currency)?,
+            market_value: None,
+            unrealized_pnl: None,
+            realized_pnl: None,
+        })
+    }
+
+    fn account_base_currency_blocking(&self) -> Result<Currency, AppErrors> {
+        let currency = self
+            .ib_connection
+            .request(|client| {
+                let account = first_managed_account(client)?;
+                let currency = client
+                    .account_summary("All", &[AccountSummaryTags::NET_LIQUIDATION])?
+                    .iter()
+                    .take_while(|summary| !matches!(summary, AccountSummaries::End))
+                    .find_map(|summary| match summary {
+                        AccountSummaries::Summary(summary) if summary.account == account => {
+                            Some(summary.currency)
+                        }
+                        _ => None,
+                    });
+                Ok(currency)
+            })
+            .map_err(AppErrors::GetPortfolioError)?;
+        currency
+            .ok_or_else(|| {
+                AppErrors::GetPortfolioError(format!(
+                    "The account summary has no {}",
+                    AccountSummaryTags::NET_LIQUIDATION
+                ))
+            })?
+            .parse()
+    }
+
+    /// The portfolio of the first managed account. Positions are named by their
+    /// local symbol, looking up every long name would cost a request per
+    /// position.
+    fn get_portfolio_blocking(&self) -> Result<Portfolio, AppErrors> {
+        let (portfolio_values, summaries) = self
+            .ib_connection
+            .request(|client| {
+                let account = first_managed_account(client)?;
+                let portfolio_values: Vec<AccountPortfolioValue> = client
+                    .account_updates(&account)?
+                    .iter()
+                    .take_while(|update| !matches!(update, AccountUpdate::End))
+                    .filter_map(|update| match update {
+                        AccountUpdate::PortfolioValue(portfolio_value) => Some(portfolio_value),
+                        _ => None,
+                    })
+                    .collect();
+                let summaries: Vec<AccountSummary> = client
+                    .account_summary(
+                        "All",
+                        &[AccountSummaryTags::TOTAL_CASH_VALUE, AccountSummaryTags::NET_LIQUIDATION],
+                    )?
+                    .iter()
+                    .take_while(|summary| !matches!(summary, AccountSummaries::End))
+                    .filter_map(|summary| match summary {
+                        AccountSummaries::Summary(summary) if summary.account == account => {
+                            Some(summary)
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                Ok((portfolio_values, summaries))
+            })
+            .map_err(AppErrors::GetPortfolioError)?;
+
+        let summary_value = |tag: &str| {
+            let summary = summaries.iter().find(|summary| summary.tag == tag).ok_or_else(|| {
+                AppErrors::GetPortfolioError(format!("The account summary has no {tag}"))
+            })?;
+            let value = summary.value.parse::<f64>().map_err(|_| {
+                AppErrors::GetPortfolioError(format!(
+                    "The account summary's {tag} isn't a number: {}",
+                    summary.value
+                ))
+            })?;
+            Ok::<Money, AppErrors>(ib_money(value, &summary.currency)?.round())
+        };
+
+        let net_liquidation_value = summary_value(AccountSummaryTags::NET_LIQUIDATION)?;
+        Ok(Portfolio {
+            base_currency: net_liquidation_value.currency,
+            positions: portfolio_values
+                .into_iter()
+                .filter(|portfolio_value| portfolio_value.position != 0.0)
+                .map(|portfolio_value| {
+                    let currency = &portfolio_value.contract.currency;
+                    let money = |amount| Ok::<Money, AppErrors>(ib_money(amount, currency)?.round());
+                    Ok(StockInvestment {
+                        current_invested_amount: money(
+                            portfolio_value.position * portfolio_value.average_cost,
+                        )?,
+                        quantity: portfolio_value.position,
+                        average_cost: ib_money(portfolio_value.average_cost, currency)?,
+                        market_value: Some(money(portfolio_value.market_value)?),
+                        unrealized_pnl: Some(money(portfolio_value.unrealized_pnl)?),
+                        realized_pnl: Some(money(portfolio_value.realized_pnl)?),
+                        stock: ib_instrument(&portfolio_value.contract)?,
+                        stock_name: match portfolio_value.contract.local_symbol.is_empty() {
+                            true => portfolio_value.contract.symbol,
+                            false => portfolio_value.contract.local_symbol,
+                        },
+                    })
+                })
+                .collect::<Result<Vec<StockInvestment>, AppErrors>>()?,
+            cash: summary_value(AccountSummaryTags::TOTAL_CASH_VALUE)?,
+            net_liquidation_value,
+        })
+    }
+}
+
+/// The contract of the instrument. ETFs are stocks to Interactive Brokers.
+pub fn ib_contract(stock: &Instrument) -> Result<Contract, AppErrors> {
+    let contract = match stock {
+        Instrument::Stock { .. } | Instrument::Etf { .. } => Contract::stock(stock.symbol()),
+        Instrument::Option { expiry, strike, right, multiplier, .. } => Contract {
+            multiplier: multiplier.normalize().to_string(),
+            ..Contract::option(
+                stock.symbol(),
+                expiry,
+                strike.to_f64().ok_or_else(|| {
+                    AppErrors::ModelCreationError(format!(
+                        "The strike of {stock} isn't a valid price: {strike}"
+                    ))
+                })?,
+                right.code(),
+            )
+        },
+        Instrument::Future { expiry, multiplier, .. } => Contract {
+            last_trade_date_or_contract_month: expiry.clone(),
+            multiplier: multiplier.normalize().to_string(),
+            ..Contract::futures(stock.symbol())
+        },
+        Instrument::Crypto { .. } => Contract::crypto(stock.symbol()),
+        Instrument::Forex { .. } => Contract {
+            symbol: stock.symbol().to_string(),
+            security_type: SecurityType::ForexPair,
+            ..Contract::default()
+        },
+    };
+    Ok(Contract {
+        exchange: stock.exchange().to_string(),
+        currency: stock.currency().to_string(),
+        ..contract
+    })
+}
+
+/// The instrument of a contract the account holds. Security types the bot
+/// doesn't trade are listed as stocks.
+pub fn ib_instrument(contract: &Contract) -> Result<Instrument, AppErrors> {
+    let symbol = contract.symbol.clone();
+    let exchange = match (contract.exchange.as_str(), contract.primary_exchange.as_str()) {
+        ("", "") => "SMART".to_string(),
+        ("", primary_exchange) => primary_exchange.to_string(),
+        (exchange, _) => exchange.to_string(),
+    };
+    let currency = contract.currency.parse()?;
+    let multiplier = || {
+        contract.multiplier.parse::<Decimal>().map_err(|_| {
+            AppErrors::ModelCreationError(format!(
+                "The multiplier of {} isn't a number: {}",
+                contract.local_symbol, contract.multiplier
+            ))
         })
+    };
+    Ok(match contract.security_type {
+        SecurityType::Option => Instrument::Option {
+            symbol,
+            exchange,
+            currency,
+            expiry: contract.last_trade_date_or_contract_month.clone(),
+            strike: Decimal::from_f64(contract.strike).ok_or_else(|| {
+                AppErrors::ModelCreationError(format!("Invalid strike: {}", contract.strike))
+            })?,
+            right: match contract.right.starts_with('P') {
+                true => OptionRight::Put,
+                false => OptionRight::Call,
+            },
+            multiplier: multiplier()?,
+        },
+        SecurityType::Future => Instrument::Future {
+            symbol,
+            exchange,
+            currency,
+            expiry: contract.last_trade_date_or_contract_month.clone(),
+            multiplier: multiplier()?,
+        },
+        SecurityType::Crypto => Instrument::Crypto { symbol, exchange, currency },
+        SecurityType::ForexPair => {
+            Instrument::Forex { symbol: symbol.parse()?, exchange, currency }
+        }
+        _ => Instrument::Stock { symbol, exchange, currency },
+    })
+}
+
+/// Whether the contract is the instrument. Positions are reported without the
+/// exchange they were routed to, so the exchange isn't compared. A futures
+/// month matches every last trading day in it.
+pub fn is_listing_of(contract: &Contract, stock: &Instrument) -> bool {
+    let same_terms = match stock {
+        Instrument::Stock { .. } | Instrument::Etf { .. } => {
+            contract.security_type == SecurityType::Stock
+        }
+        Instrument::Option { expiry, strike, right, multiplier, .. } => {
+            contract.security_type == SecurityType::Option
+                && contract.last_trade_date_or_contract_month == *expiry
+                && Decimal::from_f64(contract.strike) == Some(*strike)
+                && contract.right.starts_with(right.code())
+                && contract.multiplier.parse::<Decimal>().ok() == Some(*multiplier)
+        }
+        Instrument::Future { expiry, multiplier, .. } => {
+            contract.security_type == SecurityType::Future
+                && contract.last_trade_date_or_contract_month.starts_with(expiry.as_str())
+                && contract.multiplier.parse::<Decimal>().ok() == Some(*multiplier)
+        }
+        Instrument::Crypto { .. } => contract.security_type == SecurityType::Crypto,
+        Instrument::Forex { .. } => contract.security_type == SecurityType::ForexPair,
+    };
+    same_terms && contract.symbol == stock.symbol() && contract.currency == stock.currency().code()
+}
+
+/// Forex and crypto have no trades to show, their bars are the midpoints.
+pub fn ib_what_to_show(stock: &Instrument) -> WhatToShow {
+    match stock {
+        Instrument::Crypto { .. } | Instrument::Forex { .. } => WhatToShow::MidPoint,
+        _ => WhatToShow::Trades,
     }
 }
 
+/// Fails with a `TimeoutError` naming the operation when `future` takes longer
+/// than `timeout`.
+pub async fn with_timeout<T>(
+    operation: &str,
+    timeout: StdDuration,
+    future: impl Future<Output = Result<T, AppErrors>>,
+) -> Result<T, AppErrors> {
+    tokio::time::timeout(timeout, future).await.unwrap_or_else(|_| {
+        Err(AppErrors::TimeoutError(format!(
+            "{operation} didn't finish within {}s",
+            timeout.as_secs_f64()
+        )))
+    })
+}
+
+/// How often the model is asked for an order advice before giving up on
+/// malformed answers.
+pub const ORDER_ADVICE_ATTEMPTS: usize = 3;
+
 #[async_trait]
 impl AiService for AiServiceLive {
     async fn get_order_advice(
         &self,
         stock_data: StockData,
-    ) -> Result<OrderType, AppErrors> {
+    ) -> Result<OrderAdviceResponse, AppErrors> {
         let ollama = Ollama::default();
-        let model = CONFIG.ollama_model.to_string();
+        let model = self.config.ollama_model.clone();
         let options = GenerationOptions::default().temperature(0.0);
-        let ticker_symbol = stock_data.stock.clone().ticker_symbol;
-        let prompt = format!(
-            "Portfolio analysis:\nTicker: {}\nNews: {:?}\nPrice: {:?}\nShould I SELL or BUY? Reply with only one word: SELL or BUY. (If you are not sure, do not reply with one of those words)",
-            ticker_symbol,
-            stock_data.news,
-            stock_data.stock_price_performance
-        );
-
-        let order_advice_result = ollama
-            .generate(GenerationRequest::new(model, prompt).options(options))
-            .await;
-
-        order_advice_result
-            .map_err(|error| AppErrors::GetOrderAdviceError(error.to_string()))
-            .and_then(|ai_result| {
-                if ai_result.response.contains("SELL") {
-                    Ok(OrderType::Sell)
-                } else if ai_result.response.contains("BUY") {
-                    Ok(OrderType::Buy)
-                } else {
-                    Err(AppErrors::GetOrderAdviceError(
-                        "The Ai didn't respond with a clear order advice".to_string(),
-                    ))
-                }
-            })
+
+        request_order_advice(ORDER_ADVICE_ATTEMPTS, order_advice_prompt(&stock_data), |prompt| {
+            let request = GenerationRequest::new(model.clone(), prompt)
+                .options(options.clone())
+                .format(FormatType::StructuredJson(JsonStructure::new::<OrderAdvice>()));
+            let ollama = &ollama;
+            async move {
+                ollama
+                    .generate(request)
+                    .await
+                    .map(|generation_response| generation_response.response)
+                    .map_err(ollama_error)
+            }
+        })
+        .await
+    }
+}
+
+/// A request that never reached Ollama is a `NetworkError`, so callers can tell
+/// an unreachable model apart from a bad answer.
+fn ollama_error(error: OllamaError) -> AppErrors {
+    match error {
+        OllamaError::ReqwestError(error) => {
+            AppErrors::NetworkError(format!("Could not reach Ollama: {error}"))
+        }
+        error => AppErrors::GetOrderAdviceError(error.to_string()),
+    }
+}
+
+pub fn order_advice_prompt(stock_data: &StockData) -> String {
+    format!(
+        "Portfolio analysis:\nTicker: {}\nNews: {:?}\nPrice per {} bar (date: open, high, low, close, volume):\n{}\nShould I BUY, SELL or HOLD? Reply with only a JSON object with the fields \"action\" (\"BUY\", \"SELL\" or \"HOLD\"), \"confidence\" (a number from 0 to 1), \"rationale\" (one or two sentences), \"key_factors\" (a list of short strings) and optionally the per share price levels \"limit_price\", \"stop_price\", \"take_profit_price\" and \"stop_loss_price\" you would trade at. If you are not sure, answer HOLD.",
+        stock_data.stock,
+        stock_data.news,
+        stock_data.price_history.interval,
+        format_price_history(&stock_data.stock_price_performance)
+    )
+}
+
+/// Asks `generate` for an order advice up to `attempts` times. Answers that
+/// don't parse into a valid `OrderAdvice` are retried with the parsing error
+/// appended to the prompt; errors from `generate` itself are returned at once.
+pub async fn request_order_advice<F, Fut>(
+    attempts: usize,
+    prompt: String,
+    mut generate: F,
+) -> Result<OrderAdviceResponse, AppErrors>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<String, AppErrors>>,
+{
+    let mut next_prompt = prompt.clone();
+    let mut last_error = None;
+    for _ in 0..attempts {
+        let raw_response = generate(next_prompt.clone()).await?;
+        match parse_order_advice(&raw_response) {
+            Ok(order_advice) => {
+                return Ok(OrderAdviceResponse {
+                    order_advice,
+                    prompt: next_prompt,
+                    raw_response,
+                })
+            }
+            Err(error) => {
+                next_prompt = format!(
+                    "{prompt}\n\nYour previous answer was invalid: {error}. Reply with only the JSON object."
+                );
+                last_error = Some((error, raw_response));
+            }
+        }
     }
-}
\ No newline at end of file
+    Err(AppErrors::GetOrderAdviceError(match last_error {
+        Some((error, raw_response)) => format!(
+            "The Ai didn't respond with a valid order advice after {attempts} attempts: {error}. Last answer: {raw_response}"
+        ),
+        None => "The Ai wasn't asked for an order advice".to_string(),
+    }))
+}
+
+/// Reasoning models like deepseek-r1 prefix their answer with a
+/// `<think>...</think>` block and may wrap the JSON in prose or code fences, so
+/// only the outermost JSON object after the reasoning is parsed.
+pub fn parse_order_advice(response: &str) -> Result<OrderAdvice, AppErrors> {
+    let answer = response
+        .rfind("</think>")
+        .map(|position| &response[position + "</think>".len()..])
+        .unwrap_or(response);
+    let json = match (answer.find('{'), answer.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &answer[start..=end],
+        _ => {
+            return Err(AppErrors::GetOrderAdviceError(
+                "The answer didn't contain a JSON object".to_string(),
+            ))
+        }
+    };
+    serde_json::from_str::<OrderAdvice>(json)
+        .map_err(|error| AppErrors::GetOrderAdviceError(error.to_string()))?
+        .validate()
+}
+
+pub fn format_price_history(stock_price_performance: &[StockPricePerformance]) -> String {
+    stock_price_performance
+        .iter()
+        .map(|bar| {
+            format!(
+                "{}: {}, {}, {}, {}, {}",
+                bar.date, bar.open, bar.high, bar.low, bar.close, bar.volume
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn first_managed_account(client: &IbClient) -> Result<String, IbError> {
+    client
+        .managed_accounts()?
+        .into_iter()
+        .next()
+        .ok_or_else(|| IbError::Simple("No managed account found".to_string()))
+}
+
+/// An amount Interactive Brokers reports as a float with its currency code.
+pub fn ib_money(amount: f64, currency: &str) -> Result<Money, AppErrors> {
+    Money::from_f64(amount, currency.parse()?)
+}
+
+/// The lot size of the contract. Crypto and forex trade in the increment
+/// Interactive Brokers reports. Stocks and ETFs only trade below one share with
+/// fractional shares on, everything else in whole contracts, so a smaller
+/// increment is rounded up to one.
+pub fn lot_size(
+    stock: &Instrument,
+    contract_details: &ContractDetails,
+    fractional_shares: bool,
+) -> LotSize {
+    let takes_fractions = match stock {
+        Instrument::Crypto { .. } | Instrument::Forex { .. } => true,
+        Instrument::Stock { .. } | Instrument::Etf { .. } => fractional_shares,
+        _ => false,
+    };
+    let size_increment = match contract_details.size_increment {
+        increment if increment > 0.0 && (takes_fractions || increment >= 1.0) => increment,
+        increment => increment.max(1.0).ceil(),
+    };
+    LotSize { min_size: contract_details.min_size.max(size_increment), size_increment }
+}
+
+/// Builds the IB orders for `order`, a single order or the parent, take profit
+/// and stop loss of a bracket starting at `order_id`.
+pub fn ib_orders(order_id: i32, order: &Order) -> Vec<IbOrder> {
+    let action = match order.order_type {
+        OrderType::Buy => Action::Buy,
+        OrderType::Sell => Action::Sell,
+    };
+    let quantity = order.stock_quantity;
+    let mut ib_orders = match order.style {
+        OrderStyle::Market => vec![order_builder::market_order(action, quantity)],
+        OrderStyle::Limit { limit_price } => {
+            vec![order_builder::limit_order(action, quantity, limit_price)]
+        }
+        OrderStyle::Stop { stop_price } => vec![order_builder::stop(action, quantity, stop_price)],
+        OrderStyle::StopLimit { stop_price, limit_price } => {
+            vec![order_builder::stop_limit(action, quantity, limit_price, stop_price)]
+        }
+        OrderStyle::Bracket { limit_price, take_profit_price, stop_loss_price } => {
+            order_builder::bracket_order(
+                order_id,
+                action,
+                quantity,
+                limit_price,
+                take_profit_price,
+                stop_loss_price,
+            )
+        }
+    };
+    for ib_order in &mut ib_orders {
+        if ib_order.order_id == 0 {
+            ib_order.order_id = order_id;
+        }
+        ib_order.tif = order.time_in_force.to_string();
+    }
+    ib_orders
+}
+
+pub fn ib_bar_size(interval: PriceInterval) -> BarSize {
+    match interval {
+        PriceInterval::OneMinute => BarSize::Min,
+        PriceInterval::FiveMinutes => BarSize::Min5,
+        PriceInterval::FifteenMinutes => BarSize::Min15,
+        PriceInterval::ThirtyMinutes => BarSize::Min30,
+        PriceInterval::SixtyMinutes => BarSize::Hour,
+        PriceInterval::Daily => BarSize::Day,
+        PriceInterval::Weekly => BarSize::Week,
+        PriceInterval::Monthly => BarSize::Month,
+    }
+}
+
+/// The IB request duration that covers the lookback. Without a lookback the
+/// longest duration IB serves for the bar size is requested: a day of one minute
+/// bars, a week of 5 and 15 minute bars, a month of 30 and 60 minute bars and a
+/// year of daily and longer bars. Intraday lookbacks are capped the same way.
+pub fn ib_duration(price_history: PriceHistory) -> Duration {
+    const REGULAR_TRADING_MINUTES_PER_DAY: i32 = 390;
+    let max_intraday_days = match price_history.interval {
+        PriceInterval::OneMinute => Some(1),
+        PriceInterval::FiveMinutes | PriceInterval::FifteenMinutes => Some(7),
+        PriceInterval::ThirtyMinutes | PriceInterval::SixtyMinutes => Some(30),
+        PriceInterval::Daily | PriceInterval::Weekly | PriceInterval::Monthly => None,
+    };
+    let lookback = match (price_history.lookback, max_intraday_days) {
+        (Some(lookback), _) => i32::try_from(lookback.max(1)).unwrap_or(i32::MAX),
+        (None, Some(max_days)) => return max_days.days(),
+        (None, None) => return 1.years(),
+    };
+    match price_history.interval {
+        // Weekends and holidays are not trading days.
+        PriceInterval::Daily => lookback.saturating_mul(7).saturating_div(5).saturating_add(3).days(),
+        PriceInterval::Weekly => lookback.weeks(),
+        PriceInterval::Monthly => lookback.months(),
+        intraday => {
+            let minutes = intraday.intraday_minutes().unwrap_or(1) as i32;
+            let trading_days = lookback.saturating_mul(minutes) / REGULAR_TRADING_MINUTES_PER_DAY;
+            // Rounds up and covers the current, partial session.
+            let days = trading_days.saturating_add(1);
+            days.min(max_intraday_days.unwrap_or(days)).days()
+        }
+    }
+}
+
+pub fn stock_price_performance_from_ib_bar(bar: &Bar) -> Result<StockPricePerformance, AppErrors> {
+    let to_decimal = |value: f64| {
+        Decimal::try_from(value).map_err(|error| {
+            AppErrors::GetStockDataError(format!("Invalid price {value} in IB bar: {error}"))
+        })
+    };
+    let date = DateTime::from_timestamp(bar.date.unix_timestamp(), 0)
+        .ok_or_else(|| AppErrors::GetStockDataError(format!("Invalid IB bar date: {}", bar.date)))?
+        .naive_utc();
+    Ok(StockPricePerformance {
+        date,
+        open: to_decimal(bar.open)?,
+        high: to_decimal(bar.high)?,
+        low: to_decimal(bar.low)?,
+        close: to_decimal(bar.close)?,
+        volume: bar.volume.max(0.0).round() as u64,
+    })
+}
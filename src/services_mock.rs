@@ -0,0 +1,265 @@
+use crate::errors::AppErrors;
+use crate::models::{
+    Currency, ExchangeRate, Instrument, Money, Order, OrderAdvice, OrderAdviceResponse, Portfolio,
+    PriceHistory, StockData, StockInvestment,
+};
+use crate::orders::{OrderModification, OrderRecord};
+use crate::services::{order_advice_prompt, AiService, TradingApiService};
+use crate::sizing::LotSize;
+use async_trait::async_trait;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub enum TradingApiCall {
+    GetStockData(Instrument, PriceHistory),
+    PlaceOrder(Order),
+    ConvertMoneyAmountToStockQuantity(Money, Instrument),
+    GetQuantityToSellEverything(Instrument),
+    GetLotSize(Instrument),
+    GetCurrentInvestment(Instrument),
+    GetPortfolio,
+    GetExchangeRate(Currency, Currency),
+    CancelOrder(i32),
+    ModifyOrder(i32, OrderModification),
+}
+
+impl TradingApiCall {
+    /// The stock the call is about. Order id and account calls don't name one.
+    pub fn stock(&self) -> Option<&Instrument> {
+        match self {
+            TradingApiCall::GetStockData(stock, _)
+            | TradingApiCall::ConvertMoneyAmountToStockQuantity(_, stock)
+            | TradingApiCall::GetQuantityToSellEverything(stock)
+            | TradingApiCall::GetLotSize(stock)
+            | TradingApiCall::GetCurrentInvestment(stock) => Some(stock),
+            TradingApiCall::PlaceOrder(order) => Some(&order.stock),
+            TradingApiCall::GetPortfolio
+            | TradingApiCall::GetExchangeRate(..)
+            | TradingApiCall::CancelOrder(_)
+            | TradingApiCall::ModifyOrder(..) => None,
+        }
+    }
+}
+
+/// Scriptable `TradingApiService`. Every method pops the next queued response
+/// and records its arguments; calling a method without a queued response panics.
+/// Placed orders are kept for `get_order`.
+#[derive(Default)]
+pub struct TradingApiServiceMock {
+    stock_data: Mutex<VecDeque<Result<StockData, AppErrors>>>,
+    place_order: Mutex<VecDeque<Result<OrderRecord, AppErrors>>>,
+    cancel_order: Mutex<VecDeque<Result<OrderRecord, AppErrors>>>,
+    modify_order: Mutex<VecDeque<Result<OrderRecord, AppErrors>>>,
+    orders: Mutex<BTreeMap<i32, OrderRecord>>,
+    stock_quantity: Mutex<VecDeque<Result<f64, AppErrors>>>,
+    quantity_to_sell_everything: Mutex<VecDeque<Result<f64, AppErrors>>>,
+    lot_size: Mutex<VecDeque<Result<LotSize, AppErrors>>>,
+    current_investment: Mutex<VecDeque<Result<StockInvestment, AppErrors>>>,
+    portfolio: Mutex<VecDeque<Result<Portfolio, AppErrors>>>,
+    exchange_rate: Mutex<VecDeque<Result<ExchangeRate, AppErrors>>>,
+    calls: Mutex<Vec<TradingApiCall>>,
+}
+
+impl TradingApiServiceMock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_stock_data(self, response: Result<StockData, AppErrors>) -> Self {
+        self.stock_data.lock().unwrap().push_back(response);
+        self
+    }
+
+    pub fn with_place_order(self, response: Result<OrderRecord, AppErrors>) -> Self {
+        self.place_order.lock().unwrap().push_back(response);
+        self
+    }
+
+    pub fn with_cancel_order(self, response: Result<OrderRecord, AppErrors>) -> Self {
+        self.cancel_order.lock().unwrap().push_back(response);
+        self
+    }
+
+    pub fn with_modify_order(self, response: Result<OrderRecord, AppErrors>) -> Self {
+        self.modify_order.lock().unwrap().push_back(response);
+        self
+    }
+
+    pub fn with_stock_quantity(self, response: Result<f64, AppErrors>) -> Self {
+        self.stock_quantity.lock().unwrap().push_back(response);
+        self
+    }
+
+    pub fn with_quantity_to_sell_everything(self, response: Result<f64, AppErrors>) -> Self {
+        self.quantity_to_sell_everything.lock().unwrap().push_back(response);
+        self
+    }
+
+    pub fn with_lot_size(self, response: Result<LotSize, AppErrors>) -> Self {
+        self.lot_size.lock().unwrap().push_back(response);
+        self
+    }
+
+    pub fn with_current_investment(self, response: Result<StockInvestment, AppErrors>) -> Self {
+        self.current_investment.lock().unwrap().push_back(response);
+        self
+    }
+
+    pub fn with_portfolio(self, response: Result<Portfolio, AppErrors>) -> Self {
+        self.portfolio.lock().unwrap().push_back(response);
+        self
+    }
+
+    pub fn with_exchange_rate(self, response: Result<ExchangeRate, AppErrors>) -> Self {
+        self.exchange_rate.lock().unwrap().push_back(response);
+        self
+    }
+
+    pub fn calls(&self) -> Vec<TradingApiCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    pub fn placed_orders(&self) -> Vec<Order> {
+        self.calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                TradingApiCall::PlaceOrder(order) => Some(order),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn record(&self, call: TradingApiCall) {
+        self.calls.lock().unwrap().push(call);
+    }
+}
+
+fn next_response<T>(queue: &Mutex<VecDeque<T>>, method: &str) -> T {
+    queue
+        .lock()
+        .unwrap()
+        .pop_front()
+        .unwrap_or_else(|| panic!("No queued response for {method}"))
+}
+
+#[async_trait]
+impl TradingApiService for TradingApiServiceMock {
+    async fn get_stock_data(
+        &self,
+        stock: Instrument,
+        price_history: PriceHistory,
+    ) -> Result<StockData, AppErrors> {
+        self.record(TradingApiCall::GetStockData(stock, price_history));
+        next_response(&self.stock_data, "get_stock_data")
+    }
+
+    async fn place_order(&self, order: Order) -> Result<OrderRecord, AppErrors> {
+        self.record(TradingApiCall::PlaceOrder(order));
+        let response = next_response(&self.place_order, "place_order");
+        if let Ok(record) = &response {
+            self.orders.lock().unwrap().insert(record.order_id, record.clone());
+        }
+        response
+    }
+
+    async fn get_order(&self, order_id: i32) -> Option<OrderRecord> {
+        self.orders.lock().unwrap().get(&order_id).cloned()
+    }
+
+    async fn cancel_order(&self, order_id: i32) -> Result<OrderRecord, AppErrors> {
+        self.record(TradingApiCall::CancelOrder(order_id));
+        next_response(&self.cancel_order, "cancel_order")
+    }
+
+    async fn modify_order(
+        &self,
+        order_id: i32,
+        modification: OrderModification,
+    ) -> Result<OrderRecord, AppErrors> {
+        self.record(TradingApiCall::ModifyOrder(order_id, modification));
+        next_response(&self.modify_order, "modify_order")
+    }
+
+    async fn convert_money_amount_to_stock_quantity(
+        &self,
+        amount: Money,
+        stock: Instrument,
+    ) -> Result<f64, AppErrors> {
+        self.record(TradingApiCall::ConvertMoneyAmountToStockQuantity(amount, stock));
+        next_response(&self.stock_quantity, "convert_money_amount_to_stock_quantity")
+    }
+
+    async fn get_quantity_to_sell_everything(&self, stock: Instrument) -> Result<f64, AppErrors> {
+        self.record(TradingApiCall::GetQuantityToSellEverything(stock));
+        next_response(&self.quantity_to_sell_everything, "get_quantity_to_sell_everything")
+    }
+
+    async fn get_lot_size(&self, stock: Instrument) -> Result<LotSize, AppErrors> {
+        self.record(TradingApiCall::GetLotSize(stock));
+        next_response(&self.lot_size, "get_lot_size")
+    }
+
+    async fn get_current_investment(
+        &self,
+        stock: Instrument,
+    ) -> Result<StockInvestment, AppErrors> {
+        self.record(TradingApiCall::GetCurrentInvestment(stock));
+        next_response(&self.current_investment, "get_current_investment")
+    }
+
+    async fn get_portfolio(&self) -> Result<Portfolio, AppErrors> {
+        self.record(TradingApiCall::GetPortfolio);
+        next_response(&self.portfolio, "get_portfolio")
+    }
+
+    async fn get_exchange_rate(
+        &self,
+        from: Currency,
+        to: Currency,
+    ) -> Result<ExchangeRate, AppErrors> {
+        self.record(TradingApiCall::GetExchangeRate(from, to));
+        next_response(&self.exchange_rate, "get_exchange_rate")
+    }
+}
+
+/// Scriptable `AiService` that hands out queued order advice and records the
+/// stock data it was asked about. The raw response is the advice as JSON.
+#[derive(Default)]
+pub struct AiServiceMock {
+    order_advice: Mutex<VecDeque<Result<OrderAdvice, AppErrors>>>,
+    calls: Mutex<Vec<StockData>>,
+}
+
+impl AiServiceMock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_order_advice(self, response: Result<OrderAdvice, AppErrors>) -> Self {
+        self.order_advice.lock().unwrap().push_back(response);
+        self
+    }
+
+    pub fn calls(&self) -> Vec<StockData> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl AiService for AiServiceMock {
+    async fn get_order_advice(
+        &self,
+        stock_data: StockData,
+    ) -> Result<OrderAdviceResponse, AppErrors> {
+        let prompt = order_advice_prompt(&stock_data);
+        self.calls.lock().unwrap().push(stock_data);
+        next_response(&self.order_advice, "get_order_advice").map(|order_advice| {
+            OrderAdviceResponse {
+                raw_response: serde_json::to_string(&order_advice).unwrap(),
+                order_advice,
+                prompt,
+            }
+        })
+    }
+}
@@ -1,170 +1,574 @@
-use crate::models::Stock;
+use crate::config;
+use crate::ib_connection::IbConnection;
+use crate::models::{Config, Instrument};
+use crate::services::TradingApiServiceLive;
 use lazy_static::lazy_static;
-use std::string::ToString;
+use std::sync::Arc;
 
 lazy_static! {
-    pub static ref INVESTED_PAPER_TRADING_STOCK: Stock = Stock {
-        ticker_symbol: String::from("AAPL")
-    };
-    pub static ref NOT_VALID_STOCK: Stock = Stock {
-        ticker_symbol: String::from("Not a ticker symbol")
-    };
+    pub static ref INVESTED_PAPER_TRADING_STOCK: Instrument = Instrument::stock("AAPL");
+    pub static ref NOT_VALID_STOCK: Instrument = Instrument::stock("Not a ticker symbol");
+    pub static ref LIVE_CONFIG: Config =
+        config::load_from_args(vec![]).expect("The live service tests need a valid configuration");
+}
+
+pub fn live_trading_service() -> TradingApiServiceLive {
+    let ib_connection =
+        Arc::new(IbConnection::new(&LIVE_CONFIG.interactive_brokers_connection_url_with_port));
+    TradingApiServiceLive::new(LIVE_CONFIG.clone(), ib_connection)
 }
 
 
 mod trading_api_service {
     use crate::errors::AppErrors;
-    use crate::models::{Money, Order, OrderType, StockData, StockInvestment};
+    use crate::models::{
+        Currency, Money, Order, OrderStyle, OrderType, Portfolio, PriceHistory, StockData,
+        StockInvestment, TimeInForce,
+    };
+    use crate::orders::OrderRecord;
     use crate::services::TradingApiService;
-    use crate::services::TradingApiServiceLive;
-    use crate::services_test::{INVESTED_PAPER_TRADING_STOCK, NOT_VALID_STOCK};
-    use std::time::SystemTime;
+    use crate::services_test::{live_trading_service, INVESTED_PAPER_TRADING_STOCK, NOT_VALID_STOCK};
+    use rust_decimal::Decimal;
     use tokio::test;
 
     #[test]
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
     async fn test_get_stock_data_method_success() {
         let maybe_stock_data: Result<StockData, AppErrors> =
-            TradingApiServiceLive::get_stock_data(INVESTED_PAPER_TRADING_STOCK).await;
+            live_trading_service()
+                .get_stock_data(INVESTED_PAPER_TRADING_STOCK.clone(), PriceHistory::default()).await;
         println!("{:?}", maybe_stock_data);
         assert!(maybe_stock_data.is_ok())
     }
 
     #[test]
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
     async fn test_get_stock_data_method_failure() {
         let maybe_stock_data: Result<StockData, AppErrors> =
-            TradingApiServiceLive::get_stock_data(NOT_VALID_STOCK).await;
+            live_trading_service()
+                .get_stock_data(NOT_VALID_STOCK.clone(), PriceHistory::default()).await;
         assert!(maybe_stock_data.is_err())
     }
 
     #[test]
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
     async fn test_place_order_method_success() {
         let order_success_mock: Order = Order {
             stock_quantity: 1.1,
-            stock: INVESTED_PAPER_TRADING_STOCK,
+            stock: INVESTED_PAPER_TRADING_STOCK.clone(),
             order_type: OrderType::Buy,
-            timestamp: SystemTime::now(),
+            style: OrderStyle::Market,
+            time_in_force: TimeInForce::Day,
         };
-        let maybe_successfully_placed_order: Result<String, AppErrors> =
-            TradingApiServiceLive::place_order(order_success_mock);
+        let maybe_successfully_placed_order: Result<OrderRecord, AppErrors> =
+            live_trading_service()
+                .place_order(order_success_mock).await;
         println!("{:?}", maybe_successfully_placed_order);
         assert!(maybe_successfully_placed_order.is_ok())
     }
 
     #[test]
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
     async fn test_place_order_method_failure() {
         let order_failure_mock: Order = Order {
             stock_quantity: 1.1,
-            stock: NOT_VALID_STOCK,
+            stock: NOT_VALID_STOCK.clone(),
             order_type: OrderType::Buy,
-            timestamp: SystemTime::now(),
+            style: OrderStyle::Market,
+            time_in_force: TimeInForce::Day,
         };
-        let maybe_successfully_failed_order: Result<String, AppErrors> =
-            TradingApiServiceLive::place_order(order_failure_mock);
+        let maybe_successfully_failed_order: Result<OrderRecord, AppErrors> =
+            live_trading_service()
+                .place_order(order_failure_mock).await;
         println!("{:?}", maybe_successfully_failed_order);
         assert!(maybe_successfully_failed_order.is_err())
     }
 
     #[test]
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
     async fn test_convert_money_amount_to_stock_quantity_method_success() {
-        let money_mock = Money::new(1.1).unwrap();
+        let money_mock = Money::new(Decimal::new(11, 1), Currency::USD).unwrap();
         let maybe_quantity: Result<f64, AppErrors> =
-            TradingApiServiceLive::convert_money_amount_to_stock_quantity(
-                money_mock,
-                INVESTED_PAPER_TRADING_STOCK,
-            );
+            live_trading_service()
+                .convert_money_amount_to_stock_quantity(
+                    money_mock,
+                    INVESTED_PAPER_TRADING_STOCK.clone(),
+                ).await;
         println!("{:?}", maybe_quantity);
         assert!(maybe_quantity.is_ok())
     }
 
     #[test]
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
     async fn test_convert_money_amount_to_stock_quantity_method_failure() {
-        let money_mock = Money::new(1.1).unwrap();
+        let money_mock = Money::new(Decimal::new(11, 1), Currency::USD).unwrap();
         let maybe_stock_data: Result<f64, AppErrors> =
-            TradingApiServiceLive::convert_money_amount_to_stock_quantity(
-                money_mock,
-                NOT_VALID_STOCK,
-            );
+            live_trading_service()
+                .convert_money_amount_to_stock_quantity(
+                    money_mock,
+                    NOT_VALID_STOCK.clone(),
+                ).await;
         assert!(maybe_stock_data.is_err())
     }
 
     #[test]
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
     async fn test_get_quantity_to_sell_everything_method_success() {
         let maybe_stock_data: Result<f64, AppErrors> =
-            TradingApiServiceLive::get_quantity_to_sell_everything(INVESTED_PAPER_TRADING_STOCK);
+            live_trading_service()
+                .get_quantity_to_sell_everything(INVESTED_PAPER_TRADING_STOCK.clone()).await;
         println!("{:?}", maybe_stock_data);
         assert!(maybe_stock_data.is_ok())
     }
 
     #[test]
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
     async fn test_get_quantity_to_sell_everything_method_failure() {
         let maybe_stock_data: Result<f64, AppErrors> =
-            TradingApiServiceLive::get_quantity_to_sell_everything(
-                NOT_VALID_STOCK,
-            );
+            live_trading_service()
+                .get_quantity_to_sell_everything(NOT_VALID_STOCK.clone()).await;
         assert!(maybe_stock_data.is_err())
     }
 
     #[test]
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
     async fn test_get_current_investment_success() {
         let maybe_current_investment: Result<StockInvestment, AppErrors> =
-            TradingApiServiceLive::get_current_investment(INVESTED_PAPER_TRADING_STOCK);
+            live_trading_service()
+                .get_current_investment(INVESTED_PAPER_TRADING_STOCK.clone()).await;
         println!("{:?}", maybe_current_investment);
         assert!(maybe_current_investment.is_ok())
     }
 
     #[test]
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
+    async fn test_get_portfolio_success() {
+        let maybe_portfolio: Result<Portfolio, AppErrors> =
+            live_trading_service().get_portfolio().await;
+        println!("{:?}", maybe_portfolio);
+        assert!(maybe_portfolio.is_ok())
+    }
+
+    #[test]
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
+    async fn test_get_account_base_currency_success() {
+        let maybe_currency: Result<Currency, AppErrors> =
+            live_trading_service().get_account_base_currency().await;
+        println!("{:?}", maybe_currency);
+        assert!(maybe_currency.is_ok())
+    }
+
+    #[test]
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
     async fn test_get_current_investment_failure() {
         let maybe_current_investment: Result<StockInvestment, AppErrors> =
-            TradingApiServiceLive::get_current_investment(
-                NOT_VALID_STOCK,
-            );
+            live_trading_service()
+                .get_current_investment(NOT_VALID_STOCK.clone()).await;
         assert!(maybe_current_investment.is_err())
     }
 }
 
+mod timeouts {
+    use crate::errors::AppErrors;
+    use crate::services::with_timeout;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_with_timeout_success() {
+        let maybe_value = with_timeout("Waiting", Duration::from_secs(1), async { Ok(1) }).await;
+        assert_eq!(maybe_value.unwrap(), 1)
+    }
+
+    #[tokio::test]
+    async fn test_with_timeout_elapsed() {
+        let maybe_value: Result<(), AppErrors> =
+            with_timeout("Waiting", Duration::from_millis(10), std::future::pending()).await;
+        let error = maybe_value.unwrap_err();
+        assert!(matches!(error, AppErrors::TimeoutError(_)));
+        assert!(error.to_string().contains("Waiting"))
+    }
+}
+
 mod ai_service {
     use crate::errors::AppErrors;
-    use crate::models::{News, OrderType, StockData, StockPricePerformance};
+    use crate::models::{
+        AdviceAction, News, OrderAdviceResponse, PriceHistory, StockData, StockPricePerformance,
+    };
     use crate::services::AiService;
     use crate::services::AiServiceLive;
-    use crate::services_test::{INVESTED_PAPER_TRADING_STOCK, NOT_VALID_STOCK};
+    use crate::services_test::{INVESTED_PAPER_TRADING_STOCK, LIVE_CONFIG, NOT_VALID_STOCK};
+    use chrono::{NaiveDate, NaiveDateTime};
+    use rust_decimal::Decimal;
     use tokio::test;
 
     #[test]
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
     async fn test_get_order_advice_method_success() {
         let test_stock_data: StockData = StockData {
-            stock: INVESTED_PAPER_TRADING_STOCK,
-            stock_price_performance: vec![StockPricePerformance { date: "2017-12-29".to_string(), open: "1015.8".to_string(), high: "1078.49".to_string(), low: "988.28".to_string() }],
+            stock: INVESTED_PAPER_TRADING_STOCK.clone(),
+            price_history: PriceHistory::default(),
+            stock_price_performance: vec![StockPricePerformance {
+                date: NaiveDate::from_ymd_opt(2017, 12, 29).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+                open: Decimal::new(10158, 1),
+                high: Decimal::new(107849, 2),
+                low: Decimal::new(98828, 2),
+                close: Decimal::new(104640, 2),
+                volume: 1_904_000,
+            }],
             news: vec![News {
                 title: "Google's Fight Against Epic Games' Antitrust Win Hits Roadblock -Judges Tell Search Giant Apple Case Doesn't Apply - Alphabet  ( NASDAQ:GOOG ) , Apple  ( NASDAQ:AAPL ) ".to_string(),
                 summary: "On Monday, a federal appeals court in San Francisco showed skepticism toward Alphabet Inc.'s GOOG GOOGL efforts to overturn a jury verdict in favor of Fortine-maker Epic Games. What Happened: The jury had sided with Epic in 2023, accusing Google of imposing restrictive policies on its Google Play ...".to_string(),
-                time_published: "20250204T025520".to_string()
+                time_published: NaiveDate::from_ymd_opt(2025, 2, 4).unwrap().and_hms_opt(2, 55, 20).unwrap(),
             }],
         };
-        let maybe_order_advice: Result<OrderType, AppErrors> =
-            AiServiceLive::get_order_advice(test_stock_data).await;
+        let maybe_order_advice: Result<OrderAdviceResponse, AppErrors> =
+            AiServiceLive { config: LIVE_CONFIG.clone() }
+                .get_order_advice(test_stock_data).await;
         assert!(maybe_order_advice.is_ok())
     }
 
     #[test]
-    async fn test_get_order_advice_method_failure() {
+    #[ignore = "needs Alpha Vantage, a running IB Gateway and Ollama"]
+    async fn test_get_order_advice_method_hold_without_data() {
         let test_stock_data: StockData = StockData {
-            stock: NOT_VALID_STOCK,
+            stock: NOT_VALID_STOCK.clone(),
+            price_history: PriceHistory::default(),
             stock_price_performance: vec![StockPricePerformance {
-                date: "".to_string(),
-                open: "".to_string(),
-                high: "".to_string(),
-                low: "".to_string(),
+                date: NaiveDateTime::default(),
+                open: Decimal::ZERO,
+                high: Decimal::ZERO,
+                low: Decimal::ZERO,
+                close: Decimal::ZERO,
+                volume: 0,
             }],
             news: vec![News {
                 title: "".to_string(),
                 summary: "".to_string(),
-                time_published: "".to_string(),
+                time_published: NaiveDateTime::default(),
             }],
         };
-        let maybe_order_advice: Result<OrderType, AppErrors> =
-            AiServiceLive::get_order_advice(test_stock_data).await;
+        let maybe_order_advice: Result<OrderAdviceResponse, AppErrors> =
+            AiServiceLive { config: LIVE_CONFIG.clone() }
+                .get_order_advice(test_stock_data).await;
         println!("{:?}", maybe_order_advice);
+        assert_eq!(maybe_order_advice.unwrap().order_advice.action, AdviceAction::Hold)
+    }
+}
+
+mod interactive_brokers_mapping {
+    use super::INVESTED_PAPER_TRADING_STOCK;
+    use crate::models::{
+        Instrument, OptionRight, Order, OrderStyle, OrderType, PriceHistory, PriceInterval,
+        TimeInForce,
+    };
+    use crate::services::{
+        ib_bar_size, ib_contract, ib_duration, ib_instrument, ib_orders, ib_what_to_show,
+        is_listing_of, lot_size,
+    };
+    use ibapi::contracts::{Contract, ContractDetails, SecurityType};
+    use ibapi::market_data::historical::{BarSize, ToDuration, WhatToShow};
+    use rust_decimal::Decimal;
+    use ibapi::orders::Action;
+
+    fn order(style: OrderStyle) -> Order {
+        Order {
+            stock_quantity: 10.0,
+            stock: INVESTED_PAPER_TRADING_STOCK.clone(),
+            order_type: OrderType::Buy,
+            style,
+            time_in_force: TimeInForce::Gtc,
+        }
+    }
+
+    #[test]
+    fn test_ib_orders_stop_limit() {
+        let ib_orders =
+            ib_orders(7, &order(OrderStyle::StopLimit { stop_price: 240.0, limit_price: 241.0 }));
+        assert_eq!(ib_orders.len(), 1);
+        assert_eq!(ib_orders[0].order_id, 7);
+        assert_eq!(ib_orders[0].order_type, "STP LMT");
+        assert_eq!(ib_orders[0].aux_price, Some(240.0));
+        assert_eq!(ib_orders[0].limit_price, Some(241.0));
+        assert_eq!(ib_orders[0].tif, "GTC")
+    }
+
+    #[test]
+    fn test_ib_orders_bracket() {
+        let ib_orders = ib_orders(
+            7,
+            &order(OrderStyle::Bracket {
+                limit_price: 236.0,
+                take_profit_price: 250.0,
+                stop_loss_price: 225.0,
+            }),
+        );
+        let order_ids: Vec<i32> = ib_orders.iter().map(|ib_order| ib_order.order_id).collect();
+        assert_eq!(order_ids, vec![7, 8, 9]);
+        assert_eq!(ib_orders[1].parent_id, 7);
+        assert_eq!(ib_orders[1].action, Action::Sell);
+        assert_eq!(ib_orders[2].aux_price, Some(225.0));
+        assert!(ib_orders[2].transmit);
+        assert!(ib_orders.iter().all(|ib_order| ib_order.tif == "GTC"))
+    }
+
+    #[test]
+    fn test_ib_bar_size_for_intraday_and_monthly() {
+        assert_eq!(ib_bar_size(PriceInterval::FiveMinutes), BarSize::Min5);
+        assert_eq!(ib_bar_size(PriceInterval::SixtyMinutes), BarSize::Hour);
+        assert_eq!(ib_bar_size(PriceInterval::Monthly), BarSize::Month)
+    }
+
+    #[test]
+    fn test_ib_duration_covers_lookback() {
+        let duration = |interval, lookback| ib_duration(PriceHistory { interval, lookback });
+        assert_eq!(duration(PriceInterval::FiveMinutes, Some(78)), 2.days());
+        assert_eq!(duration(PriceInterval::Daily, Some(1)), 4.days());
+        assert_eq!(duration(PriceInterval::Weekly, Some(12)), 12.weeks());
+        assert_eq!(duration(PriceInterval::Monthly, None), 1.years())
+    }
+
+    #[test]
+    fn test_ib_duration_is_capped_per_bar_size() {
+        let duration = |interval, lookback| ib_duration(PriceHistory { interval, lookback });
+        assert_eq!(duration(PriceInterval::OneMinute, None), 1.days());
+        assert_eq!(duration(PriceInterval::FiveMinutes, None), 7.days());
+        assert_eq!(duration(PriceInterval::ThirtyMinutes, None), 30.days());
+        assert_eq!(duration(PriceInterval::Daily, None), 1.years());
+        assert_eq!(duration(PriceInterval::FifteenMinutes, Some(10_000)), 7.days())
+    }
+
+    #[test]
+    fn test_lot_size_from_contract_details() {
+        let contract_details = |min_size, size_increment| ContractDetails {
+            min_size,
+            size_increment,
+            ..ContractDetails::default()
+        };
+        let lot_size = |min_size, size_increment, fractional_shares| {
+            let contract_details = contract_details(min_size, size_increment);
+            let lot_size =
+                lot_size(&Instrument::stock("AAPL"), &contract_details, fractional_shares);
+            (lot_size.min_size, lot_size.size_increment)
+        };
+        assert_eq!(lot_size(0.0001, 0.0001, true), (0.0001, 0.0001));
+        assert_eq!(lot_size(0.0001, 0.0001, false), (1.0, 1.0));
+        assert_eq!(lot_size(100.0, 100.0, false), (100.0, 100.0));
+        assert_eq!(lot_size(0.0, 0.0, true), (1.0, 1.0))
+    }
+
+    #[test]
+    fn test_lot_size_of_crypto_ignores_fractional_shares() {
+        let bitcoin = Instrument::Crypto {
+            symbol: "BTC".to_string(),
+            exchange: "PAXOS".to_string(),
+            currency: "USD".parse().unwrap(),
+        };
+        let contract_details = ContractDetails {
+            min_size: 0.0001,
+            size_increment: 0.0001,
+            ..ContractDetails::default()
+        };
+        let lot_size = lot_size(&bitcoin, &contract_details, false);
+        assert_eq!((lot_size.min_size, lot_size.size_increment), (0.0001, 0.0001))
+    }
+
+    #[test]
+    fn test_lot_size_of_an_option_is_whole_contracts() {
+        let contract_details =
+            ContractDetails { min_size: 0.01, size_increment: 0.01, ..ContractDetails::default() };
+        let lot_size = lot_size(&aapl_call(200), &contract_details, true);
+        assert_eq!((lot_size.min_size, lot_size.size_increment), (1.0, 1.0))
+    }
+
+    #[test]
+    fn test_ib_contract_in_the_listing_currency() {
+        let sap = Instrument::stock("SAP.DE");
+        let contract = ib_contract(&sap).unwrap();
+        assert_eq!((contract.symbol.as_str(), contract.currency.as_str()), ("SAP", "EUR"));
+        assert_eq!(contract.exchange, "SMART");
+        assert_eq!(ib_contract(&INVESTED_PAPER_TRADING_STOCK).unwrap().currency, "USD")
+    }
+
+    #[test]
+    fn test_is_listing_of_compares_symbol_and_currency() {
+        let sap = Instrument::stock("SAP.DE");
+        let us_listing = Contract::stock("SAP");
+        assert!(is_listing_of(&ib_contract(&sap).unwrap(), &sap));
+        assert!(!is_listing_of(&us_listing, &sap));
+        assert!(is_listing_of(&us_listing, &Instrument::stock("SAP")))
+    }
+
+    fn aapl_call(strike: i64) -> Instrument {
+        Instrument::Option {
+            symbol: "AAPL".to_string(),
+            exchange: "SMART".to_string(),
+            currency: "USD".parse().unwrap(),
+            expiry: "20261218".to_string(),
+            strike: Decimal::from(strike),
+            right: OptionRight::Call,
+            multiplier: Decimal::ONE_HUNDRED,
+        }
+    }
+
+    fn es_future(expiry: &str) -> Instrument {
+        Instrument::Future {
+            symbol: "ES".to_string(),
+            exchange: "CME".to_string(),
+            currency: "USD".parse().unwrap(),
+            expiry: expiry.to_string(),
+            multiplier: Decimal::from(50),
+        }
+    }
+
+    #[test]
+    fn test_ib_contract_of_derivatives() {
+        let option = ib_contract(&aapl_call(200)).unwrap();
+        assert_eq!(option.security_type, SecurityType::Option);
+        assert_eq!(option.last_trade_date_or_contract_month, "20261218");
+        assert_eq!((option.strike, option.right.as_str()), (200.0, "C"));
+        assert_eq!(option.multiplier, "100");
+        let future = ib_contract(&es_future("202612")).unwrap();
+        assert_eq!(future.security_type, SecurityType::Future);
+        assert_eq!((future.exchange.as_str(), future.multiplier.as_str()), ("CME", "50"));
+        assert_eq!(future.last_trade_date_or_contract_month, "202612")
+    }
+
+    #[test]
+    fn test_ib_contract_of_forex_and_crypto() {
+        let eur_usd = Instrument::Forex {
+            symbol: "EUR".parse().unwrap(),
+            exchange: "IDEALPRO".to_string(),
+            currency: "USD".parse().unwrap(),
+        };
+        let contract = ib_contract(&eur_usd).unwrap();
+        assert_eq!(contract.security_type, SecurityType::ForexPair);
+        assert_eq!(
+            (contract.symbol.as_str(), contract.currency.as_str(), contract.exchange.as_str()),
+            ("EUR", "USD", "IDEALPRO")
+        );
+        assert_eq!(ib_what_to_show(&eur_usd), WhatToShow::MidPoint);
+        let btc = Instrument::Crypto {
+            symbol: "BTC".to_string(),
+            exchange: "PAXOS".to_string(),
+            currency: "USD".parse().unwrap(),
+        };
+        assert_eq!(ib_contract(&btc).unwrap().security_type, SecurityType::Crypto);
+        assert_eq!(ib_what_to_show(&aapl_call(200)), WhatToShow::Trades)
+    }
+
+    #[test]
+    fn test_is_listing_of_compares_the_contract_terms() {
+        let position =
+            Contract { exchange: String::new(), ..ib_contract(&aapl_call(200)).unwrap() };
+        assert!(is_listing_of(&position, &aapl_call(200)));
+        assert!(!is_listing_of(&position, &aapl_call(210)));
+        assert!(!is_listing_of(&Contract::stock("AAPL"), &aapl_call(200)));
+        assert!(!is_listing_of(&position, &Instrument::stock("AAPL")))
+    }
+
+    #[test]
+    fn test_is_listing_of_a_futures_month() {
+        let position = ib_contract(&es_future("20261218")).unwrap();
+        assert!(is_listing_of(&position, &es_future("202612")));
+        assert!(!is_listing_of(&position, &es_future("202603")))
+    }
+
+    #[test]
+    fn test_ib_instrument_of_a_held_contract() {
+        let option = aapl_call(200);
+        assert_eq!(ib_instrument(&ib_contract(&option).unwrap()).unwrap(), option);
+        let stock = Contract { exchange: String::new(), ..Contract::stock("AAPL") };
+        assert_eq!(ib_instrument(&stock).unwrap(), Instrument::stock("AAPL"))
+    }
+}
+
+mod order_advice_parsing {
+    use crate::errors::AppErrors;
+    use crate::models::AdviceAction;
+    use crate::services::{parse_order_advice, request_order_advice};
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    const BUY_ADVICE: &str = r#"{"action": "BUY", "confidence": 0.7, "rationale": "Earnings beat expectations.", "key_factors": ["Apple Earnings Beat"]}"#;
+
+    #[test]
+    fn test_parse_order_advice_success() {
+        let order_advice = parse_order_advice(BUY_ADVICE).unwrap();
+        assert_eq!(order_advice.action, AdviceAction::Buy);
+        assert_eq!(order_advice.confidence, 0.7);
+        assert_eq!(order_advice.key_factors, vec!["Apple Earnings Beat"])
+    }
+
+    #[test]
+    fn test_parse_order_advice_after_reasoning() {
+        let response = format!(
+            "<think>The news mention SELL-offs, but BUY {{pressure}} dominates.</think>\n```json\n{}\n```",
+            BUY_ADVICE.replace("BUY", "HOLD")
+        );
+        let order_advice = parse_order_advice(&response).unwrap();
+        assert_eq!(order_advice.action, AdviceAction::Hold)
+    }
+
+    #[test]
+    fn test_parse_order_advice_unknown_action() {
+        let maybe_order_advice = parse_order_advice(&BUY_ADVICE.replace("BUY", "STRONG BUY"));
         assert!(maybe_order_advice.is_err())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_order_advice_confidence_out_of_range() {
+        let maybe_order_advice = parse_order_advice(&BUY_ADVICE.replace("0.7", "70"));
+        assert!(maybe_order_advice.unwrap_err().to_string().contains("between 0 and 1"))
+    }
+
+    #[test]
+    fn test_parse_order_advice_without_json() {
+        let maybe_order_advice = parse_order_advice("I would BUY.");
+        assert!(maybe_order_advice.is_err())
+    }
+
+    #[tokio::test]
+    async fn test_request_order_advice_retries_malformed_answers() {
+        let responses = Mutex::new(VecDeque::from(["SELL".to_string(), BUY_ADVICE.to_string()]));
+        let prompts = Mutex::new(vec![]);
+        let maybe_order_advice = request_order_advice(3, "prompt".to_string(), |prompt| {
+            prompts.lock().unwrap().push(prompt);
+            let response = responses.lock().unwrap().pop_front().unwrap();
+            async move { Ok(response) }
+        })
+        .await;
+        let prompts = prompts.into_inner().unwrap();
+        let order_advice_response = maybe_order_advice.unwrap();
+        assert_eq!(order_advice_response.order_advice.action, AdviceAction::Buy);
+        assert_eq!(order_advice_response.raw_response, BUY_ADVICE);
+        assert_eq!(order_advice_response.prompt, prompts[1]);
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].starts_with("prompt\n\nYour previous answer was invalid"))
+    }
+
+    #[tokio::test]
+    async fn test_request_order_advice_gives_up_after_attempts() {
+        let attempts = Mutex::new(0);
+        let maybe_order_advice = request_order_advice(3, "prompt".to_string(), |_| {
+            *attempts.lock().unwrap() += 1;
+            async { Ok("SELL".to_string()) }
+        })
+        .await;
+        assert_eq!(attempts.into_inner().unwrap(), 3);
+        let error = maybe_order_advice.unwrap_err().to_string();
+        assert!(error.contains("after 3 attempts"));
+        assert!(error.ends_with("Last answer: SELL"))
+    }
+
+    #[tokio::test]
+    async fn test_request_order_advice_does_not_retry_ollama_errors() {
+        let attempts = Mutex::new(0);
+        let maybe_order_advice = request_order_advice(3, "prompt".to_string(), |_| {
+            *attempts.lock().unwrap() += 1;
+            async { Err(AppErrors::GetOrderAdviceError("connection refused".to_string())) }
+        })
+        .await;
+        assert_eq!(attempts.into_inner().unwrap(), 1);
+        assert!(maybe_order_advice.is_err())
+    }
+}
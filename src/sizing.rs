@@ -0,0 +1,217 @@
+use crate::errors::AppErrors;
+use crate::models::{Money, StockPricePerformance};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_ATR_PERIOD: usize = 14;
+const DEFAULT_KELLY_FRACTION: f64 = 0.5;
+
+/// How much a buy invests. The request's `amount` caps every strategy, so it
+/// is the most a single order can put in.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum PositionSizing {
+    /// Invests the whole `amount`.
+    #[default]
+    FixedNotional,
+    /// Invests `percent` of the account's net liquidation value.
+    PercentOfEquity { percent: f64 },
+    /// Buys as many shares as make one average true range move cost
+    /// `risk_percent` of the net liquidation value.
+    VolatilityTarget {
+        risk_percent: f64,
+        #[serde(default = "default_atr_period")]
+        atr_period: usize,
+    },
+    /// Invests the Kelly fraction of the net liquidation value, taking the
+    /// model's confidence as the chance to win `payoff_ratio` times what is
+    /// risked. `kelly_fraction` scales it down, 0.5 is half Kelly.
+    Kelly {
+        payoff_ratio: f64,
+        #[serde(default = "default_kelly_fraction")]
+        kelly_fraction: f64,
+    },
+}
+
+fn default_atr_period() -> usize {
+    DEFAULT_ATR_PERIOD
+}
+
+fn default_kelly_fraction() -> f64 {
+    DEFAULT_KELLY_FRACTION
+}
+
+/// What the strategies size a buy from. The equity is only needed by the
+/// strategies that scale with the account and has to be in the currency of
+/// the amount.
+#[derive(Debug, Clone, Copy)]
+pub struct SizingInputs<'a> {
+    pub amount: Money,
+    pub equity: Option<Money>,
+    pub price: f64,
+    pub bars: &'a [StockPricePerformance],
+    pub confidence: f64,
+}
+
+impl PositionSizing {
+    pub fn needs_equity(&self) -> bool {
+        !matches!(self, PositionSizing::FixedNotional)
+    }
+
+    pub fn validate(&self) -> Result<(), AppErrors> {
+        let invalid = |message: String| Err(AppErrors::PositionSizingError(message));
+        match *self {
+            PositionSizing::FixedNotional => Ok(()),
+            PositionSizing::PercentOfEquity { percent } if !(percent > 0.0 && percent <= 100.0) => {
+                invalid(format!(
+                    "The percent of equity has to be above 0 and at most 100: {percent}"
+                ))
+            }
+            PositionSizing::VolatilityTarget { risk_percent, .. }
+                if !(risk_percent > 0.0 && risk_percent <= 100.0) =>
+            {
+                invalid(format!(
+                    "The risk percent has to be above 0 and at most 100: {risk_percent}"
+                ))
+            }
+            PositionSizing::VolatilityTarget { atr_period: 0, .. } => {
+                invalid("The ATR period has to be at least 1".to_string())
+            }
+            PositionSizing::Kelly { payoff_ratio, .. }
+                if !(payoff_ratio > 0.0 && payoff_ratio.is_finite()) =>
+            {
+                invalid(format!("The payoff ratio has to be above 0: {payoff_ratio}"))
+            }
+            PositionSizing::Kelly { kelly_fraction, .. }
+                if !(kelly_fraction > 0.0 && kelly_fraction <= 1.0) =>
+            {
+                invalid(format!(
+                    "The Kelly fraction has to be above 0 and at most 1: {kelly_fraction}"
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The amount a buy invests, rounded to the currency's minor unit.
+    pub fn buy_amount(&self, inputs: &SizingInputs) -> Result<Money, AppErrors> {
+        self.validate()?;
+        let equity = || match inputs.equity {
+            Some(equity) if equity.currency != inputs.amount.currency => {
+                Err(AppErrors::PositionSizingError(format!(
+                    "The net liquidation value of {equity} isn't in the {} of the amount",
+                    inputs.amount.currency
+                )))
+            }
+            Some(equity) if equity.to_f64() > 0.0 => Ok(equity.to_f64()),
+            _ => Err(AppErrors::PositionSizingError(
+                "The sizing strategy needs a positive net liquidation value".to_string(),
+            )),
+        };
+        let notional = match *self {
+            PositionSizing::FixedNotional => return Ok(inputs.amount),
+            PositionSizing::PercentOfEquity { percent } => equity()? * percent / 100.0,
+            PositionSizing::VolatilityTarget { risk_percent, atr_period } => {
+                let atr = average_true_range(inputs.bars, atr_period).ok_or_else(|| {
+                    AppErrors::PositionSizingError(format!(
+                        "The ATR over {atr_period} bars needs {} bars, only {} were given",
+                        atr_period + 1,
+                        inputs.bars.len()
+                    ))
+                })?;
+                if atr <= 0.0 {
+                    return Err(AppErrors::PositionSizingError(
+                        "The price didn't move, the ATR is 0".to_string(),
+                    ));
+                }
+                equity()? * risk_percent / 100.0 / atr * inputs.price
+            }
+            PositionSizing::Kelly { payoff_ratio, kelly_fraction } => {
+                let fraction = kelly(inputs.confidence, payoff_ratio);
+                if fraction <= 0.0 {
+                    return Err(AppErrors::PositionSizingError(format!(
+                        "Kelly sizing doesn't buy at a confidence of {} and a payoff ratio of {payoff_ratio}",
+                        inputs.confidence
+                    )));
+                }
+                equity()? * fraction * kelly_fraction
+            }
+        };
+        let buy_amount = Money::from_f64(notional, inputs.amount.currency)?.round();
+        Ok(if buy_amount.amount > inputs.amount.amount { inputs.amount } else { buy_amount })
+    }
+}
+
+/// The share of the bankroll to bet on a win with `win_probability` that pays
+/// `payoff_ratio` times the stake.
+pub fn kelly(win_probability: f64, payoff_ratio: f64) -> f64 {
+    win_probability - (1.0 - win_probability) / payoff_ratio
+}
+
+/// The simple average of the true ranges of the last `period` bars. Every true
+/// range needs the bar before it, so `None` with `period` bars or fewer.
+pub fn average_true_range(bars: &[StockPricePerformance], period: usize) -> Option<f64> {
+    if period == 0 || bars.len() <= period {
+        return None;
+    }
+    let true_ranges = bars[bars.len() - period - 1..].windows(2).map(|pair| {
+        let previous_close = pair[0].close.to_f64()?;
+        let (high, low) = (pair[1].high.to_f64()?, pair[1].low.to_f64()?);
+        Some((high - low).max((high - previous_close).abs()).max((low - previous_close).abs()))
+    });
+    Some(true_ranges.sum::<Option<f64>>()? / period as f64)
+}
+
+/// The quantity to sell from a position, `fraction` of it rounded down to the
+/// lot size. Selling all of it keeps the exact position.
+pub fn sell_quantity(position: f64, fraction: f64, lot_size: LotSize) -> Result<f64, AppErrors> {
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        return Err(AppErrors::PositionSizingError(format!(
+            "The sell fraction has to be above 0 and at most 1: {fraction}"
+        )));
+    }
+    if fraction == 1.0 {
+        return Ok(position);
+    }
+    lot_size.normalize(position * fraction)
+}
+
+/// The order sizes a venue takes: at least `min_size`, in steps of
+/// `size_increment`. Fractional shares trade in increments below 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LotSize {
+    pub min_size: f64,
+    pub size_increment: f64,
+}
+
+impl LotSize {
+    pub const WHOLE_SHARES: LotSize = LotSize { min_size: 1.0, size_increment: 1.0 };
+    /// The ten thousandths fractional shares, crypto and forex trade in.
+    pub const FRACTIONAL: LotSize = LotSize { min_size: 0.0001, size_increment: 0.0001 };
+
+    /// Rounds the quantity down to the size increment. Fails with a
+    /// `QuantityBelowMinimumError` when that leaves less than one tradable unit.
+    pub fn normalize(&self, quantity: f64) -> Result<f64, AppErrors> {
+        let below_minimum = || {
+            AppErrors::QuantityBelowMinimumError(format!(
+                "A quantity of {quantity} is below the minimum order size of {}",
+                self.min_size
+            ))
+        };
+        let (Some(quantity), Some(size_increment)) =
+            (Decimal::from_f64(quantity), Decimal::from_f64(self.size_increment))
+        else {
+            return Err(below_minimum());
+        };
+        if size_increment <= Decimal::ZERO {
+            return Err(below_minimum());
+        }
+        let normalized =
+            ((quantity / size_increment).floor() * size_increment).to_f64().unwrap_or_default();
+        if normalized <= 0.0 || normalized < self.min_size {
+            return Err(below_minimum());
+        }
+        Ok(normalized)
+    }
+}
@@ -0,0 +1,236 @@
+use crate::models::{Currency, Money, StockPricePerformance};
+use crate::sizing::SizingInputs;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+/// Daily bars from the 1st of March that close at `close` and trade `range`
+/// around it, so every true range is `2 * range`.
+fn bars(count: u32, close: i64, range: i64) -> Vec<StockPricePerformance> {
+    (1..=count)
+        .map(|day| StockPricePerformance {
+            date: NaiveDate::from_ymd_opt(2025, 3, day).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            open: Decimal::from(close),
+            high: Decimal::from(close + range),
+            low: Decimal::from(close - range),
+            close: Decimal::from(close),
+            volume: 1_000,
+        })
+        .collect()
+}
+
+fn dollars(amount: f64) -> Money {
+    Money::from_f64(amount, Currency::USD).unwrap()
+}
+
+fn inputs(bars: &[StockPricePerformance], equity: Option<f64>) -> SizingInputs<'_> {
+    SizingInputs {
+        amount: dollars(50_000.0),
+        equity: equity.map(dollars),
+        price: 100.0,
+        bars,
+        confidence: 0.6,
+    }
+}
+
+mod strategies {
+    use super::{bars, dollars, inputs};
+    use crate::models::{Currency, Money};
+    use crate::sizing::PositionSizing;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_fixed_notional_invests_the_amount() {
+        let amount = PositionSizing::FixedNotional.buy_amount(&inputs(&[], None)).unwrap();
+        assert_eq!(amount.amount, Decimal::from(50_000))
+    }
+
+    #[test]
+    fn test_percent_of_equity() {
+        let sizing = PositionSizing::PercentOfEquity { percent: 5.0 };
+        let amount = sizing.buy_amount(&inputs(&[], Some(100_000.0))).unwrap();
+        assert_eq!(amount.amount, Decimal::from(5_000))
+    }
+
+    #[test]
+    fn test_percent_of_equity_capped_by_the_amount() {
+        let sizing = PositionSizing::PercentOfEquity { percent: 80.0 };
+        let amount = sizing.buy_amount(&inputs(&[], Some(100_000.0))).unwrap();
+        assert_eq!(amount.amount, Decimal::from(50_000))
+    }
+
+    #[test]
+    fn test_percent_of_equity_without_equity() {
+        let sizing = PositionSizing::PercentOfEquity { percent: 5.0 };
+        assert!(sizing.buy_amount(&inputs(&[], Some(0.0))).is_err())
+    }
+
+    #[test]
+    fn test_volatility_target_risks_one_atr() {
+        let bars = bars(20, 100, 2);
+        let sizing = PositionSizing::VolatilityTarget { risk_percent: 1.0, atr_period: 14 };
+        let amount = sizing.buy_amount(&inputs(&bars, Some(100_000.0))).unwrap();
+        // 1,000 at risk over an ATR of 4 is 250 shares at 100.
+        assert_eq!(amount.amount, Decimal::from(25_000))
+    }
+
+    #[test]
+    fn test_volatility_target_needs_enough_bars() {
+        let bars = bars(14, 100, 2);
+        let sizing = PositionSizing::VolatilityTarget { risk_percent: 1.0, atr_period: 14 };
+        assert!(sizing.buy_amount(&inputs(&bars, Some(100_000.0))).is_err())
+    }
+
+    #[test]
+    fn test_volatility_target_without_price_moves() {
+        let bars = bars(20, 100, 0);
+        let sizing = PositionSizing::VolatilityTarget { risk_percent: 1.0, atr_period: 14 };
+        assert!(sizing.buy_amount(&inputs(&bars, Some(100_000.0))).is_err())
+    }
+
+    #[test]
+    fn test_kelly_scaled_by_confidence() {
+        let sizing = PositionSizing::Kelly { payoff_ratio: 2.0, kelly_fraction: 0.5 };
+        let amount = sizing.buy_amount(&inputs(&[], Some(10_000.0))).unwrap();
+        // 0.6 - 0.4 / 2 is 0.4 of the equity, halved.
+        assert_eq!(amount.amount, Decimal::from(2_000))
+    }
+
+    #[test]
+    fn test_kelly_without_an_edge() {
+        let sizing = PositionSizing::Kelly { payoff_ratio: 0.5, kelly_fraction: 0.5 };
+        assert!(sizing.buy_amount(&inputs(&[], Some(10_000.0))).is_err())
+    }
+
+    #[test]
+    fn test_buy_amount_rounds_to_cents() {
+        let sizing = PositionSizing::PercentOfEquity { percent: 1.0 };
+        let amount = sizing.buy_amount(&inputs(&[], Some(1_234.567))).unwrap();
+        assert_eq!(amount.amount, Decimal::new(1235, 2))
+    }
+
+    #[test]
+    fn test_equity_in_another_currency() {
+        let sizing = PositionSizing::PercentOfEquity { percent: 5.0 };
+        let euros = Money::new(Decimal::from(100_000), "EUR".parse::<Currency>().unwrap()).unwrap();
+        let inputs = super::SizingInputs { equity: Some(euros), ..inputs(&[], None) };
+        assert!(sizing.buy_amount(&inputs).is_err());
+        assert_eq!(inputs.amount, dollars(50_000.0))
+    }
+}
+
+mod parse {
+    use crate::sizing::PositionSizing;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_sizing_defaults() {
+        let maybe_sizing: PositionSizing =
+            serde_json::from_value(json!({ "strategy": "kelly", "payoff_ratio": 1.5 })).unwrap();
+        assert_eq!(maybe_sizing, PositionSizing::Kelly { payoff_ratio: 1.5, kelly_fraction: 0.5 })
+    }
+
+    #[test]
+    fn test_parse_sizing_unknown_strategy() {
+        let maybe_sizing =
+            serde_json::from_value::<PositionSizing>(json!({ "strategy": "martingale" }));
+        assert!(maybe_sizing.is_err())
+    }
+
+    #[test]
+    fn test_validate_rejects_out_of_range_settings() {
+        assert!(PositionSizing::PercentOfEquity { percent: 0.0 }.validate().is_err());
+        assert!(PositionSizing::VolatilityTarget { risk_percent: 1.0, atr_period: 0 }
+            .validate()
+            .is_err());
+        assert!(PositionSizing::Kelly { payoff_ratio: 2.0, kelly_fraction: 1.5 }
+            .validate()
+            .is_err())
+    }
+}
+
+mod helpers {
+    use super::bars;
+    use crate::errors::AppErrors;
+    use crate::sizing::{average_true_range, kelly, sell_quantity, LotSize};
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_average_true_range_includes_gaps() {
+        let mut bars = bars(3, 100, 1);
+        // Trades 10 above the previous close, the gap counts in the true range.
+        bars[2].high = Decimal::from(112);
+        bars[2].low = Decimal::from(110);
+        bars[2].close = Decimal::from(111);
+        assert_eq!(average_true_range(&bars, 2), Some((2.0 + 12.0) / 2.0))
+    }
+
+    #[test]
+    fn test_kelly() {
+        assert!((kelly(0.6, 1.0) - 0.2).abs() < 1e-9);
+        assert!(kelly(0.3, 1.0) < 0.0)
+    }
+
+    #[test]
+    fn test_sell_quantity_everything_keeps_the_exact_position() {
+        assert_eq!(sell_quantity(7.25, 1.0, LotSize::WHOLE_SHARES).unwrap(), 7.25)
+    }
+
+    #[test]
+    fn test_sell_quantity_rounds_a_fraction_down() {
+        assert_eq!(sell_quantity(7.0, 0.5, LotSize::WHOLE_SHARES).unwrap(), 3.0)
+    }
+
+    #[test]
+    fn test_sell_quantity_rounds_to_the_lot_size() {
+        let fractional = LotSize { min_size: 0.0001, size_increment: 0.0001 };
+        let round_lots = LotSize { min_size: 100.0, size_increment: 100.0 };
+        assert_eq!(sell_quantity(7.5, 0.5, fractional).unwrap(), 3.75);
+        assert_eq!(sell_quantity(500.0, 0.5, round_lots).unwrap(), 200.0)
+    }
+
+    #[test]
+    fn test_sell_quantity_below_one_share() {
+        assert!(matches!(
+            sell_quantity(1.0, 0.5, LotSize::WHOLE_SHARES),
+            Err(AppErrors::QuantityBelowMinimumError(_))
+        ));
+        assert!(matches!(
+            sell_quantity(7.0, 1.5, LotSize::WHOLE_SHARES),
+            Err(AppErrors::PositionSizingError(_))
+        ))
+    }
+}
+
+mod lot_size {
+    use crate::errors::AppErrors;
+    use crate::sizing::LotSize;
+
+    #[test]
+    fn test_normalize_whole_shares() {
+        assert_eq!(LotSize::WHOLE_SHARES.normalize(3.97).unwrap(), 3.0)
+    }
+
+    #[test]
+    fn test_normalize_fractional_shares() {
+        let lot_size = LotSize { min_size: 0.0001, size_increment: 0.0001 };
+        assert_eq!(lot_size.normalize(2.345678).unwrap(), 2.3456);
+        assert_eq!(lot_size.normalize(0.3).unwrap(), 0.3)
+    }
+
+    #[test]
+    fn test_normalize_board_lots() {
+        let lot_size = LotSize { min_size: 100.0, size_increment: 100.0 };
+        assert_eq!(lot_size.normalize(250.0).unwrap(), 200.0)
+    }
+
+    #[test]
+    fn test_normalize_below_one_tradable_unit() {
+        let board_lots = LotSize { min_size: 100.0, size_increment: 100.0 };
+        assert!(matches!(
+            LotSize::WHOLE_SHARES.normalize(0.6),
+            Err(AppErrors::QuantityBelowMinimumError(_))
+        ));
+        assert!(board_lots.normalize(99.0).is_err());
+        assert!(LotSize::WHOLE_SHARES.normalize(f64::NAN).is_err())
+    }
+}
@@ -0,0 +1,153 @@
+use crate::errors::AppErrors;
+use crate::models::{PriceHistory, RequestedInstrument};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::task::JoinHandle;
+
+const DEFAULT_EVERY_MINUTES: u64 = 5;
+
+/// An instrument the server reanalyzes every `every_minutes`, like `/refresh`
+/// does. Jobs saved with only a `ticker` load as that stock.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct WatchJob {
+    #[serde(flatten)]
+    pub instrument: RequestedInstrument,
+    #[serde(default = "default_every_minutes")]
+    pub every_minutes: u64,
+    #[serde(flatten)]
+    pub price_history: PriceHistory,
+}
+
+fn default_every_minutes() -> u64 {
+    DEFAULT_EVERY_MINUTES
+}
+
+impl WatchJob {
+    /// The name the job is listed and removed by, like `AAPL` or
+    /// `AAPL 20250620 150 C`.
+    pub fn key(&self) -> String {
+        self.instrument.0.to_string()
+    }
+
+    pub fn validate(self) -> Result<Self, AppErrors> {
+        if self.instrument.0.ticker().trim().is_empty() {
+            Err(AppErrors::WatchlistError("The ticker can't be empty".to_string()))
+        } else if self.every_minutes == 0 {
+            Err(AppErrors::WatchlistError(
+                "every_minutes has to be at least 1".to_string(),
+            ))
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+/// A job and its task, which is stopped when the entry is dropped.
+struct WatchEntry {
+    job: WatchJob,
+    task: Option<JoinHandle<()>>,
+}
+
+impl Drop for WatchEntry {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+/// The watched instruments and their running scheduler tasks. Job definitions are
+/// saved to a JSON file on every change so they can be resumed after a restart.
+pub struct Watchlist {
+    path: Option<PathBuf>,
+    entries: Mutex<BTreeMap<String, WatchEntry>>,
+}
+
+impl Watchlist {
+    /// Loads the saved jobs without starting them, see `resume`. A missing file
+    /// is an empty watchlist.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, AppErrors> {
+        let path = path.into();
+        let jobs: Vec<WatchJob> = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|error| {
+                AppErrors::WatchlistError(format!("Invalid watchlist {}: {error}", path.display()))
+            })?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(error) => return Err(AppErrors::WatchlistError(error.to_string())),
+        };
+        let entries =
+            jobs.into_iter().map(|job| (job.key(), WatchEntry { job, task: None })).collect();
+        Ok(Watchlist { path: Some(path), entries: Mutex::new(entries) })
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Watchlist { path: None, entries: Mutex::new(BTreeMap::new()) }
+    }
+
+    pub fn jobs(&self) -> Vec<WatchJob> {
+        self.entries.lock().unwrap().values().map(|entry| entry.job.clone()).collect()
+    }
+
+    /// Starts a task for every job that isn't running yet.
+    pub fn resume(&self, spawn: impl Fn(&WatchJob) -> JoinHandle<()>) {
+        for entry in self.entries.lock().unwrap().values_mut() {
+            if entry.task.is_none() {
+                entry.task = Some(spawn(&entry.job));
+            }
+        }
+    }
+
+    /// Adds the job or replaces the job for the same instrument, stopping its
+    /// task.
+    pub fn insert(&self, job: WatchJob, task: JoinHandle<()>) -> Result<(), AppErrors> {
+        let mut entries = self.entries.lock().unwrap();
+        let key = job.key();
+        let previous = entries.insert(key.clone(), WatchEntry { job, task: Some(task) });
+        if let Err(error) = self.save(&entries) {
+            entries.remove(&key);
+            if let Some(previous) = previous {
+                entries.insert(key, previous);
+            }
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Removes the job with the `key` and stops its task. Returns `None` for
+    /// unknown keys.
+    pub fn remove(&self, key: &str) -> Result<Option<WatchJob>, AppErrors> {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(entry) = entries.remove(key) else {
+            return Ok(None);
+        };
+        if let Err(error) = self.save(&entries) {
+            entries.insert(key.to_string(), entry);
+            return Err(error);
+        }
+        Ok(Some(entry.job.clone()))
+    }
+
+    fn save(&self, entries: &BTreeMap<String, WatchEntry>) -> Result<(), AppErrors> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let jobs: Vec<&WatchJob> = entries.values().map(|entry| &entry.job).collect();
+        let content = serde_json::to_string_pretty(&jobs)
+            .map_err(|error| AppErrors::WatchlistError(error.to_string()))?;
+        // Written next to the watchlist and renamed, so a crash never leaves a
+        // half written file behind.
+        let temporary_path = path.with_extension("json.tmp");
+        fs::write(&temporary_path, content)
+            .and_then(|_| fs::rename(&temporary_path, path))
+            .map_err(|error| {
+                AppErrors::WatchlistError(format!(
+                    "Failed to save the watchlist {}: {error}",
+                    path.display()
+                ))
+            })
+    }
+}
@@ -0,0 +1,166 @@
+use crate::models::{Instrument, PriceHistory, PriceInterval, RequestedInstrument};
+use crate::watchlist::WatchJob;
+use std::path::PathBuf;
+
+fn watch_job(ticker: &str) -> WatchJob {
+    WatchJob {
+        instrument: RequestedInstrument(Instrument::stock(ticker)),
+        every_minutes: 15,
+        price_history: PriceHistory { interval: PriceInterval::Daily, lookback: Some(30) },
+    }
+}
+
+fn watchlist_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("watchlist_{}_{name}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+mod watch_job {
+    use super::watch_job;
+    use crate::models::{Currency, Instrument};
+    use crate::watchlist::WatchJob;
+    use serde_json::json;
+
+    #[test]
+    fn test_watch_job_deserialize_defaults() {
+        let maybe_watch_job = serde_json::from_value::<WatchJob>(json!({ "ticker": "AAPL" }));
+        let watch_job = maybe_watch_job.unwrap();
+        assert_eq!(watch_job.instrument.0, Instrument::stock("AAPL"));
+        assert_eq!(watch_job.every_minutes, 5);
+        assert_eq!(watch_job.price_history, Default::default())
+    }
+
+    #[test]
+    fn test_watch_job_deserialize_instrument() {
+        let maybe_watch_job = serde_json::from_value::<WatchJob>(json!({
+            "instrument": { "security_type": "crypto", "symbol": "BTC" },
+        }));
+        let watch_job = maybe_watch_job.unwrap();
+        assert_eq!(
+            watch_job.instrument.0,
+            Instrument::Crypto {
+                symbol: "BTC".to_string(),
+                exchange: "PAXOS".to_string(),
+                currency: Currency::default(),
+            }
+        );
+        assert_eq!(watch_job.key(), "BTC.USD")
+    }
+
+    #[test]
+    fn test_watch_job_serialize_round_trip() {
+        let serialized_watch_job = serde_json::to_value(watch_job("SAP.DE")).unwrap();
+        let watch_job = serde_json::from_value::<WatchJob>(serialized_watch_job.clone()).unwrap();
+        assert_eq!(serialized_watch_job["instrument"]["currency"], "EUR");
+        assert_eq!(watch_job, super::watch_job("SAP.DE"))
+    }
+
+    #[test]
+    fn test_watch_job_validate_success() {
+        assert!(watch_job("AAPL").validate().is_ok())
+    }
+
+    #[test]
+    fn test_watch_job_validate_empty_ticker() {
+        assert!(watch_job(" ").validate().is_err())
+    }
+
+    #[test]
+    fn test_watch_job_validate_zero_minutes() {
+        let maybe_watch_job = WatchJob { every_minutes: 0, ..watch_job("AAPL") }.validate();
+        assert!(maybe_watch_job.is_err())
+    }
+}
+
+mod watchlist {
+    use super::{watch_job, watchlist_path};
+    use crate::watchlist::Watchlist;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_open_missing_file() {
+        let watchlist = Watchlist::open(watchlist_path("missing")).unwrap();
+        assert!(watchlist.jobs().is_empty())
+    }
+
+    #[test]
+    fn test_open_file_with_tickers_only() {
+        let path = watchlist_path("tickers");
+        let content =
+            r#"[{"ticker": "AAPL", "every_minutes": 15, "interval": "daily", "lookback": 30}]"#;
+        std::fs::write(&path, content).unwrap();
+        let jobs = Watchlist::open(&path).unwrap().jobs();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(jobs, vec![watch_job("AAPL")])
+    }
+
+    #[test]
+    fn test_open_invalid_file() {
+        let path = watchlist_path("invalid");
+        std::fs::write(&path, "[{\"ticker\": 1}]").unwrap();
+        let maybe_watchlist = Watchlist::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(maybe_watchlist.is_err())
+    }
+
+    #[tokio::test]
+    async fn test_jobs_survive_reopening() {
+        let path = watchlist_path("reopen");
+        let watchlist = Watchlist::open(&path).unwrap();
+        watchlist.insert(watch_job("MSFT"), tokio::spawn(async {})).unwrap();
+        watchlist.insert(watch_job("AAPL"), tokio::spawn(async {})).unwrap();
+        watchlist.remove("MSFT").unwrap();
+        let reopened_jobs = Watchlist::open(&path).unwrap().jobs();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reopened_jobs, vec![watch_job("AAPL")])
+    }
+
+    #[tokio::test]
+    async fn test_insert_replaces_job_for_same_ticker() {
+        let watchlist = Watchlist::in_memory();
+        let previous_task = tokio::spawn(std::future::pending::<()>());
+        let previous_abort_handle = previous_task.abort_handle();
+        watchlist.insert(watch_job("AAPL"), previous_task).unwrap();
+        let mut job = watch_job("AAPL");
+        job.every_minutes = 60;
+        watchlist.insert(job.clone(), tokio::spawn(async {})).unwrap();
+        tokio::task::yield_now().await;
+        assert_eq!(watchlist.jobs(), vec![job]);
+        assert!(previous_abort_handle.is_finished())
+    }
+
+    #[tokio::test]
+    async fn test_remove_stops_task() {
+        let watchlist = Watchlist::in_memory();
+        let task = tokio::spawn(std::future::pending::<()>());
+        let abort_handle = task.abort_handle();
+        watchlist.insert(watch_job("AAPL"), task).unwrap();
+        let removed_job = watchlist.remove("AAPL").unwrap();
+        tokio::task::yield_now().await;
+        assert_eq!(removed_job, Some(watch_job("AAPL")));
+        assert!(abort_handle.is_finished())
+    }
+
+    #[test]
+    fn test_remove_unknown_ticker() {
+        let watchlist = Watchlist::in_memory();
+        assert_eq!(watchlist.remove("AAPL").unwrap(), None)
+    }
+
+    #[tokio::test]
+    async fn test_resume_starts_each_saved_job_once() {
+        let path = watchlist_path("resume");
+        Watchlist::open(&path).unwrap().insert(watch_job("AAPL"), tokio::spawn(async {})).unwrap();
+        let watchlist = Watchlist::open(&path).unwrap();
+        let spawned = AtomicUsize::new(0);
+        let spawn = |_: &_| {
+            spawned.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async {})
+        };
+        watchlist.resume(spawn);
+        watchlist.resume(spawn);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(spawned.load(Ordering::SeqCst), 1)
+    }
+}